@@ -0,0 +1,56 @@
+use std::error::Error;
+
+use somedb::{db::Database, entity, entity_meta::FORMAT_VERSION, schema::FieldType, value::Value};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Person {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    nicknames: Vec<String>,
+}
+
+#[cfg_attr(test, test)]
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("catalog_sdb/", true)?;
+
+    let person = Person {
+        id: 0,
+        name: "Alan".into(),
+        nicknames: vec!["Al".into()],
+    };
+
+    db.store(person.clone())?;
+    db.store(person.clone())?;
+
+    // the catalog can be read without knowing the rust types
+    let db = Database::new("catalog_sdb/", false)?;
+    let tables = db.catalog()?;
+
+    assert_eq!(tables.len(), 1);
+
+    let table = &tables[0];
+    let schema = table.schema.as_ref().unwrap();
+
+    assert_eq!(table.name(), "Person");
+    assert_eq!(table.row_count, 2);
+    assert_eq!(table.last_id, Some(Value::U32(2)));
+    assert_eq!(table.format_version, Some(FORMAT_VERSION));
+    assert_eq!(schema.id_field, "id");
+    assert_eq!(
+        schema.field("nicknames").unwrap().ty,
+        FieldType::List(Box::new(FieldType::String))
+    );
+
+    for table in tables {
+        println!(
+            "{}: {} rows, {} bytes",
+            table.name(),
+            table.row_count,
+            table.file_size
+        );
+    }
+
+    Ok(())
+}
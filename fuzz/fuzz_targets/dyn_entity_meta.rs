@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use somedb::{
+    byte_reader::ByteReader,
+    entity_meta::DynEntityMeta,
+    schema::{FieldSchema, FieldType, TableSchema},
+};
+
+fuzz_target!(|data: &[u8]| {
+    let schema = TableSchema {
+        name: "Fuzzed".to_string(),
+        type_name: "fuzz::Fuzzed".to_string(),
+        id_field: "id".to_string(),
+        generate_id: true,
+        fields: vec![
+            FieldSchema::new("id", FieldType::U32),
+            FieldSchema::new("name", FieldType::String),
+            FieldSchema::new("values", FieldType::List(Box::new(FieldType::I64))),
+        ],
+    };
+
+    let _ = ByteReader::new(data)
+        .reader_for_block()
+        .and_then(|reader| DynEntityMeta::decoded(&schema, reader));
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use somedb::{byte_reader::ByteReader, entity, entity_meta::EntityMeta, storable::Storable};
+
+#[entity]
+struct Fuzzed {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    values: Vec<i64>,
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ByteReader::new(data)
+        .reader_for_block()
+        .and_then(EntityMeta::<Fuzzed>::decoded);
+});
@@ -3,7 +3,7 @@ use proc_macro2::Span;
 use quote::{ToTokens, quote};
 use syn::{Field, Ident};
 
-#[proc_macro_derive(Storable)]
+#[proc_macro_derive(Storable, attributes(default, storable))]
 pub fn derive_storable(item: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(item as syn::DeriveInput);
 
@@ -21,6 +21,48 @@ pub fn derive_storable(item: TokenStream) -> TokenStream {
             syn::Fields::Named(n) => {
                 let names: Vec<_> = n.named.iter().map(|n| n.ident.as_ref().unwrap()).collect();
                 let types: Vec<_> = n.named.iter().map(|n| n.ty.clone()).collect();
+                let defaults: Vec<_> = n.named.iter().map(default_value).collect();
+                // fields stored with serde are wrapped so they can be read and hashed like other storables
+                let with_serde: Vec<_> = n.named.iter().map(stored_with_serde).collect();
+                let stored_types: Vec<_> = types
+                    .iter()
+                    .zip(&with_serde)
+                    .map(|(ty, serde)| {
+                        if *serde {
+                            quote! { somedb::serde::Serde<#ty> }
+                        } else {
+                            ty.to_token_stream()
+                        }
+                    })
+                    .collect();
+                let encodes = names.iter().zip(&with_serde).map(|(name, serde)| {
+                    if *serde {
+                        quote! { somedb::serde::encoded(&self.#name) }
+                    } else {
+                        quote! { self.#name.encoded() }
+                    }
+                });
+
+                // fields with a default value can be missing in data written before they were added.
+                let required = defaults.iter().take_while(|d| d.is_none()).count();
+                if defaults[required..].iter().any(|d| d.is_none()) {
+                    panic!("fields with a default value must come after all other fields");
+                }
+                let reads = stored_types.iter().zip(&with_serde).zip(&defaults).map(
+                    |((ty, serde), default)| {
+                        let read = if *serde {
+                            quote! { reader.read::<#ty>()?.0 }
+                        } else {
+                            quote! { reader.read::<#ty>()? }
+                        };
+                        match default {
+                            Some(default) => quote! {
+                                if reader.is_at_end() { #default } else { #read }
+                            },
+                            None => read,
+                        }
+                    },
+                );
 
                 quote! {
                     #[automatically_derived]
@@ -28,26 +70,35 @@ pub fn derive_storable(item: TokenStream) -> TokenStream {
                         fn type_hash() -> somedb::type_hash::TypeHash {
                             use somedb::type_hash::TypeHash;
                             let field_names = &[#(stringify!(#names)),*];
-                            let field_types = &[#(#types::type_hash()),*];
+                            let field_types = &[#(<#stored_types as somedb::storable::Storable>::type_hash()),*];
 
                             unsafe {
-                                TypeHash::new(
+                                TypeHash::new_extensible(
                                     std::any::type_name::<Self>(),
                                     field_names,
                                     field_types,
+                                    #required,
                                 )
                             }
                         }
 
+                        fn field_type() -> somedb::schema::FieldType {
+                            use somedb::schema::{FieldSchema, FieldType};
+                            FieldType::Struct {
+                                name: stringify!(#ident).to_string(),
+                                fields: vec![#(FieldSchema::new(stringify!(#names), <#stored_types as somedb::storable::Storable>::field_type())),*],
+                            }
+                        }
+
                         fn inner_encoded(&self) -> Vec<u8> {
                             let mut bytes = Vec::new();
-                            #(bytes.append(&mut self.#names.encoded());)*
+                            #(bytes.append(&mut #encodes);)*
 
                             bytes
                         }
 
                         fn decoded(mut reader: somedb::byte_reader::ByteReader) -> somedb::db::DbResult<Self> {
-                            #(let #names = #types::decoded(reader.reader_for_block())?;)*
+                            #(let #names = #reads;)*
                             Ok(#ident {
                                 #(#names),*
                             })
@@ -64,7 +115,62 @@ pub fn derive_storable(item: TokenStream) -> TokenStream {
     }
 }
 
-#[proc_macro_derive(Entity, attributes(entity_id))]
+/// Whether a field is declared with `#[storable(with = "serde")]`.
+fn stored_with_serde(field: &Field) -> bool {
+    let Some(attr) = field.attrs.iter().find(|a| a.path().is_ident("storable")) else {
+        return false;
+    };
+    let mut serde = false;
+    attr.parse_nested_meta(|m| {
+        if m.path.is_ident("with") && m.value()?.parse::<syn::LitStr>()?.value() == "serde" {
+            serde = true;
+            Ok(())
+        } else {
+            Err(m.error("invalid storable attribute, expected #[storable(with = \"serde\")]"))
+        }
+    })
+    .unwrap();
+    serde
+}
+
+/// The default value of a field declared with `#[default]` or `#[default = expr]`.
+fn default_value(field: &Field) -> Option<proc_macro2::TokenStream> {
+    let attr = field.attrs.iter().find(|a| a.path().is_ident("default"))?;
+    match &attr.meta {
+        syn::Meta::Path(_) => Some(quote! { Default::default() }),
+        // string literals are converted so `#[default = "text"]` works for String fields
+        syn::Meta::NameValue(syn::MetaNameValue {
+            value:
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(text),
+                    ..
+                }),
+            ..
+        }) => Some(quote! { #text.into() }),
+        syn::Meta::NameValue(v) => Some(v.value.to_token_stream()),
+        // attribute values must be literals, other expressions are written as `#[default(expr)]`
+        syn::Meta::List(list) => {
+            let expr: syn::Expr = list
+                .parse_args()
+                .expect("invalid default attribute, expected #[default(expr)]");
+            Some(expr.to_token_stream())
+        }
+    }
+}
+
+#[proc_macro_derive(
+    Entity,
+    attributes(
+        entity_id,
+        entity_hooks,
+        not_empty,
+        range,
+        max_len,
+        unique,
+        check,
+        fulltext
+    )
+)]
 pub fn derive_entity(item: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(item as syn::DeriveInput);
 
@@ -135,7 +241,7 @@ pub fn derive_entity(item: TokenStream) -> TokenStream {
                     names = names
                         .into_iter()
                         .enumerate()
-                        .filter(|&(i, _)| (!all_ty.contains(&i)))
+                        .filter(|&(i, _)| !all_ty.contains(&i))
                         .map(|(_, n)| n)
                         .collect();
                 }
@@ -146,11 +252,9 @@ pub fn derive_entity(item: TokenStream) -> TokenStream {
 
                     let resolve_impl = quote! {
                         impl somedb::gen_query::ResolveAttrExpr<#ty> for #ident {
-                            fn resolve(field_name: &'static str, row_id: Self::Id, db: &somedb::db::Database) -> #ty {
-                                let entity = db.find_by_id::<Self>(row_id).unwrap().unwrap();
-
+                            fn resolve(field_name: &'static str, row: &Self) -> #ty {
                                 match field_name {
-                                    #(stringify!(#names) => entity.#names,)*
+                                    #(stringify!(#names) => row.#names.clone(),)*
                                     _ => panic!("unknown field name: {field_name}")
                                 }
                             }
@@ -180,12 +284,32 @@ pub fn derive_entity(item: TokenStream) -> TokenStream {
                 let id_field_name = id_field.ident.clone().unwrap();
                 let id_field_type = id_field.ty.clone();
 
+                // entities with custom hooks implement EntityHooks themselves
+                let hooks = if input
+                    .attrs
+                    .iter()
+                    .any(|a| a.path().is_ident("entity_hooks"))
+                {
+                    quote! {}
+                } else {
+                    quote! {
+                        #[automatically_derived]
+                        impl somedb::entity::EntityHooks for #ident {}
+                    }
+                };
+
+                let (checks, declared, unique_fields) = constraints(n.named.iter());
+                let fulltext_fields = fulltext_fields(n.named.iter());
+
                 quote! {
                     #[automatically_derived]
                     impl somedb::entity::Entity for #ident {
                         type Id = #id_field_type;
                         type ExprBase = #expr_base_name;
                         #generate_id;
+                        const ID_FIELD: &'static str = stringify!(#id_field_name);
+                        const FULLTEXT_FIELDS: &'static [&'static str] =
+                            &[#(stringify!(#fulltext_fields)),*];
 
                         fn get_id(&self) -> #id_field_type {
                             self.#id_field_name
@@ -194,8 +318,30 @@ pub fn derive_entity(item: TokenStream) -> TokenStream {
                         fn set_id(&mut self, id: Self::Id) {
                             self.#id_field_name = id;
                         }
+
+                        fn check_constraints(&self) -> Result<(), (&'static str, String)> {
+                            #(#checks)*
+                            Ok(())
+                        }
+
+                        fn unique_values(&self) -> Vec<(&'static str, Vec<u8>)> {
+                            vec![#((
+                                stringify!(#unique_fields),
+                                somedb::storable::Storable::encoded(&self.#unique_fields),
+                            )),*]
+                        }
+
+                        fn field_constraints() -> Vec<somedb::schema::FieldConstraint> {
+                            use somedb::{
+                                schema::{Constraint, FieldConstraint},
+                                value::Value,
+                            };
+                            vec![#(#declared),*]
+                        }
                     }
 
+                    #hooks
+
                     #(#resolve_impls)*
 
                     #expr_base
@@ -212,15 +358,140 @@ pub fn derive_entity(item: TokenStream) -> TokenStream {
 
 #[proc_macro_attribute]
 pub fn entity(
-    _metadata: proc_macro::TokenStream,
+    metadata: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
+    let mut options = vec![];
+    let parser = syn::meta::parser(|m| {
+        if m.path.is_ident("hooks") {
+            options.push(quote! { #[entity_hooks] });
+            Ok(())
+        } else {
+            Err(m.error("invalid entity option"))
+        }
+    });
+    syn::parse_macro_input!(metadata with parser);
+
     let input: proc_macro2::TokenStream = input.into();
     // FIXME: Clone should only be derived if it isn't already since
     //        this is really annoying right now.
     let output = quote! {
         #[derive(Clone, somedb::Storable, somedb::Entity)]
+        #(#options)*
         #input
     };
     output.into()
 }
+
+/// Generates the checks for the constraint attributes of the fields, the
+/// constraints persisted with the schema and collects the fields marked
+/// as `#[unique]`.
+fn constraints<'a>(
+    fields: impl Iterator<Item = &'a Field>,
+) -> (
+    Vec<proc_macro2::TokenStream>,
+    Vec<proc_macro2::TokenStream>,
+    Vec<&'a Ident>,
+) {
+    let mut checks = vec![];
+    let mut declared = vec![];
+    let mut unique_fields = vec![];
+
+    for field in fields {
+        let name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let fail = |message: proc_macro2::TokenStream| {
+            quote! { return Err((stringify!(#name), #message)); }
+        };
+        let mut declare = |constraint: proc_macro2::TokenStream| {
+            declared.push(quote! {
+                FieldConstraint::new(stringify!(#name), Constraint::#constraint)
+            });
+        };
+
+        for attr in &field.attrs {
+            let path = attr.path();
+            if path.is_ident("not_empty") {
+                let fail = fail(quote! { "must not be empty".to_string() });
+                checks.push(quote! {
+                    if self.#name.is_empty() {
+                        #fail
+                    }
+                });
+                declare(quote! { NotEmpty });
+            } else if path.is_ident("range") {
+                attr.parse_nested_meta(|m| {
+                    let bound: syn::Expr = m.value()?.parse()?;
+                    if m.path.is_ident("min") {
+                        let fail = fail(quote! { format!("must be at least {}", #bound) });
+                        checks.push(quote! {
+                            if self.#name < #bound {
+                                #fail
+                            }
+                        });
+                        declare(
+                            quote! { Min(Value::of::<#ty>(&(#bound)).expect("a valid bound")) },
+                        );
+                    } else if m.path.is_ident("max") {
+                        let fail = fail(quote! { format!("must be at most {}", #bound) });
+                        checks.push(quote! {
+                            if self.#name > #bound {
+                                #fail
+                            }
+                        });
+                        declare(
+                            quote! { Max(Value::of::<#ty>(&(#bound)).expect("a valid bound")) },
+                        );
+                    } else {
+                        return Err(m.error("expected `min` or `max`"));
+                    }
+                    Ok(())
+                })
+                .expect("invalid range attribute, expected #[range(min = .., max = ..)]");
+            } else if path.is_ident("max_len") {
+                let max: syn::Expr = attr
+                    .parse_args()
+                    .expect("invalid max_len attribute, expected #[max_len(..)]");
+                let fail = fail(quote! { format!("must not be longer than {}", #max) });
+                checks.push(quote! {
+                    if self.#name.len() > #max {
+                        #fail
+                    }
+                });
+                declare(quote! { MaxLen((#max) as u64) });
+            } else if path.is_ident("check") {
+                let check: syn::Path = attr
+                    .parse_args()
+                    .expect("invalid check attribute, expected #[check(function)]");
+                let fail = fail(quote! {
+                    format!("failed check {}", stringify!(#check))
+                });
+                checks.push(quote! {
+                    if !#check(&self.#name) {
+                        #fail
+                    }
+                });
+                declare(quote! { Check(stringify!(#check).to_string()) });
+            } else if path.is_ident("unique") {
+                unique_fields.push(name);
+                declare(quote! { Unique });
+            }
+        }
+    }
+
+    (checks, declared, unique_fields)
+}
+
+/// Collects the fields marked as `#[fulltext]`.
+fn fulltext_fields<'a>(fields: impl Iterator<Item = &'a Field>) -> Vec<&'a Ident> {
+    fields
+        .filter(|field| field.attrs.iter().any(|a| a.path().is_ident("fulltext")))
+        .map(|field| {
+            match &field.ty {
+                syn::Type::Path(ty) if ty.path.is_ident("String") => {}
+                _ => panic!("#[fulltext] is only supported on String fields"),
+            }
+            field.ident.as_ref().unwrap()
+        })
+        .collect()
+}
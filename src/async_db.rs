@@ -0,0 +1,260 @@
+//! An async api for applications running on tokio.
+//!
+//! The [AsyncDatabase] waits for the locks of a table with tokio timers
+//! instead of blocking the thread, and runs the file operations on the
+//! blocking thread pool of the runtime, so the executor is never stalled.
+//! The runtime needs to have the time driver enabled.
+//!
+//! ```rust
+//! use somedb::{async_db::AsyncDatabase, entity};
+//!
+//! #[entity]
+//! #[derive(Debug, PartialEq)]
+//! struct Task {
+//!     #[entity_id(auto_generate)]
+//!     id: u32,
+//!     title: String,
+//! }
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let db = AsyncDatabase::new("async_doc_sdb/", true).await?;
+//! let task = db.store(Task { id: 0, title: "write docs".into() }).await?;
+//! assert_eq!(db.find_by_id::<Task>(task.id).await?, Some(task));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    panic,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::task::{JoinHandle, spawn_blocking};
+
+use crate::{
+    db::{
+        Database, DbError, DbResult, SLEEP_TIME, someone_has_rlock, someone_has_wlock, table_file,
+    },
+    entity::Entity,
+    entity_meta::EntityStream,
+    query::next_entity,
+    type_hash::TypeHash,
+};
+
+/// The number of entities a [QueryStream] reads at once.
+const BATCH_SIZE: usize = 64;
+
+/// A [Database] that can be used from async code.
+///
+/// Cloning it creates another handle to the same database.
+#[derive(Debug, Clone)]
+pub struct AsyncDatabase {
+    db: Arc<Mutex<Database>>,
+    db_dir: PathBuf,
+}
+
+impl AsyncDatabase {
+    pub async fn new(db_dir: impl AsRef<Path>, clear: bool) -> DbResult<Self> {
+        let db_dir = db_dir.as_ref().to_path_buf();
+        let dir = db_dir.clone();
+        let db = blocking(move || Database::new(dir, clear)).await?;
+        Ok(Self {
+            db: Arc::new(Mutex::new(db)),
+            db_dir,
+        })
+    }
+
+    /// Runs `f` with the synchronous database on the blocking thread pool.
+    ///
+    /// This can be used for everything that has no async equivalent.
+    /// Unlike the other methods it blocks a thread while waiting for locks.
+    pub async fn with<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Database) -> R + Send + 'static,
+    ) -> R {
+        let db = self.db.clone();
+        blocking(move || f(&mut db.lock().unwrap_or_else(|e| e.into_inner()))).await
+    }
+
+    /// Waits until the table can be locked without blocking the thread.
+    async fn wait_for_lock(&self, type_hash: TypeHash, write: bool) {
+        let file = table_file(&self.db_dir, &type_hash);
+        while someone_has_wlock(&file) || (write && someone_has_rlock(&file)) {
+            tokio::time::sleep(SLEEP_TIME).await;
+        }
+    }
+
+    async fn read<T: Entity, R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Database) -> R + Send + 'static,
+    ) -> R {
+        self.wait_for_lock(T::type_hash(), false).await;
+        self.with(f).await
+    }
+
+    async fn write<T: Entity, R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Database) -> R + Send + 'static,
+    ) -> R {
+        self.wait_for_lock(T::type_hash(), true).await;
+        self.with(f).await
+    }
+
+    pub async fn store<T: Entity + Send + 'static>(&self, entity: T) -> DbResult<T> {
+        self.write::<T, _>(move |db| db.store(entity)).await
+    }
+
+    pub async fn write_all<T: Entity + Send + 'static>(&self, entities: Vec<T>) -> DbResult<()> {
+        self.write::<T, _>(move |db| db.write_all(entities)).await
+    }
+
+    pub async fn update_entity<T: Entity + Send + 'static>(&self, entity: T) -> DbResult<()> {
+        self.write::<T, _>(move |db| db.update_entity(entity)).await
+    }
+
+    pub async fn delete_entity_by_id<T: Entity>(&self, id: T::Id) -> DbResult<()>
+    where
+        T::Id: Send + 'static,
+    {
+        self.write::<T, _>(move |db| db.delte_entity_by_id::<T>(id))
+            .await
+    }
+
+    pub async fn read_all<T: Entity + Send + 'static>(&self) -> DbResult<Vec<T>> {
+        self.read::<T, _>(|db| db.read_all()).await
+    }
+
+    pub async fn find_by_id<T: Entity + Send + 'static>(&self, id: T::Id) -> DbResult<Option<T>>
+    where
+        T::Id: Send + 'static,
+    {
+        self.read::<T, _>(move |db| db.find_by_id(id)).await
+    }
+
+    /// Streams the entities of a table like [Database::query].
+    ///
+    /// The table stays locked for reading until the stream
+    /// ended or was dropped.
+    pub async fn query<T: Entity + Send + 'static>(&self) -> DbResult<QueryStream<T>> {
+        let rows = self.read::<T, _>(|db| db.stream_table::<T>()).await?;
+        Ok(QueryStream {
+            buffered: VecDeque::new(),
+            state: StreamState::Idle(Box::new(rows)),
+        })
+    }
+}
+
+/// Runs `f` on the blocking thread pool and resumes its panics.
+async fn blocking<R: Send + 'static>(f: impl FnOnce() -> R + Send + 'static) -> R {
+    match spawn_blocking(f).await {
+        Ok(res) => res,
+        Err(e) => panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// The result of reading a batch of entities.
+struct Batch<T> {
+    rows: Box<EntityStream>,
+    /// The entities read before the batch was full or an error occurred.
+    entities: Vec<T>,
+    error: Option<DbError>,
+    finished: bool,
+}
+
+enum StreamState<T> {
+    Idle(Box<EntityStream>),
+    Reading(JoinHandle<Batch<T>>),
+    /// Reading failed, the error is returned after the entities read before it.
+    Failed(DbError),
+    Done,
+}
+
+/// The entities of a table read in batches on the blocking thread pool.
+pub struct QueryStream<T: Entity> {
+    buffered: VecDeque<T>,
+    state: StreamState<T>,
+}
+
+// the state is never pinned
+impl<T: Entity> Unpin for QueryStream<T> {}
+
+impl<T: Entity + Send + 'static> QueryStream<T> {
+    /// Waits for the next entity, `None` once all entities were read.
+    pub async fn next(&mut self) -> Option<DbResult<T>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+fn read_batch<T: Entity>(mut rows: Box<EntityStream>) -> Batch<T> {
+    let mut entities = Vec::with_capacity(BATCH_SIZE);
+    while entities.len() < BATCH_SIZE {
+        match next_entity(&mut rows) {
+            Ok(Some(entity)) => entities.push(entity),
+            Ok(None) => {
+                return Batch {
+                    rows,
+                    entities,
+                    error: None,
+                    finished: true,
+                };
+            }
+            Err(e) => {
+                return Batch {
+                    rows,
+                    entities,
+                    error: Some(e),
+                    finished: true,
+                };
+            }
+        }
+    }
+    Batch {
+        rows,
+        entities,
+        error: None,
+        finished: false,
+    }
+}
+
+impl<T: Entity + Send + 'static> Stream for QueryStream<T> {
+    type Item = DbResult<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(entity) = self.buffered.pop_front() {
+                return Poll::Ready(Some(Ok(entity)));
+            }
+
+            match std::mem::replace(&mut self.state, StreamState::Done) {
+                StreamState::Idle(rows) => {
+                    self.state = StreamState::Reading(spawn_blocking(move || read_batch(rows)));
+                }
+                StreamState::Reading(mut handle) => {
+                    let batch = match Pin::new(&mut handle).poll(cx) {
+                        Poll::Pending => {
+                            self.state = StreamState::Reading(handle);
+                            return Poll::Pending;
+                        }
+                        Poll::Ready(Ok(batch)) => batch,
+                        Poll::Ready(Err(e)) => panic::resume_unwind(e.into_panic()),
+                    };
+                    self.buffered.extend(batch.entities);
+                    if let Some(e) = batch.error {
+                        self.state = StreamState::Failed(e);
+                    } else if !batch.finished {
+                        self.state = StreamState::Idle(batch.rows);
+                    }
+                }
+                StreamState::Failed(e) => return Poll::Ready(Some(Err(e))),
+                StreamState::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
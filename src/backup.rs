@@ -0,0 +1,158 @@
+//! Backups of a [Database](crate::db::Database) that can be taken while it is in use.
+//!
+//! A backup directory contains one directory per snapshot named after the
+//! time it was taken in milliseconds since the unix epoch:
+//!
+//! ```text
+//! backups/
+//!     1760000000000/
+//!         manifest
+//!         <type hash>.sdb
+//!         <type hash>.schema
+//!     1760000060000/
+//!         manifest
+//!         <type hash>.sdb
+//! ```
+//!
+//! The manifest of a snapshot lists every table of the database at that time
+//! and the snapshot that holds its data. Incremental snapshots only contain the
+//! tables that changed since the previous snapshot and refer to older
+//! snapshots for the rest.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    byte_reader::ByteReader,
+    db::{DbError, DbResult},
+    schema::{FieldSchema, FieldType},
+    storable::Storable,
+    type_hash::TypeHash,
+};
+
+const MANIFEST: &str = "manifest";
+
+/// Describes a single snapshot in a backup directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// The time the snapshot was taken.
+    pub time: SystemTime,
+    pub path: PathBuf,
+    /// The number of tables in the database at the time of the snapshot.
+    pub tables: usize,
+    /// The number of tables whose data is stored in this snapshot.
+    pub copied_tables: usize,
+    /// The number of bytes stored in this snapshot.
+    pub copied_bytes: u64,
+}
+
+/// A table listed in the manifest of a snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ManifestEntry {
+    pub type_hash: TypeHash,
+    /// The name of the snapshot directory that holds the data of the table.
+    pub snapshot: u64,
+    /// The checksum of the whole table file.
+    pub checksum: u32,
+    pub size: u64,
+}
+
+unsafe impl Storable for ManifestEntry {
+    fn type_hash() -> TypeHash {
+        unsafe { TypeHash::from_str("somedb::ManifestEntry") }
+    }
+
+    fn field_type() -> FieldType {
+        FieldType::Struct {
+            name: "ManifestEntry".to_string(),
+            fields: vec![
+                FieldSchema::new("type_hash", FieldType::String),
+                FieldSchema::new("snapshot", FieldType::U64),
+                FieldSchema::new("checksum", FieldType::U32),
+                FieldSchema::new("size", FieldType::U64),
+            ],
+        }
+    }
+
+    fn inner_encoded(&self) -> Vec<u8> {
+        let mut res = self.type_hash.encode().encoded();
+        res.append(&mut self.snapshot.encoded());
+        res.append(&mut self.checksum.encoded());
+        res.append(&mut self.size.encoded());
+        res
+    }
+
+    fn decoded(mut reader: ByteReader) -> DbResult<Self> {
+        Ok(Self {
+            type_hash: TypeHash::decode(&reader.read::<String>()?),
+            snapshot: reader.read::<u64>()?,
+            checksum: reader.read::<u32>()?,
+            size: reader.read::<u64>()?,
+        })
+    }
+}
+
+pub(crate) fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+pub(crate) fn snapshot_dir(backup_dir: &Path, snapshot: u64) -> PathBuf {
+    backup_dir.join(snapshot.to_string())
+}
+
+/// Lists the names of all snapshots in a backup directory, oldest first.
+pub(crate) fn snapshot_names(backup_dir: &Path) -> DbResult<Vec<u64>> {
+    if !backup_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut names: Vec<u64> = fs::read_dir(backup_dir)
+        .map_err(DbError::io_at(backup_dir))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?.parse().ok()?;
+            entry.path().join(MANIFEST).exists().then_some(name)
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+pub(crate) fn read_manifest(backup_dir: &Path, snapshot: u64) -> DbResult<Vec<ManifestEntry>> {
+    let path = snapshot_dir(backup_dir, snapshot).join(MANIFEST);
+    let data = fs::read(&path).map_err(DbError::io_at(&path))?;
+    ByteReader::new(&data).read::<Vec<ManifestEntry>>()
+}
+
+pub(crate) fn write_manifest(
+    backup_dir: &Path,
+    snapshot: u64,
+    entries: &[ManifestEntry],
+) -> DbResult<()> {
+    let path = snapshot_dir(backup_dir, snapshot).join(MANIFEST);
+    fs::write(&path, entries.to_vec().encoded()).map_err(DbError::io_at(&path))
+}
+
+/// Lists all snapshots in a backup directory, oldest first.
+pub fn list_snapshots(backup_dir: impl AsRef<Path>) -> DbResult<Vec<Snapshot>> {
+    let backup_dir = backup_dir.as_ref();
+    snapshot_names(backup_dir)?
+        .into_iter()
+        .map(|name| {
+            let manifest = read_manifest(backup_dir, name)?;
+            let copied: Vec<_> = manifest.iter().filter(|e| e.snapshot == name).collect();
+            Ok(Snapshot {
+                time: UNIX_EPOCH + Duration::from_millis(name),
+                path: snapshot_dir(backup_dir, name),
+                tables: manifest.len(),
+                copied_tables: copied.len(),
+                copied_bytes: copied.iter().map(|e| e.size).sum(),
+            })
+        })
+        .collect()
+}
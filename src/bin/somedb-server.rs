@@ -0,0 +1,100 @@
+//! Serves a somedb directory to `RemoteDatabase` clients over tcp or a unix socket.
+
+use std::{
+    error::Error,
+    io::{self, Write},
+    net::TcpListener,
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use somedb::{db::Database, server};
+
+const USAGE: &str = "\
+usage: somedb-server [--dir <path>] [--listen <addr> | --unix <path>]
+
+options:
+    --dir <path>        the database directory, `sdb/` by default
+    --listen <addr>     the tcp address to listen on, `127.0.0.1:7373` by default
+    --unix <path>       listen on a unix socket instead
+
+The address the server listens on is printed once it accepts connections.";
+
+enum Listen {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+struct Args {
+    dir: PathBuf,
+    listen: Listen,
+    help: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, Box<dyn Error>> {
+    let mut res = Args {
+        dir: PathBuf::from("sdb/"),
+        listen: Listen::Tcp("127.0.0.1:7373".to_string()),
+        help: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => res.dir = args.next().ok_or("missing value for --dir")?.into(),
+            "--listen" => {
+                res.listen = Listen::Tcp(args.next().ok_or("missing value for --listen")?)
+            }
+            "--unix" => {
+                res.listen = Listen::Unix(args.next().ok_or("missing value for --unix")?.into())
+            }
+            "-h" | "--help" => res.help = true,
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    Ok(res)
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    if args.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    let db = Database::new(&args.dir, false)?;
+    let mut out = io::stdout();
+
+    match args.listen {
+        Listen::Tcp(addr) => {
+            let listener = TcpListener::bind(&addr)?;
+            writeln!(out, "listening on {}", listener.local_addr()?)?;
+            out.flush()?;
+            server::serve_tcp(db, listener)?;
+        }
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            // a socket file is left behind when the server is killed
+            let _ = std::fs::remove_file(&path);
+            let listener = std::os::unix::net::UnixListener::bind(&path)?;
+            writeln!(out, "listening on {}", path.display())?;
+            out.flush()?;
+            server::serve_unix(db, listener)?;
+        }
+        #[cfg(not(unix))]
+        Listen::Unix(_) => return Err("unix sockets are not supported on this platform".into()),
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let res = parse_args(std::env::args().skip(1)).and_then(run);
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Command line tool for inspecting and editing somedb directories.
+//!
+//! Everything works from the persisted table schemas so the
+//! rust types of the application are not needed.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use somedb::{
+    catalog::TableInfo,
+    db::{Database, DbError},
+    format::{Format, IdMode, RowWriter},
+    integrity::TableReport,
+    sql::SqlOutput,
+};
+
+const USAGE: &str = "\
+usage: somedb [--dir <path>] <command> [args]
+
+commands:
+    tables                                      list all tables
+    count <table>                               print the number of rows in a table
+    dump <table> [--format json|ndjson|csv]     print all rows of a table
+    check                                       find corrupted rows in all tables
+    repair                                      remove corrupted rows from all tables
+    compact [<table>]                           remove unused bytes from table files
+    upgrade                                     rewrite tables stored in an older format
+    export <table> <file> [--format ...]        write all rows of a table to a file
+    import <table> <file> [--format ...]        add the rows in a file to a table
+    sql <statement>                             run a SELECT, INSERT, UPDATE or DELETE statement
+
+<table> is the struct name, the full rust type name or the file name of a table.
+The default directory is `sdb/` and the default format is json.";
+
+struct Args {
+    dir: PathBuf,
+    format: Format,
+    positional: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, Box<dyn Error>> {
+    let mut res = Args {
+        dir: PathBuf::from("sdb/"),
+        format: Format::Json,
+        positional: vec![],
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => res.dir = args.next().ok_or("missing value for --dir")?.into(),
+            "--format" => res.format = args.next().ok_or("missing value for --format")?.parse()?,
+            "-h" | "--help" => res.positional.insert(0, "help".to_string()),
+            _ => res.positional.push(arg),
+        }
+    }
+
+    Ok(res)
+}
+
+fn find_table(db: &Database, name: &str) -> Result<TableInfo, Box<dyn Error>> {
+    Ok(db
+        .table_info(name)?
+        .ok_or_else(|| format!("no table named {name}"))?)
+}
+
+fn tables_or_all(db: &Database, name: Option<&String>) -> Result<Vec<TableInfo>, Box<dyn Error>> {
+    match name {
+        Some(name) => Ok(vec![find_table(db, name)?]),
+        None => Ok(db.catalog()?),
+    }
+}
+
+fn write_table(
+    db: &Database,
+    table: &TableInfo,
+    writer: impl Write,
+    format: Format,
+) -> Result<usize, Box<dyn Error>> {
+    let schema = table
+        .schema
+        .as_ref()
+        .ok_or_else(|| DbError::SchemaNotFound {
+            table: table.name(),
+        })?;
+    let raw = db.raw_read_dyn(table)?;
+
+    let mut writer = RowWriter::new(writer, schema, format)?;
+    for row in &raw.entities {
+        writer.write_row(row)?;
+    }
+    Ok(writer.finish()?)
+}
+
+fn print_reports(out: &mut impl Write, reports: &[TableReport]) -> io::Result<()> {
+    for report in reports {
+        if report.is_ok() {
+            writeln!(out, "{}: ok ({} rows)", report.table, report.intact_rows)?;
+        }
+        for corruption in &report.corruptions {
+            writeln!(out, "{}: {corruption}", report.table)?;
+        }
+    }
+    Ok(())
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let [command, rest @ ..] = args.positional.as_slice() else {
+        return Err(USAGE.into());
+    };
+
+    if command == "help" {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    if !args.dir.is_dir() {
+        return Err(format!("{} is not a directory", args.dir.display()).into());
+    }
+
+    let mut db = Database::new(&args.dir, false)?;
+    let mut out = io::stdout().lock();
+
+    match (command.as_str(), rest) {
+        ("tables", []) => {
+            for table in db.catalog()? {
+                let schema = match &table.schema {
+                    Some(schema) => schema
+                        .fields
+                        .iter()
+                        .map(|f| format!("{}: {}", f.name, f.ty))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    None => "unknown schema".to_string(),
+                };
+                let last_id = table
+                    .last_id
+                    .as_ref()
+                    .map(|id| id.to_string())
+                    .unwrap_or_else(|| "?".to_string());
+
+                writeln!(
+                    out,
+                    "{}\t{} rows\t{} bytes\tlast id {}\tformat {}\t{{{}}}",
+                    table.name(),
+                    table.row_count,
+                    table.file_size,
+                    last_id,
+                    table
+                        .format_version
+                        .map(|v| v.to_string())
+                        .unwrap_or_else(|| "?".to_string()),
+                    schema
+                )?;
+            }
+        }
+        ("count", [table]) => writeln!(out, "{}", find_table(&db, table)?.row_count)?,
+        ("dump", [table]) => {
+            write_table(&db, &find_table(&db, table)?, &mut out, args.format)?;
+        }
+        ("check", []) => {
+            let reports = db.verify()?;
+            print_reports(&mut out, &reports)?;
+            if reports.iter().any(|r| !r.is_ok()) {
+                return Err("some tables are corrupted".into());
+            }
+        }
+        ("repair", []) => {
+            let reports = db.repair()?;
+            print_reports(&mut out, &reports)?;
+            for report in reports.iter().filter(|r| !r.is_ok()) {
+                writeln!(
+                    out,
+                    "{}: kept {} rows, dropped {} rows",
+                    report.table, report.intact_rows, report.lost_rows
+                )?;
+            }
+        }
+        ("upgrade", []) => {
+            for table in db.upgrade()? {
+                writeln!(out, "upgraded {table}")?;
+            }
+        }
+        ("compact", table) if table.len() <= 1 => {
+            for table in tables_or_all(&db, table.first())? {
+                let reclaimed = db.compact_table(&table.type_hash)?;
+                writeln!(out, "{}: reclaimed {reclaimed} bytes", table.name())?;
+            }
+        }
+        ("export", [table, file]) => {
+            let table = find_table(&db, table)?;
+            let rows = write_table(
+                &db,
+                &table,
+                BufWriter::new(File::create(file)?),
+                args.format,
+            )?;
+            writeln!(out, "exported {rows} rows")?;
+        }
+        ("import", [table, file]) => {
+            let table = find_table(&db, table)?;
+            let count = db.import_dyn(&table, File::open(file)?, args.format, IdMode::Auto)?;
+            writeln!(out, "imported {count} rows")?;
+        }
+        ("sql", [statement]) => match db.execute(statement)? {
+            SqlOutput::Rows(rows) => {
+                for row in rows {
+                    writeln!(out, "{row}")?;
+                }
+            }
+            SqlOutput::Changed(count) => writeln!(out, "changed {count} rows")?,
+        },
+        _ => return Err(USAGE.into()),
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let res = parse_args(std::env::args().skip(1)).and_then(run);
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,3 +1,8 @@
+use crate::{
+    db::{DbError, DbResult},
+    storable::Storable,
+};
+
 #[derive(Debug, Clone)]
 pub struct ByteReader<'a> {
     src: &'a [u8],
@@ -14,28 +19,88 @@ impl<'a> ByteReader<'a> {
         }
     }
 
-    pub fn reader_for_block(&mut self) -> ByteReader<'a> {
-        if self.is_at_end() {
-            return self.clone();
+    /// Reads the next length prefixed block.
+    pub fn reader_for_block(&mut self) -> DbResult<ByteReader<'a>> {
+        self.block("block")
+    }
+
+    /// Reads and decodes the next block as a `T`.
+    pub fn read<T: Storable>(&mut self) -> DbResult<T> {
+        T::decoded(self.block(std::any::type_name::<T>())?)
+    }
+
+    fn block(&mut self, type_name: &'static str) -> DbResult<ByteReader<'a>> {
+        let offset = self.start;
+        let len = self.read_len(type_name)? as usize;
+        if len > self.end - self.start {
+            self.start = offset;
+            return Err(DbError::Decode {
+                type_name,
+                offset,
+                reason: format!(
+                    "block length {len} exceeds the remaining {} bytes",
+                    self.end - self.start - 4
+                ),
+            });
         }
-        let len = self.read_len();
-        self.start += len as usize;
-        ByteReader {
+        self.start += len;
+        Ok(ByteReader {
             src: self.src,
-            start: self.start - len as usize,
+            start: self.start - len,
             end: self.start,
+        })
+    }
+
+    fn read_len(&mut self, type_name: &'static str) -> DbResult<u32> {
+        let bytes = self.take::<4>().map_err(|remaining| DbError::Decode {
+            type_name,
+            offset: self.start,
+            reason: if remaining == 0 {
+                "unexpected end of data".to_string()
+            } else {
+                format!("expected a 4 byte block length, found {remaining} bytes")
+            },
+        })?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    /// Takes the next `N` bytes or returns the number of remaining bytes.
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], usize> {
+        if self.end - self.start < N {
+            return Err(self.end - self.start);
         }
+        self.start += N;
+        Ok(self.src[self.start - N..self.start].try_into().unwrap())
     }
 
-    fn read_len(&mut self) -> u32 {
-        self.start += 4;
-        u32::from_be_bytes(self.src[(self.start - 4)..self.start].try_into().unwrap())
+    /// Reads the remaining bytes as an array of exactly `N` bytes.
+    pub fn read_exact<const N: usize>(&self, type_name: &'static str) -> DbResult<[u8; N]> {
+        self.read_byte_slice().try_into().map_err(|_| {
+            self.error(
+                type_name,
+                format!("expected {N} bytes, found {}", self.end - self.start),
+            )
+        })
     }
 
-    pub fn read_byte_slice(&'a self) -> &'a [u8] {
+    pub fn read_byte_slice(&self) -> &'a [u8] {
         &self.src[self.start..self.end]
     }
 
+    /// Creates a [DbError::Decode] at the current position.
+    pub fn error(&self, type_name: &'static str, reason: impl Into<String>) -> DbError {
+        DbError::Decode {
+            type_name,
+            offset: self.start,
+            reason: reason.into(),
+        }
+    }
+
+    /// The offset of the reader from the start of the source data.
+    pub fn position(&self) -> usize {
+        self.start
+    }
+
     pub fn is_at_end(&self) -> bool {
         self.start == self.end
     }
@@ -0,0 +1,86 @@
+//! Introspection of the tables stored in a [Database](crate::db::Database).
+
+use std::path::PathBuf;
+
+use crate::{
+    byte_reader::ByteReader,
+    entity_meta::{FORMAT_VERSION, RawEntityMeta},
+    schema::TableSchema,
+    type_hash::TypeHash,
+    value::Value,
+};
+
+/// Describes a single stored table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub type_hash: TypeHash,
+    pub path: PathBuf,
+    pub file_size: u64,
+    /// The [format version](crate::entity_meta::FORMAT_VERSION) of the table file,
+    /// `None` if it could not be read or is not supported.
+    pub format_version: Option<u32>,
+    pub row_count: usize,
+    /// The persisted schema. Tables written by older versions of somedb
+    /// only get a schema once they are written to again.
+    pub schema: Option<TableSchema>,
+    /// The last used id, only known if the schema is known.
+    pub last_id: Option<Value>,
+}
+
+impl TableInfo {
+    /// The name of the table, falling back to the encoded type hash.
+    pub fn name(&self) -> String {
+        self.schema
+            .as_ref()
+            .map(|s| s.name.clone())
+            .unwrap_or_else(|| self.type_hash.encode())
+    }
+
+    /// Checks whether `name` refers to this table. The name can
+    /// either be the struct name, the full rust type name or the
+    /// encoded type hash.
+    pub fn matches(&self, name: &str) -> bool {
+        self.type_hash.encode() == name
+            || self
+                .schema
+                .as_ref()
+                .is_some_and(|s| s.name == name || s.type_name == name)
+    }
+
+    /// Checks whether the table is stored in an older format that
+    /// can be [upgraded](crate::db::Database::upgrade).
+    pub fn needs_upgrade(&self) -> bool {
+        self.format_version.is_some_and(|v| v < FORMAT_VERSION)
+    }
+
+    /// Reads the table information from the raw file content.
+    ///
+    /// Corrupted tables are still listed so they can be
+    /// [verified](crate::db::Database::verify) later.
+    pub(crate) fn from_raw(
+        type_hash: TypeHash,
+        path: PathBuf,
+        data: &[u8],
+        schema: Option<TableSchema>,
+    ) -> Self {
+        let raw = ByteReader::new(data)
+            .reader_for_block()
+            .and_then(RawEntityMeta::split)
+            .ok();
+
+        let last_id = raw.as_ref().and_then(|raw| {
+            let id_type = schema.as_ref()?.id_type()?;
+            Value::decode(id_type, raw.last_id.clone()).ok()
+        });
+
+        Self {
+            type_hash,
+            path,
+            file_size: data.len() as u64,
+            format_version: raw.as_ref().and_then(|r| r.format_version()),
+            row_count: raw.as_ref().map(|r| r.entities.len()).unwrap_or_default(),
+            schema,
+            last_id,
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Change data capture for stored entities.
+//!
+//! Every write of a table is compared with the previous content of the
+//! table to find the inserted, updated and deleted entities. The changes are
+//! sent to [subscribers](crate::db::Database::subscribe) and, if it is
+//! [enabled](crate::db::Database::set_change_log), appended to a change log
+//! in the database directory that external consumers can tail using
+//! [changes_since](crate::db::Database::changes_since).
+//!
+//! Changes are logged before the table is written. If the process stops in
+//! between, the last logged changes may be missing from the table, so consumers
+//! that need to be exact should check them against the table after a crash.
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    backup::millis,
+    byte_reader::ByteReader,
+    db::{DbError, DbResult},
+    entity::Entity,
+    entity_meta::RawEntityMeta,
+    schema::{FieldSchema, FieldType, TableSchema},
+    storable::Storable,
+    type_hash::TypeHash,
+    value::Value,
+};
+
+/// The file name of the change log in the database directory.
+pub(crate) const CHANGE_LOG: &str = "changes.log";
+
+/// A change of a single entity.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<T: Entity> {
+    Inserted(T),
+    Updated { old: T, new: T },
+    Deleted(T::Id),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Inserted,
+    Updated,
+    Deleted,
+}
+
+/// A change as it is stored in the change log.
+///
+/// The entities are kept encoded so the record can be read
+/// with or without the rust type of the table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeRecord {
+    /// Increases by one with every logged change of any table.
+    /// Changes that are only sent to subscribers have the sequence 0.
+    pub sequence: u64,
+    pub time: SystemTime,
+    pub table: TypeHash,
+    pub kind: ChangeKind,
+    old: Option<Vec<u8>>,
+    new: Option<Vec<u8>>,
+}
+
+impl ChangeRecord {
+    /// Decodes the change if it belongs to the table of `T`.
+    pub fn decode<T: Entity>(&self) -> DbResult<Option<Change<T>>> {
+        if self.table != T::type_hash() {
+            return Ok(None);
+        }
+
+        let decode = |data: &Option<Vec<u8>>| {
+            data.as_deref()
+                .map(|d| T::decoded(ByteReader::new(d)))
+                .transpose()
+        };
+
+        Ok(Some(match (decode(&self.old)?, decode(&self.new)?) {
+            (None, Some(new)) => Change::Inserted(new),
+            (Some(old), Some(new)) => Change::Updated { old, new },
+            (Some(old), None) => Change::Deleted(old.get_id()),
+            (None, None) => unreachable!("every change has an old or a new entity"),
+        }))
+    }
+
+    /// Decodes the entity before and after the change using a persisted schema.
+    pub fn decode_dyn(&self, schema: &TableSchema) -> DbResult<(Option<Value>, Option<Value>)> {
+        let row_type = schema.row_type();
+        let decode = |data: &Option<Vec<u8>>| {
+            data.as_deref()
+                .map(|d| Value::decode(&row_type, ByteReader::new(d)))
+                .transpose()
+        };
+        Ok((decode(&self.old)?, decode(&self.new)?))
+    }
+}
+
+unsafe impl Storable for ChangeRecord {
+    fn type_hash() -> TypeHash {
+        unsafe { TypeHash::from_str("somedb::ChangeRecord") }
+    }
+
+    fn field_type() -> FieldType {
+        FieldType::Struct {
+            name: "ChangeRecord".to_string(),
+            fields: vec![
+                FieldSchema::new("sequence", FieldType::U64),
+                FieldSchema::new("time", FieldType::U64),
+                FieldSchema::new("table", FieldType::String),
+                FieldSchema::new("kind", FieldType::U8),
+            ],
+        }
+    }
+
+    fn inner_encoded(&self) -> Vec<u8> {
+        let mut res = self.sequence.encoded();
+        res.append(&mut millis(self.time).encoded());
+        res.append(&mut self.table.encode().encoded());
+        res.append(&mut (self.kind as u8).encoded());
+        for data in [&self.old, &self.new].into_iter().flatten() {
+            res.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            res.extend_from_slice(data);
+        }
+        res
+    }
+
+    fn decoded(mut reader: ByteReader) -> DbResult<Self> {
+        let sequence = reader.read::<u64>()?;
+        let time = UNIX_EPOCH + Duration::from_millis(reader.read::<u64>()?);
+        let table = TypeHash::decode(&reader.read::<String>()?);
+
+        let offset = reader.position();
+        let kind = match reader.read::<u8>()? {
+            0 => ChangeKind::Inserted,
+            1 => ChangeKind::Updated,
+            2 => ChangeKind::Deleted,
+            kind => {
+                return Err(DbError::Decode {
+                    type_name: "ChangeKind",
+                    offset,
+                    reason: format!("unknown change kind {kind}"),
+                });
+            }
+        };
+
+        let mut read_entity = || {
+            reader
+                .reader_for_block()
+                .map(|r| Some(r.read_byte_slice().to_vec()))
+        };
+        let (old, new) = match kind {
+            ChangeKind::Inserted => (None, read_entity()?),
+            ChangeKind::Updated => (read_entity()?, read_entity()?),
+            ChangeKind::Deleted => (read_entity()?, None),
+        };
+
+        Ok(Self {
+            sequence,
+            time,
+            table,
+            kind,
+            old,
+            new,
+        })
+    }
+}
+
+/// Compares the encoded content of a table file before and after a write.
+///
+/// `id_of` decodes the encoded id of an entity.
+pub(crate) fn diff(
+    table: TypeHash,
+    old_data: &[u8],
+    new_data: &[u8],
+    id_of: impl Fn(ByteReader) -> DbResult<Vec<u8>>,
+) -> DbResult<Vec<ChangeRecord>> {
+    let rows = |data: &[u8]| -> DbResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        if data.is_empty() {
+            return Ok(vec![]);
+        }
+        RawEntityMeta::split(ByteReader::new(data).reader_for_block()?)?
+            .entities
+            .into_iter()
+            .map(|e| Ok((id_of(e.clone())?, e.read_byte_slice().to_vec())))
+            .collect()
+    };
+
+    let old_rows = rows(old_data)?;
+    let new_rows = rows(new_data)?;
+    let old: HashMap<_, _> = old_rows.iter().map(|(id, e)| (id, e)).collect();
+    let new: HashMap<_, _> = new_rows.iter().map(|(id, e)| (id, e)).collect();
+
+    let time = SystemTime::now();
+    let record = |kind, old: Option<&Vec<u8>>, new: Option<&Vec<u8>>| ChangeRecord {
+        sequence: 0,
+        time,
+        table,
+        kind,
+        old: old.cloned(),
+        new: new.cloned(),
+    };
+
+    let mut changes = vec![];
+    for (id, entity) in &new_rows {
+        match old.get(id) {
+            None => changes.push(record(ChangeKind::Inserted, None, Some(entity))),
+            Some(&old) if old != entity => {
+                changes.push(record(ChangeKind::Updated, Some(old), Some(entity)))
+            }
+            Some(_) => {}
+        }
+    }
+    for (id, entity) in &old_rows {
+        if !new.contains_key(id) {
+            changes.push(record(ChangeKind::Deleted, Some(entity), None));
+        }
+    }
+
+    Ok(changes)
+}
+
+type Subscriber = Box<dyn FnMut(&ChangeRecord) -> bool + Send>;
+
+/// The subscribers of a database by table.
+#[derive(Default)]
+pub(crate) struct Subscribers(HashMap<TypeHash, Vec<Subscriber>>);
+
+impl Subscribers {
+    pub fn add<T: Entity + Send + 'static>(&mut self) -> Receiver<Change<T>>
+    where
+        T::Id: Send,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.0
+            .entry(T::type_hash())
+            .or_default()
+            .push(Box::new(move |record| match record.decode::<T>() {
+                Ok(Some(change)) => sender.send(change).is_ok(),
+                _ => true,
+            }));
+        receiver
+    }
+
+    pub fn has(&self, table: &TypeHash) -> bool {
+        self.0.get(table).is_some_and(|s| !s.is_empty())
+    }
+
+    /// Sends the changes to all subscribers and removes
+    /// the subscribers whose receiver was dropped.
+    pub fn publish(&mut self, changes: &[ChangeRecord]) {
+        for change in changes {
+            if let Some(subscribers) = self.0.get_mut(&change.table) {
+                subscribers.retain_mut(|s| s(change));
+            }
+        }
+    }
+}
+
+impl Debug for Subscribers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(table, s)| (table, s.len())))
+            .finish()
+    }
+}
@@ -1,38 +1,68 @@
 use std::{
-    collections::HashMap,
-    error::Error,
+    collections::{HashMap, HashSet},
     fs::{self, File, OpenOptions},
-    io::{self, Read, Write},
+    io::{Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
-    sync::atomic::{AtomicU32, Ordering},
-    time::Duration,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::Receiver,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+pub use crate::error::{DbError, DbResult, ErrorKind};
+
 use crate::{
+    backup::{
+        ManifestEntry, Snapshot, list_snapshots, millis, read_manifest, snapshot_dir,
+        snapshot_names, write_manifest,
+    },
     byte_reader::ByteReader,
+    catalog::TableInfo,
+    changes::{CHANGE_LOG, Change, ChangeRecord, Subscribers, diff},
+    checksum::crc32,
+    dyn_query::{Expr, Row},
     entity::Entity,
-    entity_meta::EntityMeta,
+    entity_meta::{DynEntityMeta, EntityMeta, EntityStream, RawEntityMeta, encode_inner, with_len},
+    error::id_string,
+    format::{Format, IdMode, RowReader, RowWriter},
+    fulltext::{FullTextIndex, SearchHit, SearchQuery},
     id::IdType,
+    integrity::{TableReport, check_table},
     query::{DbQuery, DbQueryMut},
+    schema::{Compatibility, Constraint, TableSchema},
+    sql::{SqlOutput, Statement},
     storable::Storable,
     type_hash::TypeHash,
+    value::Value,
 };
 
 /// This timeout is used for a lot of internal stuff it is pretty arbitraty right now
-const SLEEP_TIME: Duration = Duration::from_millis(10);
+pub(crate) const SLEEP_TIME: Duration = Duration::from_millis(10);
 
 /// A counter for the db. this is used to allow for multiple db instances in the same process.
 static DB_CNT: AtomicU32 = AtomicU32::new(0);
 
+/// A counter for the locks of all db instances in this process.
+static LOCK_CNT: AtomicU32 = AtomicU32::new(0);
+
 /// A SomeDb instance
 #[derive(Debug)]
 pub struct Database {
     db_dir: PathBuf,
     stored_types: HashMap<TypeHash, ()>,
     db_id: u32,
+    auto_compact: Option<u64>,
+    change_log: bool,
+    /// The length of the change log and the sequence of its last change
+    /// after this handle appended to it, the log is only read again if
+    /// another handle changed its length.
+    change_log_end: Option<(u64, u64)>,
+    subscribers: Subscribers,
 }
 
 impl Database {
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> DbResult<Self> {
         Self::new(PathBuf::from("sdb/"), false)
     }
@@ -69,10 +99,14 @@ impl Database {
             db_dir: db_dir.as_ref().to_path_buf(),
             stored_types,
             db_id,
+            auto_compact: None,
+            change_log: false,
+            change_log_end: None,
+            subscribers: Subscribers::default(),
         })
     }
 
-    pub fn store<T: Entity>(&mut self, mut data: T) -> DbResult<T> {
+    pub fn store<T: Entity>(&mut self, data: T) -> DbResult<T> {
         let type_hash = T::type_hash();
 
         if !self.stored_types.contains_key(&type_hash) {
@@ -80,23 +114,7 @@ impl Database {
         }
 
         let mut existing = self.raw_read_all::<T>()?;
-
-        if !T::GENERATE_ID
-            && existing
-                .entities
-                .iter()
-                .any(|e| e.get_id() == data.get_id())
-        {
-            return Err(DbError::IdExists);
-        }
-
-        if T::GENERATE_ID {
-            data.set_id(<T::Id as IdType>::generate(existing.last_id))
-        }
-
-        existing.entities.push(data.clone());
-        existing.last_id = data.get_id();
-
+        let data = existing.insert(data)?;
         self.raw_write_all(existing)?;
 
         Ok(data)
@@ -109,22 +127,100 @@ impl Database {
             self.add_new_type::<T>()?;
         }
 
-        let last_id = entities
-            .last()
-            .map(|e| e.get_id())
-            .unwrap_or_else(<T::Id as IdType>::initial);
-
-        self.raw_write_all(EntityMeta { last_id, entities })?;
+        let mut existing = self.raw_read_all::<T>()?;
+        existing.replace(entities)?;
+        self.raw_write_all(existing)?;
 
         Ok(())
     }
 
     pub fn raw_write_all<T: Entity>(&mut self, raw: EntityMeta<T>) -> DbResult<()> {
-        let new_data = raw.encoded();
+        check_constraints(&raw.entities)?;
 
-        self.get_wlock::<T>().get()?.write(&new_data)?;
+        // the schema changes when fields with default values are added
+        let schema = T::table_schema();
+        if self.read_schema(&T::type_hash())?.as_ref() != Some(&schema) {
+            let schema_path = self.type_hash_schema_path(&T::type_hash());
+            fs::write(&schema_path, schema.encoded()).map_err(DbError::io_at(&schema_path))?;
+        }
+        self.prepare_fulltext::<T>()?;
 
-        Ok(())
+        self.write_table(&T::type_hash(), &raw.encoded(), |reader| {
+            Ok(T::decoded(reader)?.get_id().encoded())
+        })
+    }
+
+    /// Writes the encoded data of a table over the start of its file, publishes
+    /// the [changes](crate::changes) and compacts the file if the
+    /// [threshold](Self::set_auto_compact) is reached.
+    ///
+    /// `id_of` decodes the encoded id of a stored entity.
+    fn write_table(
+        &mut self,
+        type_hash: &TypeHash,
+        new_data: &[u8],
+        id_of: impl Fn(ByteReader) -> DbResult<Vec<u8>>,
+    ) -> DbResult<()> {
+        self.write_table_if(type_hash, None, new_data, id_of)
+            .map(|_| ())
+    }
+
+    /// Like [Self::write_table] but the table is only written if the content of
+    /// its file still has the `expected` [fingerprint]. Returns whether it was written.
+    fn write_table_if(
+        &mut self,
+        type_hash: &TypeHash,
+        expected: Option<&Fingerprint>,
+        new_data: &[u8],
+        id_of: impl Fn(ByteReader) -> DbResult<Vec<u8>>,
+    ) -> DbResult<bool> {
+        let lock = self.get_wlock_for(type_hash);
+        let mut file = lock.get()?;
+
+        let track_changes = self.change_log || self.subscribers.has(type_hash);
+        let mut old_data = Vec::new();
+        if track_changes || expected.is_some() {
+            file.read_to_end(&mut old_data)
+                .and_then(|_| file.seek(SeekFrom::Start(0)))
+                .map_err(DbError::io_at(&lock.file))?;
+        }
+        if expected.is_some_and(|expected| *expected != fingerprint(&old_data)) {
+            return Ok(false);
+        }
+
+        let mut changes = if track_changes {
+            diff(*type_hash, &old_data, new_data, id_of)?
+        } else {
+            vec![]
+        };
+
+        // the changes are logged before the table is written and removed
+        // again if that fails. After a crash the log can still contain the
+        // changes of the interrupted write, which the table doesn't contain.
+        let log_len = if self.change_log && !changes.is_empty() {
+            Some(self.append_changes(&mut changes)?)
+        } else {
+            None
+        };
+        let written = file.write_all(new_data).map_err(DbError::io_at(&lock.file));
+        if let (Err(_), Some(len)) = (&written, log_len) {
+            self.truncate_change_log(len);
+        }
+        written?;
+        let size = file.metadata().map_err(DbError::io_at(&lock.file))?.len();
+        self.refresh_fulltext(type_hash, new_data);
+        drop(lock);
+
+        self.subscribers.publish(&changes);
+
+        if self
+            .auto_compact
+            .is_some_and(|threshold| size - new_data.len() as u64 >= threshold)
+        {
+            self.compact_table(type_hash)?;
+        }
+
+        Ok(true)
     }
 
     pub fn read_all<T: Entity>(&self) -> DbResult<Vec<T>> {
@@ -132,18 +228,28 @@ impl Database {
 
         self.stored_types
             .get(&type_hash)
-            .ok_or(DbError::TypeNotFound)?;
+            .ok_or_else(DbError::type_not_found::<T>)?;
 
-        Ok(self.raw_read_all()?.entities)
+        self.raw_read_all()?.loaded()
     }
 
+    /// Reads the stored table of `T` without running the
+    /// [after_load](crate::entity::EntityHooks::after_load) hooks.
     pub fn raw_read_all<T: Entity>(&self) -> DbResult<EntityMeta<T>> {
+        self.check_schema::<T>()?;
+
         let mut vec = Vec::new();
-        self.get_rlock::<T>().get()?.read_to_end(&mut vec)?;
+        let lock = self.get_rlock::<T>();
+        lock.get()?
+            .read_to_end(&mut vec)
+            .map_err(DbError::io_at(&lock.file))?;
 
-        let mut reader = ByteReader::new(&vec);
+        EntityMeta::load(&vec)
+    }
 
-        EntityMeta::decoded(reader.reader_for_block())
+    /// Checks that the stored data of `T` can be read with the current schema.
+    fn check_schema<T: Entity>(&self) -> DbResult<()> {
+        check_compatible::<T>(self.read_schema(&T::type_hash())?.as_ref())
     }
 
     pub fn read_all_ids<T: Entity>(&self) -> DbResult<Vec<T::Id>> {
@@ -159,17 +265,10 @@ impl Database {
 
         self.stored_types
             .get(&type_hash)
-            .ok_or(DbError::TypeNotFound)?;
+            .ok_or_else(DbError::type_not_found::<T>)?;
 
         let mut raw = self.raw_read_all::<T>()?;
-        let res = raw
-            .entities
-            .iter_mut()
-            .find(|e| e.get_id() == entity.get_id())
-            .ok_or(DbError::IdNotFound)?;
-
-        *res = entity;
-
+        raw.update(entity)?;
         self.raw_write_all(raw)?;
 
         Ok(())
@@ -180,21 +279,26 @@ impl Database {
 
         self.stored_types
             .get(&type_hash)
-            .ok_or(DbError::TypeNotFound)?;
+            .ok_or_else(DbError::type_not_found::<T>)?;
 
         let mut raw = self.raw_read_all::<T>()?;
-        raw.entities.retain(|e| e.get_id() != id);
-
+        raw.delete(id)?;
         self.raw_write_all(raw)?;
         Ok(())
     }
 
     pub fn delete_entity_store<T: Entity>(&mut self) -> DbResult<()> {
         let type_hash = T::type_hash();
+        for entity in self.read_all::<T>()? {
+            entity.before_delete().map_err(DbError::validation::<T>)?;
+        }
         self.stored_types
             .remove(&type_hash)
-            .ok_or(DbError::TypeNotFound)?;
-        fs::remove_file(self.type_hash_file_path(&type_hash))?;
+            .ok_or_else(DbError::type_not_found::<T>)?;
+        let path = self.type_hash_file_path(&type_hash);
+        fs::remove_file(&path).map_err(DbError::io_at(&path))?;
+        let _ = fs::remove_file(self.type_hash_fulltext_path(&type_hash));
+        let _ = fs::remove_file(self.type_hash_schema_path(&type_hash));
         Ok(())
     }
 
@@ -205,26 +309,740 @@ impl Database {
         opts.read(true);
         opts.write(true);
         opts.create(true);
-        opts.open(self.type_hash_file_path(&type_hash))?;
+        let path = self.type_hash_file_path(&type_hash);
+        opts.open(&path).map_err(DbError::io_at(&path))?;
 
-        self.raw_write_all::<T>(EntityMeta {
-            last_id: <T::Id as IdType>::initial(),
-            entities: vec![],
-        })?;
+        self.raw_write_all::<T>(EntityMeta::empty())?;
 
         self.stored_types.insert(type_hash, ());
         Ok(())
     }
 
     fn type_hash_file_path(&self, type_hash: &TypeHash) -> PathBuf {
-        let mut path = self.db_dir.clone();
-        path.push(PathBuf::from(format!("{}.sdb", type_hash.encode())));
-        path
+        table_file(&self.db_dir, type_hash)
+    }
+
+    fn type_hash_schema_path(&self, type_hash: &TypeHash) -> PathBuf {
+        self.type_hash_file_path(type_hash).with_extension("schema")
+    }
+
+    fn type_hash_fulltext_path(&self, type_hash: &TypeHash) -> PathBuf {
+        self.type_hash_file_path(type_hash).with_extension("fts")
+    }
+
+    /// Reads the persisted schema of a table if there is one.
+    pub fn read_schema(&self, type_hash: &TypeHash) -> DbResult<Option<TableSchema>> {
+        let path = self.type_hash_schema_path(type_hash);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let data = fs::read(&path).map_err(DbError::io_at(&path))?;
+        let mut reader = ByteReader::new(&data);
+        Ok(Some(reader.read::<TableSchema>()?))
+    }
+
+    /// Lists all tables stored in this database, sorted by name.
+    ///
+    /// This only uses the persisted schemas so the rust types of
+    /// the tables don't need to be known.
+    pub fn catalog(&self) -> DbResult<Vec<TableInfo>> {
+        let mut tables = self
+            .stored_types
+            .keys()
+            .map(|type_hash| {
+                let path = self.type_hash_file_path(type_hash);
+                let mut data = Vec::new();
+                let lock = self.get_rlock_for(type_hash);
+                lock.get()?
+                    .read_to_end(&mut data)
+                    .map_err(DbError::io_at(&lock.file))?;
+
+                Ok(TableInfo::from_raw(
+                    *type_hash,
+                    path,
+                    &data,
+                    self.read_schema(type_hash)?,
+                ))
+            })
+            .collect::<DbResult<Vec<_>>>()?;
+
+        tables.sort_by_key(|t| t.name());
+        Ok(tables)
+    }
+
+    /// Reads a table without knowing its rust type using its persisted schema.
+    pub fn raw_read_dyn(&self, table: &TableInfo) -> DbResult<DynEntityMeta> {
+        let schema = table
+            .schema
+            .as_ref()
+            .ok_or_else(|| DbError::SchemaNotFound {
+                table: table.name(),
+            })?;
+
+        let mut vec = Vec::new();
+        let lock = self.get_rlock_for(&table.type_hash);
+        lock.get()?
+            .read_to_end(&mut vec)
+            .map_err(DbError::io_at(&lock.file))?;
+
+        let mut reader = ByteReader::new(&vec);
+
+        DynEntityMeta::decoded(schema, reader.reader_for_block()?)
+    }
+
+    /// Writes a table without knowing its rust type.
+    pub fn raw_write_dyn(&mut self, table: &TableInfo, raw: DynEntityMeta) -> DbResult<()> {
+        let schema = table
+            .schema
+            .as_ref()
+            .ok_or_else(|| DbError::SchemaNotFound {
+                table: table.name(),
+            })?;
+        check_dyn_constraints(schema, &raw.entities, true)?;
+
+        self.write_table(&table.type_hash, &raw.encoded(schema), id_of_row(schema))
+    }
+
+    /// Counts the stored entities of a table without decoding them.
+    pub(crate) fn row_count(&self, type_hash: &TypeHash) -> DbResult<usize> {
+        let Some((_, data)) = self.read_table_file(type_hash)? else {
+            return Ok(0);
+        };
+        let raw = RawEntityMeta::split(ByteReader::new(&data).reader_for_block()?)?;
+        Ok(raw.entities.len())
+    }
+
+    /// Reads the persisted schema and the file of a table, `None` if it isn't stored.
+    ///
+    /// Used by the [server](crate::server) which doesn't know the rust types.
+    pub(crate) fn read_table_file(
+        &self,
+        type_hash: &TypeHash,
+    ) -> DbResult<Option<(Option<TableSchema>, Vec<u8>)>> {
+        if !self.stored_types.contains_key(type_hash) {
+            return Ok(None);
+        }
+
+        let mut data = Vec::new();
+        let lock = self.get_rlock_for(type_hash);
+        lock.get()?
+            .read_to_end(&mut data)
+            .map_err(DbError::io_at(&lock.file))?;
+        Ok(Some((self.read_schema(type_hash)?, data)))
+    }
+
+    /// Reads the persisted schema and the rows of a table that match `filter`,
+    /// encoded like a table file. `None` if the table isn't stored.
+    pub(crate) fn query_table_file(
+        &self,
+        type_hash: &TypeHash,
+        filter: &Expr,
+    ) -> DbResult<Option<(Option<TableSchema>, Vec<u8>)>> {
+        let Some((schema, data)) = self.read_table_file(type_hash)? else {
+            return Ok(None);
+        };
+        let stored = schema.as_ref().ok_or_else(|| DbError::SchemaNotFound {
+            table: type_hash.encode(),
+        })?;
+        filter.check(stored)?;
+
+        let mut raw = DynEntityMeta::decoded(stored, ByteReader::new(&data).reader_for_block()?)?;
+        raw.entities.retain(|row| filter.matches(row));
+        let data = raw.encoded(stored);
+        Ok(Some((schema, data)))
+    }
+
+    /// Writes the encoded data of a table if its file still has the `expected`
+    /// [fingerprint], creating the table if it isn't stored yet. Returns
+    /// whether the table was written.
+    pub(crate) fn write_table_file(
+        &mut self,
+        type_hash: &TypeHash,
+        schema: &TableSchema,
+        expected: &Fingerprint,
+        data: &[u8],
+    ) -> DbResult<bool> {
+        // never write anything that can't be read again
+        let raw = DynEntityMeta::decoded(schema, ByteReader::new(data).reader_for_block()?)?;
+        // clients ran the custom checks with the rust type already
+        check_dyn_constraints(schema, &raw.entities, false)?;
+
+        if !self.stored_types.contains_key(type_hash) {
+            let path = self.type_hash_file_path(type_hash);
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+                .map_err(DbError::io_at(&path))?;
+            self.stored_types.insert(*type_hash, ());
+        }
+
+        if !self.write_table_if(type_hash, Some(expected), data, id_of_row(schema))? {
+            return Ok(false);
+        }
+        if self.read_schema(type_hash)?.as_ref() != Some(schema) {
+            let schema_path = self.type_hash_schema_path(type_hash);
+            fs::write(&schema_path, schema.encoded()).map_err(DbError::io_at(&schema_path))?;
+
+            // the full-text index was built with the previous schema
+            let _lock = self.get_wlock_for(type_hash);
+            self.refresh_fulltext(type_hash, data);
+        }
+        Ok(true)
+    }
+
+    /// Deletes the file of a table if it still has the `expected` [fingerprint].
+    /// Returns whether the table was deleted.
+    pub(crate) fn delete_table_file(
+        &mut self,
+        type_hash: &TypeHash,
+        expected: &Fingerprint,
+    ) -> DbResult<bool> {
+        let lock = self.get_wlock_for(type_hash);
+        let mut data = Vec::new();
+        lock.get()?
+            .read_to_end(&mut data)
+            .map_err(DbError::io_at(&lock.file))?;
+        if fingerprint(&data) != *expected {
+            return Ok(false);
+        }
+
+        fs::remove_file(&lock.file).map_err(DbError::io_at(&lock.file))?;
+        let _ = fs::remove_file(self.type_hash_fulltext_path(type_hash));
+        let _ = fs::remove_file(self.type_hash_schema_path(type_hash));
+        self.stored_types.remove(type_hash);
+        Ok(true)
+    }
+
+    /// Removes any bytes after the stored data of a table by atomically
+    /// replacing the table file. Returns the number of bytes that were reclaimed.
+    pub fn compact_table(&mut self, type_hash: &TypeHash) -> DbResult<u64> {
+        let lock = self.get_wlock_for(type_hash);
+
+        let mut data = Vec::new();
+        lock.get()?
+            .read_to_end(&mut data)
+            .map_err(DbError::io_at(&lock.file))?;
+
+        let used = table_block(&data)?.len();
+        if used < data.len() {
+            replace_file(&lock.file, &data[..used])?;
+        }
+
+        Ok((data.len() - used) as u64)
+    }
+
+    /// Compacts the table of `T`, see [compact_table](Self::compact_table).
+    pub fn compact<T: Entity>(&mut self) -> DbResult<u64> {
+        self.stored_types
+            .get(&T::type_hash())
+            .ok_or_else(DbError::type_not_found::<T>)?;
+
+        self.compact_table(&T::type_hash())
+    }
+
+    /// Compacts every table in the database.
+    /// Returns the total number of bytes that were reclaimed.
+    pub fn vacuum(&mut self) -> DbResult<u64> {
+        let type_hashes: Vec<_> = self.stored_types.keys().copied().collect();
+        type_hashes
+            .iter()
+            .map(|type_hash| self.compact_table(type_hash))
+            .sum()
+    }
+
+    /// Subscribes to all changes of the entities of type `T` made through this handle.
+    ///
+    /// The subscription ends when the receiver is dropped.
+    pub fn subscribe<T: Entity + Send + 'static>(&mut self) -> Receiver<Change<T>>
+    where
+        T::Id: Send,
+    {
+        self.subscribers.add::<T>()
+    }
+
+    /// Enables or disables appending all changes made through
+    /// this handle to the change log of the database.
+    pub fn set_change_log(&mut self, enabled: bool) -> DbResult<()> {
+        if enabled {
+            let path = self.db_dir.join(CHANGE_LOG);
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(DbError::io_at(&path))?;
+        }
+        self.change_log = enabled;
+        Ok(())
+    }
+
+    /// Reads all logged changes with a sequence number greater than `sequence`.
+    ///
+    /// Consumers can tail the log by remembering the sequence
+    /// of the last change they have processed.
+    pub fn changes_since(&self, sequence: u64) -> DbResult<Vec<ChangeRecord>> {
+        let path = self.db_dir.join(CHANGE_LOG);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut data = Vec::new();
+        let lock = RLock::new(path, self.guid());
+        lock.get()?
+            .read_to_end(&mut data)
+            .map_err(DbError::io_at(&lock.file))?;
+
+        let mut changes = read_change_log(&data)?;
+        changes.retain(|c| c.sequence > sequence);
+        Ok(changes)
+    }
+
+    /// Assigns sequence numbers to the changes and appends them to the
+    /// change log. Returns the length of the log before the changes.
+    fn append_changes(&mut self, changes: &mut [ChangeRecord]) -> DbResult<u64> {
+        let lock = WLock::new(self.db_dir.join(CHANGE_LOG), self.guid());
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&lock.file)
+            .map_err(DbError::io_at(&lock.file))?;
+        let len = file.metadata().map_err(DbError::io_at(&lock.file))?.len();
+
+        let last = match self.change_log_end {
+            Some((end, last)) if end == len => last,
+            _ => {
+                let mut data = Vec::new();
+                file.read_to_end(&mut data)
+                    .map_err(DbError::io_at(&lock.file))?;
+                read_change_log(&data)?.last().map_or(0, |c| c.sequence)
+            }
+        };
+
+        let mut new_data = Vec::new();
+        for (i, change) in changes.iter_mut().enumerate() {
+            change.sequence = last + 1 + i as u64;
+            new_data.append(&mut change.encoded());
+        }
+        file.write_all(&new_data)
+            .map_err(DbError::io_at(&lock.file))?;
+        self.change_log_end = Some((len + new_data.len() as u64, last + changes.len() as u64));
+        Ok(len)
+    }
+
+    /// Removes the changes appended after the log had the length `len`.
+    fn truncate_change_log(&mut self, len: u64) {
+        let lock = WLock::new(self.db_dir.join(CHANGE_LOG), self.guid());
+        if let Ok(file) = lock.get() {
+            let _ = file.set_len(len);
+        }
+        self.change_log_end = None;
+    }
+
+    /// Compacts a table whenever a write leaves at least `threshold` unused
+    /// bytes in its file. `None` disables automatic compaction, which is the default.
+    pub fn set_auto_compact(&mut self, threshold: Option<u64>) {
+        self.auto_compact = threshold;
+    }
+
+    /// Checks every table for corruption without changing anything.
+    pub fn verify(&self) -> DbResult<Vec<TableReport>> {
+        self.catalog()?
+            .iter()
+            .map(|table| {
+                let mut data = Vec::new();
+                let lock = self.get_rlock_for(&table.type_hash);
+                lock.get()?
+                    .read_to_end(&mut data)
+                    .map_err(DbError::io_at(&lock.file))?;
+                Ok(check_table(table, &data).report)
+            })
+            .collect()
+    }
+
+    /// Checks every table for corruption and rewrites corrupted tables
+    /// so they only contain the intact rows.
+    ///
+    /// Returns the reports of the tables before they were repaired.
+    /// Tables whose last id can't be recovered are left untouched.
+    pub fn repair(&mut self) -> DbResult<Vec<TableReport>> {
+        self.catalog()?
+            .iter()
+            .map(|table| {
+                let lock = self.get_wlock_for(&table.type_hash);
+
+                let mut data = Vec::new();
+                lock.get()?
+                    .read_to_end(&mut data)
+                    .map_err(DbError::io_at(&lock.file))?;
+
+                let salvage = check_table(table, &data);
+                if !salvage.report.is_ok()
+                    && let Some(new_data) = salvage.encoded()
+                {
+                    replace_file(&lock.file, &new_data)?;
+                    self.refresh_fulltext(&table.type_hash, &new_data);
+                }
+
+                Ok(salvage.report)
+            })
+            .collect()
+    }
+
+    /// Rewrites all tables stored in an older format in the current
+    /// [FORMAT_VERSION](crate::entity_meta::FORMAT_VERSION).
+    ///
+    /// Upgrading is optional because older formats can still be read, tables
+    /// are also upgraded whenever they are written. Returns the names
+    /// of the upgraded tables.
+    pub fn upgrade(&mut self) -> DbResult<Vec<String>> {
+        let mut upgraded = vec![];
+        for table in self.catalog()? {
+            if !table.needs_upgrade() {
+                continue;
+            }
+
+            let lock = self.get_wlock_for(&table.type_hash);
+
+            let mut data = Vec::new();
+            lock.get()?
+                .read_to_end(&mut data)
+                .map_err(DbError::io_at(&lock.file))?;
+
+            let raw = RawEntityMeta::split(ByteReader::new(&data).reader_for_block()?)?;
+            raw.check(&table.name())?;
+
+            let new_data = with_len(encode_inner(
+                with_len(raw.last_id.read_byte_slice().to_vec()),
+                raw.entities
+                    .iter()
+                    .map(|e| with_len(e.read_byte_slice().to_vec()))
+                    .collect(),
+            ));
+
+            replace_file(&lock.file, &new_data)?;
+            self.refresh_fulltext(&table.type_hash, &new_data);
+
+            upgraded.push(table.name());
+        }
+        Ok(upgraded)
+    }
+
+    /// Takes a full [snapshot](crate::backup) of all tables and stores it in `backup_dir`.
+    ///
+    /// All tables are read locked at the same time so the snapshot is consistent
+    /// while other handles can keep writing once the files are copied.
+    pub fn backup_to(&self, backup_dir: impl AsRef<Path>) -> DbResult<Snapshot> {
+        self.backup(backup_dir.as_ref(), false)
+    }
+
+    /// Like [backup_to](Self::backup_to) but only copies the tables that
+    /// changed since the latest snapshot in `backup_dir`.
+    pub fn backup_incremental_to(&self, backup_dir: impl AsRef<Path>) -> DbResult<Snapshot> {
+        self.backup(backup_dir.as_ref(), true)
+    }
+
+    fn backup(&self, backup_dir: &Path, incremental: bool) -> DbResult<Snapshot> {
+        let names = snapshot_names(backup_dir)?;
+        let previous = match names.last() {
+            Some(&last) if incremental => read_manifest(backup_dir, last)?,
+            _ => vec![],
+        };
+
+        let name = millis(SystemTime::now()).max(names.last().map_or(0, |last| last + 1));
+        let dir = snapshot_dir(backup_dir, name);
+        fs::create_dir_all(&dir).map_err(DbError::io_at(&dir))?;
+
+        // always lock in the same order
+        let mut type_hashes: Vec<_> = self.stored_types.keys().copied().collect();
+        type_hashes.sort_by_key(|t| t.encode());
+        let locks: Vec<_> = type_hashes
+            .iter()
+            .map(|type_hash| self.get_rlock_for(type_hash))
+            .collect();
+
+        let mut manifest = vec![];
+        for (type_hash, lock) in type_hashes.iter().zip(&locks) {
+            let mut data = Vec::new();
+            lock.get()?
+                .read_to_end(&mut data)
+                .map_err(DbError::io_at(&lock.file))?;
+            let checksum = crc32(&data);
+
+            if let Some(entry) = previous
+                .iter()
+                .find(|e| e.type_hash == *type_hash && e.checksum == checksum)
+            {
+                manifest.push(entry.clone());
+                continue;
+            }
+
+            let path = dir.join(format!("{}.sdb", type_hash.encode()));
+            fs::write(&path, &data).map_err(DbError::io_at(&path))?;
+
+            let schema_path = self.type_hash_schema_path(type_hash);
+            if schema_path.exists() {
+                let target = path.with_extension("schema");
+                fs::copy(&schema_path, &target).map_err(DbError::io_at(&target))?;
+            }
+
+            manifest.push(ManifestEntry {
+                type_hash: *type_hash,
+                snapshot: name,
+                checksum,
+                size: data.len() as u64,
+            });
+        }
+        drop(locks);
+
+        // the manifest is written last so unfinished snapshots are ignored.
+        write_manifest(backup_dir, name, &manifest)?;
+
+        let copied: Vec<_> = manifest.iter().filter(|e| e.snapshot == name).collect();
+        Ok(Snapshot {
+            time: UNIX_EPOCH + Duration::from_millis(name),
+            path: dir,
+            tables: manifest.len(),
+            copied_tables: copied.len(),
+            copied_bytes: copied.iter().map(|e| e.size).sum(),
+        })
+    }
+
+    /// Restores the database to the latest snapshot in `backup_dir`
+    /// that was taken at or before `at`.
+    ///
+    /// The database is restored as it was when the snapshot was taken,
+    /// changes made between the snapshot and `at` are not replayed. Take
+    /// snapshots as often as the database has to be restorable.
+    ///
+    /// Tables that didn't exist at the time of the snapshot are removed.
+    /// Returns the restored snapshot.
+    pub fn restore_from(
+        &mut self,
+        backup_dir: impl AsRef<Path>,
+        at: SystemTime,
+    ) -> DbResult<Snapshot> {
+        let backup_dir = backup_dir.as_ref();
+        let snapshot = list_snapshots(backup_dir)?
+            .into_iter()
+            .rfind(|s| s.time <= at)
+            .ok_or_else(|| DbError::BackupNotFound {
+                path: backup_dir.to_path_buf(),
+            })?;
+        let manifest = read_manifest(backup_dir, millis(snapshot.time))?;
+
+        // everything is read and checked before the database is changed.
+        let tables = manifest
+            .iter()
+            .map(|entry| {
+                let path = snapshot_dir(backup_dir, entry.snapshot)
+                    .join(format!("{}.sdb", entry.type_hash.encode()));
+                let data = fs::read(&path).map_err(DbError::io_at(&path))?;
+                if crc32(&data) != entry.checksum {
+                    return Err(DbError::ChecksumMismatch {
+                        table: entry.type_hash.encode(),
+                    });
+                }
+                let schema = fs::read(path.with_extension("schema")).ok();
+                Ok((entry.type_hash, data, schema))
+            })
+            .collect::<DbResult<Vec<_>>>()?;
+
+        for (type_hash, data, schema) in &tables {
+            let lock = self.get_wlock_for(type_hash);
+            let schema_path = self.type_hash_schema_path(type_hash);
+            match schema {
+                Some(schema) => replace_file(&schema_path, schema)?,
+                None => {
+                    let _ = fs::remove_file(&schema_path);
+                }
+            }
+            replace_file(&lock.file, data)?;
+            if let Ok(block) = table_block(data) {
+                self.refresh_fulltext(type_hash, block);
+            }
+        }
+
+        // tables are only removed once the restored ones are written.
+        for type_hash in self.stored_types.keys() {
+            if tables.iter().any(|(t, ..)| t == type_hash) {
+                continue;
+            }
+            let lock = self.get_wlock_for(type_hash);
+            fs::remove_file(&lock.file).map_err(DbError::io_at(&lock.file))?;
+            let _ = fs::remove_file(self.type_hash_schema_path(type_hash));
+            let _ = fs::remove_file(self.type_hash_fulltext_path(type_hash));
+        }
+
+        self.stored_types = tables.iter().map(|(t, ..)| (*t, ())).collect();
+        Ok(snapshot)
+    }
+
+    /// Writes all entities of `T` to `writer` and returns the number of written rows.
+    ///
+    /// The rows are written one at a time using the field metadata of `T`.
+    pub fn export<T: Entity>(&self, writer: impl Write, format: Format) -> DbResult<usize> {
+        let schema = T::table_schema();
+        let mut rows = RowWriter::new(writer, &schema, format)?;
+        let mut query = self.query::<T>()?;
+        while let Some(entity) = query.try_next()? {
+            rows.write_row(&Value::of(&entity)?)?;
+        }
+        rows.finish()
+    }
+
+    /// Adds the rows read from `reader` to the table of `T` and returns
+    /// the number of imported rows. `mode` decides whether the imported
+    /// ids are kept or new ids are generated, the id can be left out of
+    /// the rows if it is generated.
+    pub fn import<T: Entity>(
+        &mut self,
+        reader: impl Read,
+        format: Format,
+        mode: IdMode,
+    ) -> DbResult<usize> {
+        if !self.stored_types.contains_key(&T::type_hash()) {
+            self.add_new_type::<T>()?;
+        }
+
+        let generate = match mode {
+            IdMode::Auto => T::GENERATE_ID,
+            IdMode::Preserve => false,
+            IdMode::Regenerate => true,
+        };
+
+        let mut raw = self.raw_read_all::<T>()?;
+        let mut ids = ImportIds {
+            table: std::any::type_name::<T>().to_string(),
+            generate: generate
+                .then_some(|last: &Value| Value::of(&<T::Id as IdType>::generate(last.to()?))),
+            ids: raw.entities.iter().map(|e| e.get_id().encoded()).collect(),
+            last_id: Value::of(&raw.last_id)?,
+        };
+        let schema = ids.row_schema(T::table_schema());
+        let count = raw.entities.len();
+        for row in RowReader::new(reader, &schema, format)? {
+            let mut row = row?;
+            ids.assign(&schema.id_field, &mut row)?;
+            let mut entity: T = row.to()?;
+            entity.before_insert().map_err(DbError::validation::<T>)?;
+            raw.entities.push(entity);
+        }
+
+        let count = raw.entities.len() - count;
+        raw.last_id = ids.last_id.to()?;
+        self.raw_write_all(raw)?;
+        Ok(count)
+    }
+
+    /// Adds the rows read from `reader` to a table without knowing its rust
+    /// type, like [import](Self::import) but without running the hooks of the entity.
+    pub fn import_dyn(
+        &mut self,
+        table: &TableInfo,
+        reader: impl Read,
+        format: Format,
+        mode: IdMode,
+    ) -> DbResult<usize> {
+        let schema = table
+            .schema
+            .as_ref()
+            .ok_or_else(|| DbError::SchemaNotFound {
+                table: table.name(),
+            })?;
+        let generate = match mode {
+            IdMode::Auto => schema.generate_id,
+            IdMode::Preserve => false,
+            IdMode::Regenerate => true,
+        };
+
+        let mut raw = self.raw_read_dyn(table)?;
+        let mut ids = ImportIds {
+            table: schema.name.clone(),
+            generate: generate.then_some(|last: &Value| {
+                last.next_id().ok_or_else(|| DbError::Validation {
+                    table: schema.name.clone(),
+                    field: Some(schema.id_field.clone()),
+                    message: format!("can't generate an id after {last}"),
+                })
+            }),
+            ids: raw
+                .entities
+                .iter()
+                .filter_map(|e| e.field(&schema.id_field))
+                .map(Value::encoded)
+                .collect(),
+            last_id: raw.last_id.clone(),
+        };
+        let row_schema = ids.row_schema(schema.clone());
+        let count = raw.entities.len();
+        for row in RowReader::new(reader, &row_schema, format)? {
+            let mut row = row?;
+            ids.assign(&schema.id_field, &mut row)?;
+            raw.entities.push(row);
+        }
+
+        let count = raw.entities.len() - count;
+        raw.last_id = ids.last_id;
+        self.raw_write_dyn(table, raw)?;
+        Ok(count)
+    }
+
+    /// Finds a table in the [catalog](Self::catalog) by its name.
+    pub fn table_info(&self, name: &str) -> DbResult<Option<TableInfo>> {
+        Ok(self.catalog()?.into_iter().find(|t| t.matches(name)))
+    }
+
+    /// Reads the rows of a table that match `filter` without knowing its rust type.
+    ///
+    /// The table is found by its [name](Self::table_info) and the filter
+    /// is checked against its persisted schema.
+    pub fn query_dyn(&self, table: &str, filter: &Expr) -> DbResult<Vec<Row>> {
+        let info = self
+            .table_info(table)?
+            .ok_or_else(|| DbError::TypeNotFound {
+                table: table.to_string(),
+            })?;
+        let schema = info
+            .schema
+            .as_ref()
+            .ok_or_else(|| DbError::SchemaNotFound { table: info.name() })?;
+        filter.check(schema)?;
+
+        Ok(self
+            .raw_read_dyn(&info)?
+            .entities
+            .into_iter()
+            .filter(|row| filter.matches(row))
+            .map(Row::from)
+            .collect())
+    }
+
+    /// Parses and runs a statement of the [sql dialect](crate::sql).
+    pub fn execute(&mut self, sql: &str) -> DbResult<SqlOutput> {
+        Statement::parse(sql)?.execute(self)
+    }
+
+    /// Reads the entities of `T` that match a filter built at runtime.
+    pub fn query_dyn_as<T: Entity>(&self, filter: &Expr) -> DbResult<Vec<T>> {
+        filter.check(&T::table_schema())?;
+
+        let mut query = self.query::<T>()?;
+        let mut res = Vec::new();
+        while let Some(entity) = query.try_next()? {
+            if filter.matches(&Value::of(&entity)?) {
+                res.push(entity);
+            }
+        }
+        Ok(res)
     }
 
     /// Creates a [DbQuery](crate::query::DbQuery) which can
     /// be used to query the database like any other iterator.
-    pub fn query<T: Entity>(&self) -> DbResult<DbQuery<T>> {
+    ///
+    /// The entities are read from disk one at a time while iterating.
+    pub fn query<T: Entity>(&self) -> DbResult<DbQuery<'_, T>> {
         DbQuery::new(self)
     }
 
@@ -236,41 +1054,178 @@ impl Database {
         DbQueryMut::new(self)
     }
 
+    /// Opens the table of `T` for reading its entities one at a time.
+    pub(crate) fn stream_table<T: Entity>(&self) -> DbResult<EntityStream> {
+        self.stored_types
+            .get(&T::type_hash())
+            .ok_or_else(DbError::type_not_found::<T>)?;
+        self.check_schema::<T>()?;
+        EntityStream::new(self.get_rlock::<T>(), std::any::type_name::<T>())
+    }
+
+    /// Searches a `#[fulltext]` field of `T` and returns the matching
+    /// entities, the most relevant first. See [fulltext](crate::fulltext)
+    /// for the query syntax.
+    pub fn search<T: Entity>(&self, field: &str, query: &str) -> DbResult<Vec<SearchHit<T>>> {
+        let type_hash = T::type_hash();
+        self.stored_types
+            .get(&type_hash)
+            .ok_or_else(DbError::type_not_found::<T>)?;
+        if !T::FULLTEXT_FIELDS.contains(&field) {
+            return Err(DbError::Validation {
+                table: std::any::type_name::<T>().to_string(),
+                field: Some(field.to_string()),
+                message: "is not a full-text field".to_string(),
+            });
+        }
+        let query = SearchQuery::parse(query)?;
+        self.check_schema::<T>()?;
+
+        let mut data = Vec::new();
+        let lock = self.get_rlock::<T>();
+        lock.get()?
+            .read_to_end(&mut data)
+            .map_err(DbError::io_at(&lock.file))?;
+        let data = table_block(&data)?;
+
+        let index = match self.read_fulltext(&type_hash) {
+            Some(index) if index.is_current(data, T::FULLTEXT_FIELDS) => index,
+            _ => {
+                let schema =
+                    self.read_schema(&type_hash)?
+                        .ok_or_else(|| DbError::SchemaNotFound {
+                            table: std::any::type_name::<T>().to_string(),
+                        })?;
+                let index = FullTextIndex::build(T::FULLTEXT_FIELDS, &schema, data)?;
+                // the index is only a cache, the next search tries again
+                let _ = self.write_fulltext(&type_hash, &index);
+                index
+            }
+        };
+        drop(lock);
+
+        // only the rows that were hit are decoded
+        let raw = RawEntityMeta::split(ByteReader::new(data).reader_for_block()?)?;
+        raw.check(std::any::type_name::<T>())?;
+        index
+            .search(field, &query)?
+            .into_iter()
+            .map(|(row, score)| {
+                let reader = raw
+                    .entities
+                    .get(row as usize)
+                    .ok_or_else(|| DbError::Decode {
+                        type_name: "FullTextIndex",
+                        offset: 0,
+                        reason: format!("row {row} is not in the table"),
+                    })?;
+                let mut entity = T::decoded(reader.clone())?;
+                entity.after_load().map_err(DbError::validation::<T>)?;
+                Ok(SearchHit { entity, score })
+            })
+            .collect()
+    }
+
+    /// Creates or removes the full-text index of `T` so it covers the
+    /// `#[fulltext]` fields, the index is built when the table is written.
+    fn prepare_fulltext<T: Entity>(&self) -> DbResult<()> {
+        let type_hash = T::type_hash();
+        let path = self.type_hash_fulltext_path(&type_hash);
+        if T::FULLTEXT_FIELDS.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path).map_err(DbError::io_at(&path))?;
+            }
+            return Ok(());
+        }
+
+        let current = self
+            .read_fulltext(&type_hash)
+            .is_some_and(|index| index.fields() == T::FULLTEXT_FIELDS);
+        if !current {
+            self.write_fulltext(&type_hash, &FullTextIndex::empty(T::FULLTEXT_FIELDS))?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the full-text index of a table, if it has one, after the table
+    /// was written with `data`. The caller holds the write lock of the table.
+    ///
+    /// An index that can't be rebuilt is removed so the next search builds it.
+    fn refresh_fulltext(&self, type_hash: &TypeHash, data: &[u8]) {
+        let Some(index) = self.read_fulltext(type_hash) else {
+            return;
+        };
+        let rebuilt = self
+            .read_schema(type_hash)
+            .and_then(|schema| {
+                schema.ok_or_else(|| DbError::SchemaNotFound {
+                    table: type_hash.encode(),
+                })
+            })
+            .and_then(|schema| FullTextIndex::build(&index.fields(), &schema, data))
+            .and_then(|index| self.write_fulltext(type_hash, &index));
+        if rebuilt.is_err() {
+            let _ = fs::remove_file(self.type_hash_fulltext_path(type_hash));
+        }
+    }
+
+    /// Reads the full-text index of a table, `None` if there is none or it can't be used.
+    fn read_fulltext(&self, type_hash: &TypeHash) -> Option<FullTextIndex> {
+        let data = fs::read(self.type_hash_fulltext_path(type_hash)).ok()?;
+        FullTextIndex::decoded(&data).ok().flatten()
+    }
+
+    fn write_fulltext(&self, type_hash: &TypeHash, index: &FullTextIndex) -> DbResult<()> {
+        let path = self.type_hash_fulltext_path(type_hash);
+        // searches only hold a read lock, so the temporary file can't be shared
+        let tmp = path.with_extension(format!("{}-fts", self.guid()));
+        fs::write(&tmp, index.encoded()).map_err(DbError::io_at(&tmp))?;
+        fs::rename(&tmp, &path).map_err(DbError::io_at(&path))
+    }
+
     ///////////// LOCKING AND SYNC CODE /////////////
 
     fn get_rlock<T: Entity>(&self) -> RLock {
-        RLock::new(self.type_hash_file_path(&T::type_hash()), self.guid())
+        self.get_rlock_for(&T::type_hash())
+    }
+
+    fn get_rlock_for(&self, type_hash: &TypeHash) -> RLock {
+        RLock::new(self.type_hash_file_path(type_hash), self.guid())
     }
 
-    fn get_wlock<T: Entity>(&self) -> WLock {
-        WLock::new(self.type_hash_file_path(&T::type_hash()), self.guid())
+    fn get_wlock_for(&self, type_hash: &TypeHash) -> WLock {
+        WLock::new(self.type_hash_file_path(type_hash), self.guid())
     }
 
+    /// A unique id for every lock, so that multiple
+    /// locks of the same database don't collide.
     fn guid(&self) -> String {
-        format!("{}-{}", std::process::id(), self.db_id)
+        let lock_id = LOCK_CNT.fetch_add(1, Ordering::Relaxed);
+        format!("{}-{}-{lock_id}", std::process::id(), self.db_id)
     }
 }
 
-fn someone_has_rlock(file: &PathBuf) -> bool {
+pub(crate) fn someone_has_rlock(file: &Path) -> bool {
     let files = fs::read_dir(file.parent().unwrap()).unwrap();
+    let prefix = file.with_extension("");
+    let prefix = prefix.file_name().unwrap().to_str().unwrap();
 
-    for file in files {
-        if file
-            .unwrap()
-            .path()
-            .extension()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .starts_with("rlock")
+    for entry in files {
+        let name = entry.unwrap().file_name();
+        let name = name.to_string_lossy();
+        // read locks are named `<table>.<guid>-rlock`
+        if name.ends_with("-rlock")
+            && name
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with('.'))
         {
             return true;
         }
     }
-    return false;
+    false
 }
 
-fn someone_has_wlock(file: &PathBuf) -> bool {
+pub(crate) fn someone_has_wlock(file: &Path) -> bool {
     let files = fs::read_dir(file.parent().unwrap()).unwrap();
 
     for entry in files {
@@ -278,15 +1233,203 @@ fn someone_has_wlock(file: &PathBuf) -> bool {
             return true;
         }
     }
-    return false;
+    false
+}
+
+/// The file of a table in the database directory.
+pub(crate) fn table_file(db_dir: &Path, type_hash: &TypeHash) -> PathBuf {
+    db_dir.join(format!("{}.sdb", type_hash.encode()))
+}
+
+/// The part of a table file holding the stored data, the rest is unused.
+fn table_block(data: &[u8]) -> DbResult<&[u8]> {
+    let used = ByteReader::new(data)
+        .reader_for_block()?
+        .read_byte_slice()
+        .len()
+        + 4;
+    Ok(&data[..used])
+}
+
+/// Replaces the content of a file by writing a temporary
+/// file first and renaming it to `path`.
+fn replace_file(path: &Path, data: &[u8]) -> DbResult<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp).map_err(DbError::io_at(&tmp))?;
+    file.write_all(data)
+        .and_then(|_| file.sync_all())
+        .map_err(DbError::io_at(&tmp))?;
+    fs::rename(&tmp, path).map_err(DbError::io_at(path))
+}
+
+/// Identifies the content of a table file by its checksum and length.
+pub(crate) type Fingerprint = [u8; 12];
+
+pub(crate) fn fingerprint(data: &[u8]) -> Fingerprint {
+    let mut res = [0; 12];
+    res[..4].copy_from_slice(&crc32(data).to_be_bytes());
+    res[4..].copy_from_slice(&(data.len() as u64).to_be_bytes());
+    res
+}
+
+/// Decodes the encoded id of a row using a persisted schema.
+fn id_of_row(schema: &TableSchema) -> impl Fn(ByteReader) -> DbResult<Vec<u8>> + '_ {
+    let row_type = schema.row_type();
+    move |reader| {
+        Value::decode(&row_type, reader)?
+            .field(&schema.id_field)
+            .map(|id| id.encoded())
+            .ok_or_else(|| DbError::SchemaNotFound {
+                table: schema.name.clone(),
+            })
+    }
+}
+
+/// Checks that the stored data of `T` with the `stored` schema
+/// can be read with the current schema.
+pub(crate) fn check_compatible<T: Entity>(stored: Option<&TableSchema>) -> DbResult<()> {
+    if let Some(stored) = stored
+        && T::table_schema().compatibility(stored) == Compatibility::Breaking
+    {
+        return Err(DbError::SchemaMismatch {
+            table: std::any::type_name::<T>().to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Chooses the ids of imported rows, shared by [Database::import]
+/// and [Database::import_dyn].
+struct ImportIds<G> {
+    table: String,
+    /// Generates the id following the given one, `None` if the imported ids are kept.
+    generate: Option<G>,
+    /// The encoded ids of the table.
+    ids: HashSet<Vec<u8>>,
+    last_id: Value,
+}
+
+impl<G: FnMut(&Value) -> DbResult<Value>> ImportIds<G> {
+    /// The schema the rows are read with, generated ids can be left out.
+    fn row_schema(&self, schema: TableSchema) -> TableSchema {
+        match self.generate {
+            Some(_) => schema.with_optional_id(),
+            None => schema,
+        }
+    }
+
+    /// Generates the id of the row or checks that its id is new.
+    fn assign(&mut self, id_field: &str, row: &mut Value) -> DbResult<()> {
+        if let Some(generate) = &mut self.generate {
+            let id = generate(&self.last_id)?;
+            row.set_field(id_field, id);
+        }
+        let id = row
+            .field(id_field)
+            .cloned()
+            .ok_or_else(|| DbError::ParseError(format!("a row has no {id_field} field")))?;
+        if !self.ids.insert(id.encoded()) {
+            return Err(DbError::IdExists {
+                table: self.table.clone(),
+                id: id.to_string(),
+            });
+        }
+
+        // preserved ids must not be generated again later on
+        if self.generate.is_some() || id > self.last_id {
+            self.last_id = id;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the field constraints of all entities of a table.
+pub(crate) fn check_constraints<T: Entity>(entities: &[T]) -> DbResult<()> {
+    let invalid = |field: &str, message| DbError::Validation {
+        table: std::any::type_name::<T>().to_string(),
+        field: Some(field.to_string()),
+        message,
+    };
+
+    let mut unique = HashMap::new();
+    for entity in entities {
+        entity
+            .check_constraints()
+            .map_err(|(field, message)| invalid(field, message))?;
+
+        for (field, value) in entity.unique_values() {
+            if let Some(other) = unique.insert((field, value), entity.get_id()) {
+                return Err(invalid(
+                    field,
+                    format!(
+                        "must be unique but the entity with id {} has the same value",
+                        id_string(&other)
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the constraints persisted in the schema of a table for rows
+/// written without the rust type. Custom checks fail unless they are skipped.
+pub(crate) fn check_dyn_constraints(
+    schema: &TableSchema,
+    rows: &[Value],
+    custom_checks: bool,
+) -> DbResult<()> {
+    let invalid = |field: &str, message| DbError::Validation {
+        table: schema.type_name.clone(),
+        field: Some(field.to_string()),
+        message,
+    };
+
+    let mut unique = HashMap::new();
+    for row in rows {
+        for constraint in &schema.constraints {
+            if !custom_checks && matches!(constraint.constraint, Constraint::Check(_)) {
+                continue;
+            }
+            let field = &constraint.field;
+            let value = row
+                .field(field)
+                .ok_or_else(|| invalid(field, "is missing".to_string()))?;
+            if constraint.constraint != Constraint::Unique {
+                constraint
+                    .check(value)
+                    .map_err(|message| invalid(field, message))?;
+            } else if let Some(other) =
+                unique.insert((field, value.encoded()), row.field(&schema.id_field))
+            {
+                return Err(invalid(
+                    field,
+                    format!(
+                        "must be unique but the entity with id {} has the same value",
+                        other.map(|id| id.to_string()).unwrap_or_default()
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_change_log(data: &[u8]) -> DbResult<Vec<ChangeRecord>> {
+    let mut reader = ByteReader::new(data);
+    let mut changes = vec![];
+    while !reader.is_at_end() {
+        changes.push(reader.read::<ChangeRecord>()?);
+    }
+    Ok(changes)
 }
 
-fn rlock_file(file: &PathBuf, guid: &str) -> PathBuf {
+fn rlock_file(file: &Path, guid: &str) -> PathBuf {
     file.with_extension(format!("{guid}-rlock"))
 }
 
 pub struct RLock {
-    file: PathBuf,
+    pub(crate) file: PathBuf,
     guid: String,
 }
 
@@ -300,8 +1443,8 @@ impl RLock {
         RLock { guid, file }
     }
 
-    pub fn get(&self) -> io::Result<File> {
-        File::open(&self.file)
+    pub fn get(&self) -> DbResult<File> {
+        File::open(&self.file).map_err(DbError::io_at(&self.file))
     }
 }
 
@@ -336,8 +1479,12 @@ impl WLock {
         WLock { file }
     }
 
-    pub fn get(&self) -> io::Result<File> {
-        OpenOptions::new().read(true).write(true).open(&self.file)
+    pub fn get(&self) -> DbResult<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.file)
+            .map_err(DbError::io_at(&self.file))
     }
 }
 
@@ -346,42 +1493,3 @@ impl Drop for WLock {
         fs::remove_file(self.file.with_extension("wlock")).unwrap();
     }
 }
-
-pub type DbResult<T> = Result<T, DbError>;
-
-#[derive(Debug)]
-pub enum DbError {
-    IdExists,
-    TypeNotFound,
-    IdNotFound,
-    IoError(std::io::Error),
-    LoadError,
-    InvalidFileVersion,
-}
-
-impl PartialEq for DbError {
-    fn eq(&self, other: &Self) -> bool {
-        match self {
-            Self::IdExists => matches!(other, Self::IdExists),
-            Self::TypeNotFound => matches!(other, Self::TypeNotFound),
-            Self::IdNotFound => matches!(other, Self::IdNotFound),
-            Self::IoError(_) => false,
-            Self::LoadError => matches!(other, Self::LoadError),
-            Self::InvalidFileVersion => matches!(other, Self::InvalidFileVersion),
-        }
-    }
-}
-
-impl From<std::io::Error> for DbError {
-    fn from(value: std::io::Error) -> Self {
-        Self::IoError(value)
-    }
-}
-
-impl std::fmt::Display for DbError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
-    }
-}
-
-impl Error for DbError {}
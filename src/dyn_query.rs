@@ -0,0 +1,879 @@
+//! Queries that are built at runtime and don't need the rust types of the tables.
+//!
+//! An [Expr] can either be built in code or [parsed](Expr::parse) from text like
+//! `age > 30 AND name LIKE 'A%'`. It is [checked](Expr::check) against the
+//! persisted [TableSchema] before it is run.
+
+use std::{cmp::Ordering, fmt::Display, mem::discriminant, ops::Not};
+
+use crate::{
+    db::{DbError, DbResult},
+    entity::Entity,
+    gen_query::like,
+    schema::{FieldType, TableSchema},
+    value::Value,
+};
+
+/// A dynamically typed query expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A field of the row.
+    Field(String),
+    /// A constant.
+    Value(Value),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+    Text(Box<Expr>, TextOp, Box<Expr>),
+    /// Whether the value is equal to one of the constants.
+    InList(Box<Expr>, Vec<Value>),
+    /// Whether an optional value is `None`.
+    IsNull(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+impl CompareOp {
+    fn holds(self, ord: Option<Ordering>) -> bool {
+        let Some(ord) = ord else {
+            return false;
+        };
+        match self {
+            Self::Eq => ord.is_eq(),
+            Self::Neq => ord.is_ne(),
+            Self::Lt => ord.is_lt(),
+            Self::Lte => ord.is_le(),
+            Self::Gt => ord.is_gt(),
+            Self::Gte => ord.is_ge(),
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Eq => "=",
+            Self::Neq => "!=",
+            Self::Lt => "<",
+            Self::Lte => "<=",
+            Self::Gt => ">",
+            Self::Gte => ">=",
+        }
+    }
+}
+
+/// Operations on two strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextOp {
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// An sql `LIKE` pattern, see [GenExpr::like](crate::gen_query::GenExpr::like).
+    Like,
+}
+
+impl TextOp {
+    fn holds(self, text: &str, other: &str) -> bool {
+        match self {
+            Self::Contains => text.contains(other),
+            Self::StartsWith => text.starts_with(other),
+            Self::EndsWith => text.ends_with(other),
+            Self::Like => like(text, other),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Contains => "contains",
+            Self::StartsWith => "starts_with",
+            Self::EndsWith => "ends_with",
+            Self::Like => "LIKE",
+        }
+    }
+}
+
+/// What an expression evaluates to, used to check expressions before they are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Bool,
+    Number,
+    Text,
+    Other,
+}
+
+impl Class {
+    fn of_type(ty: &FieldType) -> Self {
+        match ty {
+            FieldType::String => Self::Text,
+            FieldType::Option(inner) => Self::of_type(inner),
+            FieldType::List(_) | FieldType::Struct { .. } | FieldType::Serde(_) => Self::Other,
+            _ => Self::Number,
+        }
+    }
+
+    fn of_value(value: &Value) -> Self {
+        match value {
+            Value::String(_) => Self::Text,
+            Value::List(_) | Value::Struct(_) | Value::Bytes(_) | Value::Null => Self::Other,
+            _ => Self::Number,
+        }
+    }
+}
+
+impl Expr {
+    pub fn field(name: impl Into<String>) -> Self {
+        Self::Field(name.into())
+    }
+
+    pub fn value(value: Value) -> Self {
+        Self::Value(value)
+    }
+
+    /// Parses an expression like `age >= 18 AND NOT name LIKE 'A%'`.
+    ///
+    /// Strings are quoted with `'`, field names can be quoted with `"`.
+    /// Besides the comparison operators there are `AND`, `OR`, `NOT`, `LIKE`,
+    /// `IN (...)`, `BETWEEN ... AND ...`, `IS NULL`, `IS NOT NULL` and the
+    /// functions `contains`, `starts_with` and `ends_with`.
+    pub fn parse(src: &str) -> DbResult<Self> {
+        let mut parser = Parser::new(src)?;
+        let expr = parser.expr()?;
+        parser.finish()?;
+        Ok(expr)
+    }
+
+    fn compare(self, op: CompareOp, rhs: impl Into<Expr>) -> Self {
+        Self::Compare(Box::new(self), op, Box::new(rhs.into()))
+    }
+
+    fn text(self, op: TextOp, rhs: impl Into<Expr>) -> Self {
+        Self::Text(Box::new(self), op, Box::new(rhs.into()))
+    }
+
+    pub fn eq(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Eq, rhs)
+    }
+
+    pub fn neq(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Neq, rhs)
+    }
+
+    pub fn lt(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Lt, rhs)
+    }
+
+    pub fn lte(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Lte, rhs)
+    }
+
+    pub fn gt(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Gt, rhs)
+    }
+
+    pub fn gte(self, rhs: impl Into<Expr>) -> Self {
+        self.compare(CompareOp::Gte, rhs)
+    }
+
+    /// Checks whether the value lies within `low..=high`.
+    pub fn between(self, low: impl Into<Expr>, high: impl Into<Expr>) -> Self {
+        self.clone().gte(low).and(self.lte(high))
+    }
+
+    pub fn in_list(self, values: impl IntoIterator<Item = Value>) -> Self {
+        Self::InList(Box::new(self), values.into_iter().collect())
+    }
+
+    pub fn contains(self, rhs: impl Into<Expr>) -> Self {
+        self.text(TextOp::Contains, rhs)
+    }
+
+    pub fn starts_with(self, rhs: impl Into<Expr>) -> Self {
+        self.text(TextOp::StartsWith, rhs)
+    }
+
+    pub fn ends_with(self, rhs: impl Into<Expr>) -> Self {
+        self.text(TextOp::EndsWith, rhs)
+    }
+
+    pub fn like(self, pattern: impl Into<Expr>) -> Self {
+        self.text(TextOp::Like, pattern)
+    }
+
+    /// Checks whether an optional value is `None`.
+    pub fn is_null(self) -> Self {
+        Self::IsNull(Box::new(self))
+    }
+
+    /// Checks whether an optional value is `Some`.
+    pub fn is_some(self) -> Self {
+        !self.is_null()
+    }
+
+    pub fn and(self, rhs: Expr) -> Self {
+        Self::And(Box::new(self), Box::new(rhs))
+    }
+
+    pub fn or(self, rhs: Expr) -> Self {
+        Self::Or(Box::new(self), Box::new(rhs))
+    }
+
+    /// Checks that all fields exist in `schema`, that only compatible values
+    /// are compared and that the expression is a condition.
+    pub fn check(&self, schema: &TableSchema) -> DbResult<()> {
+        self.condition(schema)
+    }
+
+    fn class(&self, schema: &TableSchema) -> DbResult<Class> {
+        match self {
+            Self::Field(name) => schema
+                .field(name)
+                .map(|f| Class::of_type(&f.ty))
+                .ok_or_else(|| DbError::ParseError(format!("unknown field: {name}"))),
+            Self::Value(value) => Ok(Class::of_value(value)),
+            Self::Compare(lhs, op, rhs) => {
+                let class = lhs.operand(schema)?;
+                let ordered = !matches!(op, CompareOp::Eq | CompareOp::Neq);
+                if class != rhs.operand(schema)? || (ordered && class == Class::Other) {
+                    return Err(DbError::ParseError(format!(
+                        "cannot compare {lhs} and {rhs}"
+                    )));
+                }
+                Ok(Class::Bool)
+            }
+            Self::Text(lhs, op, rhs) => {
+                if lhs.operand(schema)? != Class::Text || rhs.operand(schema)? != Class::Text {
+                    return Err(DbError::ParseError(format!(
+                        "{} needs text operands: {self}",
+                        op.name()
+                    )));
+                }
+                Ok(Class::Bool)
+            }
+            Self::InList(expr, values) => {
+                let class = expr.operand(schema)?;
+                if let Some(value) = values.iter().find(|v| Class::of_value(v) != class) {
+                    return Err(DbError::ParseError(format!(
+                        "cannot compare {expr} and {}",
+                        Self::Value(value.clone())
+                    )));
+                }
+                Ok(Class::Bool)
+            }
+            Self::IsNull(expr) => {
+                expr.operand(schema)?;
+                Ok(Class::Bool)
+            }
+            Self::And(lhs, rhs) | Self::Or(lhs, rhs) => {
+                lhs.condition(schema)?;
+                rhs.condition(schema)?;
+                Ok(Class::Bool)
+            }
+            Self::Not(expr) => {
+                expr.condition(schema)?;
+                Ok(Class::Bool)
+            }
+        }
+    }
+
+    fn operand(&self, schema: &TableSchema) -> DbResult<Class> {
+        match self.class(schema)? {
+            Class::Bool => Err(DbError::ParseError(format!("{self} is not a value"))),
+            class => Ok(class),
+        }
+    }
+
+    fn condition(&self, schema: &TableSchema) -> DbResult<()> {
+        match self.class(schema)? {
+            Class::Bool => Ok(()),
+            _ => Err(DbError::ParseError(format!("{self} is not a condition"))),
+        }
+    }
+
+    /// Evaluates the condition for a row decoded with [Value::decode].
+    ///
+    /// Expressions that aren't [checked](Self::check) are false if
+    /// they don't fit the row.
+    pub fn matches(&self, row: &Value) -> bool {
+        match self {
+            Self::Field(_) | Self::Value(_) => false,
+            Self::Compare(lhs, op, rhs) => match (lhs.eval(row), rhs.eval(row)) {
+                (Some(a), Some(b)) => op.holds(compare(a, b)),
+                _ => false,
+            },
+            Self::Text(lhs, op, rhs) => match (lhs.eval(row), rhs.eval(row)) {
+                (Some(Value::String(a)), Some(Value::String(b))) => op.holds(a, b),
+                _ => false,
+            },
+            Self::InList(expr, values) => expr.eval(row).is_some_and(|value| {
+                values
+                    .iter()
+                    .any(|v| compare(value, v) == Some(Ordering::Equal))
+            }),
+            Self::IsNull(expr) => expr.eval(row) == Some(&Value::Null),
+            Self::And(lhs, rhs) => lhs.matches(row) && rhs.matches(row),
+            Self::Or(lhs, rhs) => lhs.matches(row) || rhs.matches(row),
+            Self::Not(expr) => !expr.matches(row),
+        }
+    }
+
+    fn eval<'a>(&'a self, row: &'a Value) -> Option<&'a Value> {
+        match self {
+            Self::Field(name) => row.field(name),
+            Self::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<Value> for Expr {
+    fn from(value: Value) -> Self {
+        Self::Value(value)
+    }
+}
+
+impl Not for Expr {
+    type Output = Expr;
+
+    fn not(self) -> Self::Output {
+        Self::Not(Box::new(self))
+    }
+}
+
+/// Formats the expression so it can be [parsed](Expr::parse) again.
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // `AND` binds stronger than `OR` and `NOT` stronger than both
+        let grouped = |expr: &Expr, f: &mut std::fmt::Formatter<'_>, or_only: bool| match expr {
+            Self::Or(..) => write!(f, "({expr})"),
+            Self::And(..) if !or_only => write!(f, "({expr})"),
+            _ => write!(f, "{expr}"),
+        };
+
+        match self {
+            Self::Field(name) => write_ident(f, name),
+            Self::Value(value) => write_value(f, value),
+            Self::Compare(lhs, op, rhs) => write!(f, "{lhs} {} {rhs}", op.symbol()),
+            Self::Text(lhs, TextOp::Like, rhs) => write!(f, "{lhs} LIKE {rhs}"),
+            Self::Text(lhs, op, rhs) => write!(f, "{}({lhs}, {rhs})", op.name()),
+            Self::InList(expr, values) => {
+                write!(f, "{expr} IN (")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_value(f, value)?;
+                }
+                write!(f, ")")
+            }
+            Self::IsNull(expr) => write!(f, "{expr} IS NULL"),
+            Self::And(lhs, rhs) => {
+                grouped(lhs, f, true)?;
+                write!(f, " AND ")?;
+                grouped(rhs, f, true)
+            }
+            Self::Or(lhs, rhs) => write!(f, "{lhs} OR {rhs}"),
+            Self::Not(expr) => {
+                write!(f, "NOT ")?;
+                grouped(expr, f, false)
+            }
+        }
+    }
+}
+
+fn write_ident(f: &mut std::fmt::Formatter<'_>, name: &str) -> std::fmt::Result {
+    let plain = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(name));
+    if plain {
+        write!(f, "{name}")
+    } else {
+        write!(f, "\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+fn write_value(f: &mut std::fmt::Formatter<'_>, value: &Value) -> std::fmt::Result {
+    match value {
+        Value::String(s) => write!(f, "'{}'", s.replace('\'', "''")),
+        Value::Null => write!(f, "NULL"),
+        value => write!(f, "{value}"),
+    }
+}
+
+/// Compares two values, numbers of different types are compared by their value.
+pub(crate) fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (number(a), number(b)) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ if discriminant(a) == discriminant(b) => a.partial_cmp(b),
+        _ => None,
+    }
+}
+
+/// A number of any of the integer types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Number {
+    // negative numbers are always smaller, so the order of the variants matters
+    Negative(i128),
+    Positive(u128),
+}
+
+fn number(value: &Value) -> Option<Number> {
+    let signed = |v: i128| match u128::try_from(v) {
+        Ok(v) => Number::Positive(v),
+        Err(_) => Number::Negative(v),
+    };
+    Some(match *value {
+        Value::U8(v) => Number::Positive(v.into()),
+        Value::U16(v) => Number::Positive(v.into()),
+        Value::U32(v) => Number::Positive(v.into()),
+        Value::U64(v) => Number::Positive(v.into()),
+        Value::U128(v) => Number::Positive(v),
+        Value::Usize(v) => Number::Positive(v as u128),
+        Value::I8(v) => signed(v.into()),
+        Value::I16(v) => signed(v.into()),
+        Value::I32(v) => signed(v.into()),
+        Value::I64(v) => signed(v.into()),
+        Value::I128(v) => signed(v),
+        Value::Isize(v) => signed(v as i128),
+        _ => return None,
+    })
+}
+
+/// A row returned by a dynamic query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub columns: Vec<(String, Value)>,
+}
+
+impl Row {
+    /// Gets the value of a column.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.columns.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    /// Converts the row to an entity, the row needs to have
+    /// all fields of the entity in the same order.
+    pub fn into_entity<T: Entity>(self) -> DbResult<T> {
+        let schema = T::table_schema();
+        if !self
+            .columns
+            .iter()
+            .map(|(name, _)| name)
+            .eq(schema.fields.iter().map(|f| &f.name))
+        {
+            return Err(DbError::SchemaMismatch {
+                table: schema.type_name,
+            });
+        }
+
+        let mut entity: T = Value::Struct(self.columns).to()?;
+        entity.after_load().map_err(DbError::validation::<T>)?;
+        Ok(entity)
+    }
+}
+
+impl From<Value> for Row {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Struct(columns) => Self { columns },
+            value => Self {
+                columns: vec![("value".to_string(), value)],
+            },
+        }
+    }
+}
+
+impl Display for Row {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Value::Struct(self.columns.clone()))
+    }
+}
+
+/// Words that can't be used as field names without quoting them.
+pub(crate) const KEYWORDS: &[&str] = &["AND", "OR", "NOT", "LIKE", "IN", "BETWEEN", "IS", "NULL"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Ident(String),
+    /// An identifier in double quotes which is never a keyword.
+    QuotedIdent(String),
+    Str(String),
+    Int(u128),
+    Symbol(&'static str),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ident(name) => write!(f, "{name}"),
+            Self::QuotedIdent(name) => write!(f, "\"{name}\""),
+            Self::Str(s) => write!(f, "'{s}'"),
+            Self::Int(v) => write!(f, "{v}"),
+            Self::Symbol(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "!=", "<>", "=", "<", ">", "(", ")", ",", "-", "*", ";",
+];
+
+fn tokenize(src: &str) -> DbResult<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = src;
+    loop {
+        rest = rest.trim_start();
+        let Some(c) = rest.chars().next() else {
+            return Ok(tokens);
+        };
+
+        if c == '\'' || c == '"' {
+            let (text, len) = quoted(rest, c)?;
+            tokens.push(if c == '\'' {
+                Token::Str(text)
+            } else {
+                Token::QuotedIdent(text)
+            });
+            rest = &rest[len..];
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let int = rest[..len]
+                .parse()
+                .map_err(|_| DbError::ParseError(format!("number too large: {}", &rest[..len])))?;
+            tokens.push(Token::Int(int));
+            rest = &rest[len..];
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(DbError::ParseError(format!("unexpected character: {c}")));
+        }
+    }
+}
+
+/// Reads text up to the closing `quote`, a doubled quote is an escaped quote.
+/// Returns the text and the length including the quotes.
+fn quoted(src: &str, quote: char) -> DbResult<(String, usize)> {
+    let mut text = String::new();
+    let mut chars = src.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != quote {
+            text.push(c);
+        } else if chars.next_if(|(_, c)| *c == quote).is_some() {
+            text.push(quote);
+        } else {
+            return Ok((text, i + 1));
+        }
+    }
+    Err(DbError::ParseError(format!("unterminated quote: {src}")))
+}
+
+/// A recursive descent parser for [Expr] which is also used for the
+/// statements of the sql dialect.
+pub(crate) struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    pub(crate) fn new(src: &str) -> DbResult<Self> {
+        Ok(Self {
+            tokens: tokenize(src)?,
+            pos: 0,
+        })
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    pub(crate) fn unexpected<T>(&self, expected: &str) -> DbResult<T> {
+        Err(DbError::ParseError(match self.peek() {
+            Some(token) => format!("expected {expected}, found {token}"),
+            None => format!("expected {expected}, found the end of the query"),
+        }))
+    }
+
+    /// Consumes the keyword if it is next.
+    pub(crate) fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Ident(i)) if i.eq_ignore_ascii_case(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    pub(crate) fn expect_keyword(&mut self, keyword: &str) -> DbResult<()> {
+        if !self.keyword(keyword) {
+            return self.unexpected(keyword);
+        }
+        Ok(())
+    }
+
+    /// Consumes the symbol if it is next.
+    pub(crate) fn symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    pub(crate) fn expect_symbol(&mut self, symbol: &str) -> DbResult<()> {
+        if !self.symbol(symbol) {
+            return self.unexpected(symbol);
+        }
+        Ok(())
+    }
+
+    /// Parses the name of a field or table.
+    pub(crate) fn ident(&mut self) -> DbResult<String> {
+        match self.peek() {
+            Some(Token::Ident(name)) if !KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            Some(Token::QuotedIdent(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("a name"),
+        }
+    }
+
+    /// Parses a string, an integer constant or `NULL`.
+    pub(crate) fn value(&mut self) -> DbResult<Value> {
+        if self.keyword("NULL") {
+            return Ok(Value::Null);
+        }
+        let negative = self.symbol("-");
+        match (self.peek(), negative) {
+            (Some(Token::Str(s)), false) => {
+                let value = Value::String(s.clone());
+                self.pos += 1;
+                Ok(value)
+            }
+            (Some(Token::Int(int)), _) => {
+                let value = int_value(*int, negative)?;
+                self.pos += 1;
+                Ok(value)
+            }
+            _ => self.unexpected("a value"),
+        }
+    }
+
+    /// Fails if not all of the input was parsed.
+    pub(crate) fn finish(&mut self) -> DbResult<()> {
+        self.symbol(";");
+        match self.peek() {
+            Some(_) => self.unexpected("the end of the query"),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn expr(&mut self) -> DbResult<Expr> {
+        let mut expr = self.and()?;
+        while self.keyword("OR") {
+            expr = expr.or(self.and()?);
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> DbResult<Expr> {
+        let mut expr = self.not()?;
+        while self.keyword("AND") {
+            expr = expr.and(self.not()?);
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> DbResult<Expr> {
+        if self.keyword("NOT") {
+            return Ok(!self.not()?);
+        }
+        self.predicate()
+    }
+
+    fn predicate(&mut self) -> DbResult<Expr> {
+        let lhs = self.operand()?;
+
+        if self.keyword("IS") {
+            let negated = self.keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(if negated {
+                lhs.is_some()
+            } else {
+                lhs.is_null()
+            });
+        }
+
+        for (symbol, op) in [
+            ("=", CompareOp::Eq),
+            ("!=", CompareOp::Neq),
+            ("<>", CompareOp::Neq),
+            ("<", CompareOp::Lt),
+            ("<=", CompareOp::Lte),
+            (">", CompareOp::Gt),
+            (">=", CompareOp::Gte),
+        ] {
+            if self.symbol(symbol) {
+                return Ok(lhs.compare(op, self.operand()?));
+            }
+        }
+
+        let negated = self.keyword("NOT");
+        let expr = if self.keyword("LIKE") {
+            lhs.like(self.operand()?)
+        } else if self.keyword("IN") {
+            self.expect_symbol("(")?;
+            let mut values = vec![self.value()?];
+            while self.symbol(",") {
+                values.push(self.value()?);
+            }
+            self.expect_symbol(")")?;
+            lhs.in_list(values)
+        } else if self.keyword("BETWEEN") {
+            let low = self.operand()?;
+            self.expect_keyword("AND")?;
+            lhs.between(low, self.operand()?)
+        } else if negated {
+            return self.unexpected("LIKE, IN or BETWEEN");
+        } else {
+            return Ok(lhs);
+        };
+
+        Ok(if negated { !expr } else { expr })
+    }
+
+    fn operand(&mut self) -> DbResult<Expr> {
+        if self.symbol("(") {
+            let expr = self.expr()?;
+            self.expect_symbol(")")?;
+            return Ok(expr);
+        }
+
+        match self.peek() {
+            Some(Token::Str(_) | Token::Int(_) | Token::Symbol("-")) => {
+                return Ok(Expr::Value(self.value()?));
+            }
+            Some(Token::Ident(name)) if name.eq_ignore_ascii_case("NULL") => {
+                return Ok(Expr::Value(self.value()?));
+            }
+            _ => {}
+        }
+
+        let name = self.ident()?;
+        if !self.symbol("(") {
+            return Ok(Expr::Field(name));
+        }
+
+        let op = match name.to_lowercase().as_str() {
+            "contains" => TextOp::Contains,
+            "starts_with" => TextOp::StartsWith,
+            "ends_with" => TextOp::EndsWith,
+            _ => return Err(DbError::ParseError(format!("unknown function: {name}"))),
+        };
+        let lhs = self.operand()?;
+        self.expect_symbol(",")?;
+        let rhs = self.operand()?;
+        self.expect_symbol(")")?;
+        Ok(lhs.text(op, rhs))
+    }
+}
+
+/// Converts a parsed integer to the smallest of `i64`, `u64`, `i128` and `u128` it fits in.
+fn int_value(int: u128, negative: bool) -> DbResult<Value> {
+    let too_large = || DbError::ParseError(format!("number too large: -{int}"));
+    if negative {
+        let int = i128::try_from(int)
+            .ok()
+            .and_then(|v| v.checked_neg())
+            .ok_or_else(too_large)?;
+        return Ok(i64::try_from(int).map_or(Value::I128(int), Value::I64));
+    }
+
+    Ok(if let Ok(v) = i64::try_from(int) {
+        Value::I64(v)
+    } else if let Ok(v) = u64::try_from(int) {
+        Value::U64(v)
+    } else {
+        Value::U128(int)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Expr, compare};
+    use crate::value::Value;
+
+    #[test]
+    fn parse_and_display() {
+        for src in [
+            "age > 30",
+            "age >= -5 AND name LIKE 'A%'",
+            "(a = 1 OR b = 2) AND NOT c IN (1, 2, 3)",
+            "NOT (a = 1 AND b != 'it''s')",
+            "contains(name, 'x') OR \"and\" < 3",
+            "nickname IS NULL AND NOT age IS NULL",
+        ] {
+            assert_eq!(Expr::parse(src).unwrap().to_string(), src);
+        }
+
+        assert_eq!(
+            Expr::parse("age between 1 and 5 or x not like 'y'").unwrap(),
+            Expr::field("age")
+                .gte(Value::I64(1))
+                .and(Expr::field("age").lte(Value::I64(5)))
+                .or(!Expr::field("x").like(Value::String("y".into())))
+        );
+        assert_eq!(
+            Expr::parse("a = 18446744073709551615").unwrap(),
+            Expr::field("a").eq(Value::U64(u64::MAX))
+        );
+
+        for src in [
+            "",
+            "a =",
+            "a = 'b",
+            "a = 1 b",
+            "and = 1",
+            "f(a, 'b')",
+            "a NOT = 1",
+        ] {
+            assert!(Expr::parse(src).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        use std::cmp::Ordering::*;
+
+        assert_eq!(compare(&Value::U8(30), &Value::I64(30)), Some(Equal));
+        assert_eq!(compare(&Value::I8(-1), &Value::U128(u128::MAX)), Some(Less));
+        assert_eq!(
+            compare(&Value::U128(u128::MAX), &Value::I128(5)),
+            Some(Greater)
+        );
+        assert_eq!(
+            compare(&Value::String("a".into()), &Value::String("b".into())),
+            Some(Less)
+        );
+        assert_eq!(compare(&Value::String("1".into()), &Value::I64(1)), None);
+    }
+}
@@ -1,12 +1,74 @@
-use crate::{gen_query::ExprEntity, id::IdType, storable::Storable};
+use crate::{
+    gen_query::ExprEntity,
+    id::IdType,
+    schema::{FieldConstraint, TableSchema},
+    storable::Storable,
+};
 
-pub trait Entity: Storable {
+pub trait Entity: Storable + EntityHooks {
     type Id: IdType;
     type ExprBase: ExprEntity<Self>;
 
     const GENERATE_ID: bool;
 
+    /// The name of the field holding the id.
+    const ID_FIELD: &'static str;
+
+    /// The fields marked as `#[fulltext]`, see [search](crate::db::Database::search).
+    const FULLTEXT_FIELDS: &'static [&'static str] = &[];
+
     fn get_id(&self) -> Self::Id;
 
     fn set_id(&mut self, id: Self::Id);
+
+    /// Checks the constraints declared on the fields of the entity like
+    /// `#[not_empty]`, `#[range(min = 0, max = 150)]`, `#[max_len(64)]`
+    /// and `#[check(function)]`. Returns the invalid field and the problem.
+    fn check_constraints(&self) -> Result<(), (&'static str, String)> {
+        Ok(())
+    }
+
+    /// The encoded values of the fields marked as `#[unique]`.
+    fn unique_values(&self) -> Vec<(&'static str, Vec<u8>)> {
+        vec![]
+    }
+
+    /// The constraints checked by [check_constraints](Self::check_constraints)
+    /// and [unique_values](Self::unique_values), persisted with the schema.
+    fn field_constraints() -> Vec<FieldConstraint> {
+        vec![]
+    }
+
+    /// The schema that gets persisted alongside the stored entities.
+    fn table_schema() -> TableSchema {
+        TableSchema::of::<Self>()
+    }
+}
+
+/// Callbacks run by the [Database](crate::db::Database) when entities are written or loaded.
+///
+/// The `entity` macro implements this trait with callbacks that do nothing
+/// unless the entity is declared with `#[entity(hooks)]`, in which case
+/// it has to be implemented manually. Returning an error aborts the
+/// operation with [DbError::Validation](crate::db::DbError::Validation).
+pub trait EntityHooks {
+    /// Called before a new entity is stored. Generated ids are already set.
+    fn before_insert(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called before an existing entity is replaced.
+    fn before_update(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called before an entity is deleted.
+    fn before_delete(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after an entity was read from the database.
+    fn after_load(&mut self) -> Result<(), String> {
+        Ok(())
+    }
 }
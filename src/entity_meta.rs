@@ -1,37 +1,542 @@
+use std::{
+    fs::File,
+    io::{BufReader, ErrorKind, Read},
+    path::PathBuf,
+};
+
 use crate::{
-    db::{DbError, DbResult},
+    byte_reader::ByteReader,
+    checksum::{crc32, crc32_update},
+    db::{DbError, DbResult, RLock},
     entity::Entity,
+    id::IdType,
+    schema::{FieldSchema, FieldType, TableSchema},
     storable::Storable,
     type_hash::TypeHash,
+    value::Value,
 };
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The version of the on-disk format written by this version of somedb.
+///
+/// It is independent of the crate version and only changes when the
+/// layout of stored tables changes. Older formats can still be read
+/// and are written in the current format the next time a table is written
+/// or when the database is [upgraded](crate::db::Database::upgrade).
+///
+/// - `1`: written by somedb 0.1, the version block contains the crate
+///   version and checksums are optional.
+/// - `2`: the version block contains the format version and every
+///   table ends with its checksums.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Finds the format version of a version block.
+///
+/// Returns `None` for versions that are newer than [FORMAT_VERSION] or unknown.
+pub(crate) fn parse_format_version(version: &str) -> Option<u32> {
+    if let Ok(format) = version.parse::<u32>() {
+        return (2..=FORMAT_VERSION).contains(&format).then_some(format);
+    }
+
+    // format 1 stored the crate version instead of a format version.
+    version
+        .strip_prefix("0.1.")
+        .filter(|patch| patch.parse::<u32>().is_ok())
+        .map(|_| 1)
+}
 
 #[derive(Clone)]
 pub struct EntityMeta<T: Entity> {
     pub last_id: T::Id,
     pub entities: Vec<T>,
 }
+
+impl<T: Entity> EntityMeta<T> {
+    /// A table without any entities.
+    pub(crate) fn empty() -> Self {
+        Self {
+            last_id: <T::Id as IdType>::initial(),
+            entities: vec![],
+        }
+    }
+
+    /// Decodes the content of a table file.
+    ///
+    /// The `after_load` hooks aren't run since the table is usually read to be
+    /// written again, see [loaded](Self::loaded) for entities returned to the user.
+    pub(crate) fn load(data: &[u8]) -> DbResult<Self> {
+        Self::decoded(ByteReader::new(data).reader_for_block()?)
+    }
+
+    /// The entities after running their `after_load` hooks.
+    pub(crate) fn loaded(mut self) -> DbResult<Vec<T>> {
+        for entity in &mut self.entities {
+            entity.after_load().map_err(DbError::validation::<T>)?;
+        }
+        Ok(self.entities)
+    }
+
+    /// Adds a new entity, generating its id if needed.
+    pub(crate) fn insert(&mut self, mut data: T) -> DbResult<T> {
+        if !T::GENERATE_ID && self.entities.iter().any(|e| e.get_id() == data.get_id()) {
+            return Err(DbError::id_exists::<T>(&data.get_id()));
+        }
+
+        if T::GENERATE_ID {
+            data.set_id(<T::Id as IdType>::generate(self.last_id))
+        }
+        data.before_insert().map_err(DbError::validation::<T>)?;
+
+        self.entities.push(data.clone());
+        self.last_id = data.get_id();
+        Ok(data)
+    }
+
+    /// Replaces all entities, running the hooks of the
+    /// inserted, updated and removed entities.
+    pub(crate) fn replace(&mut self, mut entities: Vec<T>) -> DbResult<()> {
+        for entity in &mut entities {
+            if self.entities.iter().any(|e| e.get_id() == entity.get_id()) {
+                entity.before_update()
+            } else {
+                entity.before_insert()
+            }
+            .map_err(DbError::validation::<T>)?;
+        }
+        for removed in self
+            .entities
+            .iter()
+            .filter(|e| !entities.iter().any(|n| n.get_id() == e.get_id()))
+        {
+            removed.before_delete().map_err(DbError::validation::<T>)?;
+        }
+
+        self.last_id = entities
+            .last()
+            .map(|e| e.get_id())
+            .unwrap_or_else(<T::Id as IdType>::initial);
+        self.entities = entities;
+        Ok(())
+    }
+
+    /// Replaces the stored entity with the same id.
+    pub(crate) fn update(&mut self, mut entity: T) -> DbResult<()> {
+        let res = self
+            .entities
+            .iter_mut()
+            .find(|e| e.get_id() == entity.get_id())
+            .ok_or_else(|| DbError::id_not_found::<T>(&entity.get_id()))?;
+
+        entity.before_update().map_err(DbError::validation::<T>)?;
+        *res = entity;
+        Ok(())
+    }
+
+    /// Removes the entity with the id, if there is one.
+    pub(crate) fn delete(&mut self, id: T::Id) -> DbResult<()> {
+        for entity in self.entities.iter().filter(|e| e.get_id() == id) {
+            entity.before_delete().map_err(DbError::validation::<T>)?;
+        }
+        self.entities.retain(|e| e.get_id() != id);
+        Ok(())
+    }
+}
+
 unsafe impl<T: Entity> Storable for EntityMeta<T> {
     fn type_hash() -> crate::type_hash::TypeHash {
         unsafe { TypeHash::new("", &[], &[]) }
     }
 
+    fn field_type() -> FieldType {
+        FieldType::Struct {
+            name: "EntityMeta".to_string(),
+            fields: vec![
+                FieldSchema::new("version", FieldType::String),
+                FieldSchema::new("last_id", T::Id::field_type()),
+                FieldSchema::new("entities", Vec::<T>::field_type()),
+            ],
+        }
+    }
+
     fn inner_encoded(&self) -> Vec<u8> {
-        let mut res = String::from(VERSION).encoded();
-        res.append(&mut self.last_id.encoded());
-        res.append(&mut self.entities.encoded());
+        encode_inner(
+            self.last_id.encoded(),
+            self.entities.iter().map(|e| e.encoded()).collect(),
+        )
+    }
+
+    fn decoded(reader: crate::byte_reader::ByteReader) -> DbResult<Self> {
+        let raw = RawEntityMeta::split(reader)?;
+        raw.check(std::any::type_name::<T>())?;
 
+        Ok(Self {
+            last_id: T::Id::decoded(raw.last_id)?,
+            entities: raw
+                .entities
+                .into_iter()
+                .map(T::decoded)
+                .collect::<DbResult<_>>()?,
+        })
+    }
+}
+
+/// The untyped equivalent of [EntityMeta] decoded using a persisted [TableSchema].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynEntityMeta {
+    pub last_id: Value,
+    pub entities: Vec<Value>,
+}
+
+impl DynEntityMeta {
+    /// Encodes the table like [EntityMeta] would, including the outer block length.
+    pub fn encoded(&self, schema: &TableSchema) -> Vec<u8> {
+        let row_type = schema.row_type();
+        with_len(encode_inner(
+            self.last_id.encoded(),
+            self.entities
+                .iter()
+                .map(|e| e.encoded_as(&row_type))
+                .collect(),
+        ))
+    }
+
+    pub fn decoded(schema: &TableSchema, reader: ByteReader) -> DbResult<Self> {
+        let raw = RawEntityMeta::split(reader)?;
+        raw.check(&schema.type_name)?;
+
+        let id_type = schema.id_type().ok_or_else(|| DbError::SchemaNotFound {
+            table: schema.name.clone(),
+        })?;
+        let row_type = schema.row_type();
+
+        Ok(Self {
+            last_id: Value::decode(id_type, raw.last_id)?,
+            entities: raw
+                .entities
+                .into_iter()
+                .map(|e| Value::decode(&row_type, e))
+                .collect::<DbResult<_>>()?,
+        })
+    }
+}
+
+/// Checksums stored after the entities of a table.
+///
+/// Files written before checksums were introduced don't contain them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checksums {
+    /// Checksum of the version, last id and entities blocks.
+    pub file: u32,
+    /// Checksum of the content of every entity block.
+    pub records: Vec<u32>,
+}
+
+unsafe impl Storable for Checksums {
+    fn type_hash() -> TypeHash {
+        unsafe { TypeHash::from_str("somedb::Checksums") }
+    }
+
+    fn field_type() -> FieldType {
+        FieldType::Struct {
+            name: "Checksums".to_string(),
+            fields: vec![
+                FieldSchema::new("file", FieldType::U32),
+                FieldSchema::new("records", Vec::<u32>::field_type()),
+            ],
+        }
+    }
+
+    fn inner_encoded(&self) -> Vec<u8> {
+        let mut res = self.file.encoded();
+        res.append(&mut self.records.encoded());
         res
     }
 
-    fn decoded(mut reader: crate::byte_reader::ByteReader) -> DbResult<Self> {
-        if String::decoded(reader.reader_for_block())? != VERSION {
-            return Err(DbError::InvalidFileVersion);
+    fn decoded(mut reader: ByteReader) -> DbResult<Self> {
+        Ok(Self {
+            file: reader.read::<u32>()?,
+            records: reader.read::<Vec<_>>()?,
+        })
+    }
+}
+
+/// The blocks of a stored table before the entities are decoded.
+pub struct RawEntityMeta<'a> {
+    pub version: String,
+    pub last_id: ByteReader<'a>,
+    pub entities: Vec<ByteReader<'a>>,
+    pub checksums: Option<Checksums>,
+    /// The checksum of the stored data as it was read.
+    pub file_checksum: u32,
+}
+
+impl<'a> RawEntityMeta<'a> {
+    /// Splits the content of a table block into its parts.
+    pub fn split(mut reader: ByteReader<'a>) -> DbResult<Self> {
+        let body = reader.read_byte_slice();
+        let body_start = reader.position();
+
+        let version = reader.read::<String>()?;
+        let last_id = reader.reader_for_block()?;
+
+        let mut entities_reader = reader.reader_for_block()?;
+        let mut entities = Vec::new();
+        while !entities_reader.is_at_end() {
+            entities.push(entities_reader.reader_for_block()?);
         }
+
+        let file_checksum = crc32(&body[..reader.position() - body_start]);
+
+        let checksums = if reader.is_at_end() {
+            None
+        } else {
+            Some(reader.read::<Checksums>()?)
+        };
+
         Ok(Self {
-            last_id: T::Id::decoded(reader.reader_for_block())?,
-            entities: Vec::decoded(reader.reader_for_block())?,
+            version,
+            last_id,
+            entities,
+            checksums,
+            file_checksum,
         })
     }
+
+    /// The format version of the table, `None` if it is not supported.
+    pub fn format_version(&self) -> Option<u32> {
+        parse_format_version(&self.version)
+    }
+
+    /// Checks the version and the file checksum if there is one.
+    pub fn check(&self, table: &str) -> DbResult<()> {
+        let format = self
+            .format_version()
+            .ok_or_else(|| DbError::InvalidFileVersion {
+                found: self.version.clone(),
+            })?;
+
+        let valid = match &self.checksums {
+            Some(checksums) => checksums.file == self.file_checksum,
+            None => format < 2,
+        };
+        if !valid {
+            return Err(DbError::ChecksumMismatch {
+                table: table.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Reads the entities of a stored table one at a time instead of loading the whole file.
+///
+/// The checksum of every entity is checked before it is returned. The read
+/// lock of the table is held until the last entity was read, at which point
+/// the file checksum is checked.
+pub(crate) struct EntityStream {
+    reader: BufReader<File>,
+    lock: Option<RLock>,
+    path: PathBuf,
+    table: String,
+    format: u32,
+    /// The encoded last id without its block length.
+    pub last_id: Vec<u8>,
+    /// The number of bytes of the entities block that weren't read yet.
+    remaining: usize,
+    /// The checksums after the entities, `None` for tables without checksums.
+    checksums: Option<Checksums>,
+    /// The number of entities that were read.
+    read: usize,
+    /// The offset of the end of the table block in the file.
+    end: usize,
+    /// The offset of the next byte in the file.
+    offset: usize,
+    crc: u32,
+    buf: Vec<u8>,
+}
+
+impl EntityStream {
+    pub fn new(lock: RLock, table: &str) -> DbResult<Self> {
+        let file = lock.get()?;
+        let file_len = file.metadata().map_err(DbError::io_at(&lock.file))?.len() as usize;
+        let mut stream = Self {
+            reader: BufReader::new(file),
+            path: lock.file.clone(),
+            lock: Some(lock),
+            table: table.to_string(),
+            format: FORMAT_VERSION,
+            last_id: vec![],
+            remaining: 0,
+            checksums: None,
+            read: 0,
+            end: file_len,
+            offset: 0,
+            crc: 0,
+            buf: vec![],
+        };
+
+        stream.end = stream.read_len("table")? + 4;
+        if stream.end > file_len {
+            return Err(stream.error("table", "block length exceeds the file"));
+        }
+        // the file checksum covers the table block without its length
+        stream.crc = 0;
+
+        stream.read_block("version")?;
+        let version = String::decoded(ByteReader::new(&stream.buf))?;
+        stream.format =
+            parse_format_version(&version).ok_or(DbError::InvalidFileVersion { found: version })?;
+
+        stream.read_block("last_id")?;
+        stream.last_id = std::mem::take(&mut stream.buf);
+
+        stream.remaining = stream.read_len("entities")?;
+        let trailing = stream
+            .end
+            .checked_sub(stream.offset + stream.remaining)
+            .ok_or_else(|| stream.error("entities", "the entities exceed the table block"))?;
+
+        if trailing > 0 {
+            stream.checksums = Some(stream.read_checksums(trailing)?);
+        }
+
+        Ok(stream)
+    }
+
+    /// Reads the checksums after the entities block without consuming the entities.
+    fn read_checksums(&mut self, len: usize) -> DbResult<Checksums> {
+        let (crc, offset) = (self.crc, self.offset);
+        let skip = self.remaining as i64;
+        self.reader
+            .seek_relative(skip)
+            .map_err(DbError::io_at(&self.path))?;
+        self.offset += self.remaining;
+
+        self.read_buf(len, "checksums")?;
+        let checksums = ByteReader::new(&self.buf).read::<Checksums>()?;
+
+        self.reader
+            .seek_relative(-(skip + len as i64))
+            .map_err(DbError::io_at(&self.path))?;
+        (self.crc, self.offset) = (crc, offset);
+        Ok(checksums)
+    }
+
+    /// Reads the next entity, `None` once all entities were read.
+    pub fn next_entity(&mut self) -> DbResult<Option<ByteReader<'_>>> {
+        if self.remaining == 0 {
+            self.finish()?;
+            return Ok(None);
+        }
+
+        if self.remaining < 4 {
+            return Err(self.error("entity", "unexpected end of data"));
+        }
+        let len = self.read_len("entity")?;
+        if len + 4 > self.remaining {
+            return Err(self.error(
+                "entity",
+                format!(
+                    "block length {len} exceeds the remaining {} bytes",
+                    self.remaining.saturating_sub(4)
+                ),
+            ));
+        }
+        self.read_buf(len, "entity")?;
+        self.remaining -= len + 4;
+
+        if let Some(checksums) = &self.checksums
+            && checksums.records.get(self.read) != Some(&crc32(&self.buf))
+        {
+            return Err(self.checksum_mismatch());
+        }
+        self.read += 1;
+        Ok(Some(ByteReader::new(&self.buf)))
+    }
+
+    /// Checks the file checksum and releases the lock.
+    fn finish(&mut self) -> DbResult<()> {
+        if self.lock.is_none() {
+            return Ok(());
+        }
+        self.lock = None;
+
+        let valid = match &self.checksums {
+            Some(checksums) => checksums.file == self.crc && checksums.records.len() == self.read,
+            None => self.format < 2,
+        };
+        if !valid {
+            return Err(self.checksum_mismatch());
+        }
+        Ok(())
+    }
+
+    fn read_len(&mut self, type_name: &'static str) -> DbResult<usize> {
+        self.read_buf(4, type_name)?;
+        Ok(u32::from_be_bytes(self.buf[..].try_into().unwrap()) as usize)
+    }
+
+    /// Reads a length prefixed block that has to fit into the table block.
+    fn read_block(&mut self, type_name: &'static str) -> DbResult<()> {
+        if self.end - self.offset < 4 {
+            return Err(self.error(type_name, "unexpected end of data"));
+        }
+        let len = self.read_len(type_name)?;
+        if len > self.end - self.offset {
+            return Err(self.error(
+                type_name,
+                format!(
+                    "block length {len} exceeds the remaining {} bytes",
+                    self.end - self.offset
+                ),
+            ));
+        }
+        self.read_buf(len, type_name)
+    }
+
+    fn read_buf(&mut self, len: usize, type_name: &'static str) -> DbResult<()> {
+        self.buf.resize(len, 0);
+        match self.reader.read_exact(&mut self.buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                return Err(self.error(type_name, "unexpected end of data"));
+            }
+            Err(e) => return Err(DbError::io_at(&self.path)(e)),
+        }
+        self.crc = crc32_update(self.crc, &self.buf);
+        self.offset += len;
+        Ok(())
+    }
+
+    fn checksum_mismatch(&self) -> DbError {
+        DbError::ChecksumMismatch {
+            table: self.table.clone(),
+        }
+    }
+
+    fn error(&self, type_name: &'static str, reason: impl Into<String>) -> DbError {
+        DbError::Decode {
+            type_name,
+            offset: self.offset,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Encodes the content of a table block from the encoded last id and entities.
+pub(crate) fn encode_inner(mut last_id: Vec<u8>, entities: Vec<Vec<u8>>) -> Vec<u8> {
+    let records = entities.iter().map(|e| crc32(&e[4..])).collect();
+
+    let mut res = FORMAT_VERSION.to_string().encoded();
+    res.append(&mut last_id);
+    res.append(&mut with_len(entities.concat()));
+
+    let file = crc32(&res);
+    res.append(&mut Checksums { file, records }.encoded());
+    res
+}
+
+pub(crate) fn with_len(mut data: Vec<u8>) -> Vec<u8> {
+    let mut vec = Vec::from((data.len() as u32).to_be_bytes());
+    vec.append(&mut data);
+    vec
 }
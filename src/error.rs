@@ -0,0 +1,293 @@
+//! Errors returned by SomeDb.
+
+use std::{
+    error::Error,
+    fmt::{Debug, Display},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{storable::Storable, value::Value};
+
+pub type DbResult<T> = Result<T, DbError>;
+
+#[derive(Debug)]
+pub enum DbError {
+    /// An entity with the same id is already stored.
+    IdExists {
+        table: String,
+        id: String,
+    },
+    /// Nothing has been stored for this entity type yet.
+    TypeNotFound {
+        table: String,
+    },
+    IdNotFound {
+        table: String,
+        id: String,
+    },
+    Io {
+        /// The file that was accessed if it is known.
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// The file was written by an incompatible version of SomeDb.
+    InvalidFileVersion {
+        found: String,
+    },
+    /// The table has no persisted schema, it is written
+    /// the next time the table is written from rust.
+    SchemaNotFound {
+        table: String,
+    },
+    ParseError(String),
+    ChecksumMismatch {
+        table: String,
+    },
+    /// Stored data could not be decoded.
+    Decode {
+        type_name: &'static str,
+        /// Byte offset from the start of the decoded data.
+        offset: usize,
+        reason: String,
+    },
+    /// There is no snapshot to restore in the backup directory.
+    BackupNotFound {
+        path: PathBuf,
+    },
+    /// The stored schema of a table can't be read with the current definition of the entity.
+    SchemaMismatch {
+        table: String,
+    },
+    /// An entity was rejected by its [hooks](crate::entity::EntityHooks)
+    /// or the constraints declared on its fields.
+    Validation {
+        table: String,
+        /// The invalid field if the problem is limited to a single field.
+        field: Option<String>,
+        message: String,
+    },
+    /// The server of a [RemoteDatabase](crate::remote::RemoteDatabase) failed.
+    Remote {
+        /// The kind of the error on the server.
+        kind: ErrorKind,
+        message: String,
+    },
+}
+
+/// The kind of a [DbError] without any of the details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    IdExists,
+    TypeNotFound,
+    IdNotFound,
+    Io,
+    InvalidFileVersion,
+    SchemaNotFound,
+    Parse,
+    ChecksumMismatch,
+    Decode,
+    BackupNotFound,
+    SchemaMismatch,
+    Validation,
+}
+
+impl DbError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::IdExists { .. } => ErrorKind::IdExists,
+            Self::TypeNotFound { .. } => ErrorKind::TypeNotFound,
+            Self::IdNotFound { .. } => ErrorKind::IdNotFound,
+            Self::Io { .. } => ErrorKind::Io,
+            Self::InvalidFileVersion { .. } => ErrorKind::InvalidFileVersion,
+            Self::SchemaNotFound { .. } => ErrorKind::SchemaNotFound,
+            Self::ParseError(_) => ErrorKind::Parse,
+            Self::ChecksumMismatch { .. } => ErrorKind::ChecksumMismatch,
+            Self::Decode { .. } => ErrorKind::Decode,
+            Self::BackupNotFound { .. } => ErrorKind::BackupNotFound,
+            Self::SchemaMismatch { .. } => ErrorKind::SchemaMismatch,
+            Self::Validation { .. } => ErrorKind::Validation,
+            Self::Remote { kind, .. } => *kind,
+        }
+    }
+
+    pub(crate) fn id_exists<T>(id: &impl Storable) -> Self {
+        Self::IdExists {
+            table: std::any::type_name::<T>().to_string(),
+            id: id_string(id),
+        }
+    }
+
+    pub(crate) fn type_not_found<T>() -> Self {
+        Self::TypeNotFound {
+            table: std::any::type_name::<T>().to_string(),
+        }
+    }
+
+    pub(crate) fn id_not_found<T>(id: &impl Storable) -> Self {
+        Self::IdNotFound {
+            table: std::any::type_name::<T>().to_string(),
+            id: id_string(id),
+        }
+    }
+
+    pub(crate) fn validation<T>(message: String) -> Self {
+        Self::Validation {
+            table: std::any::type_name::<T>().to_string(),
+            field: None,
+            message,
+        }
+    }
+
+    /// Creates a function that adds `path` to io errors.
+    pub(crate) fn io_at(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |source| Self::Io {
+            path: Some(path.to_path_buf()),
+            source,
+        }
+    }
+}
+
+impl PartialEq for DbError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::IdExists { table: t1, id: i1 }, Self::IdExists { table: t2, id: i2 }) => {
+                t1 == t2 && i1 == i2
+            }
+            (Self::TypeNotFound { table: t1 }, Self::TypeNotFound { table: t2 }) => t1 == t2,
+            (Self::IdNotFound { table: t1, id: i1 }, Self::IdNotFound { table: t2, id: i2 }) => {
+                t1 == t2 && i1 == i2
+            }
+            // io errors can't be compared so only their kind is used.
+            (
+                Self::Io {
+                    path: p1,
+                    source: s1,
+                },
+                Self::Io {
+                    path: p2,
+                    source: s2,
+                },
+            ) => p1 == p2 && s1.kind() == s2.kind(),
+            (Self::InvalidFileVersion { found: f1 }, Self::InvalidFileVersion { found: f2 }) => {
+                f1 == f2
+            }
+            (Self::SchemaNotFound { table: t1 }, Self::SchemaNotFound { table: t2 }) => t1 == t2,
+            (Self::ParseError(a), Self::ParseError(b)) => a == b,
+            (Self::ChecksumMismatch { table: t1 }, Self::ChecksumMismatch { table: t2 }) => {
+                t1 == t2
+            }
+            (
+                Self::Decode {
+                    type_name: t1,
+                    offset: o1,
+                    reason: r1,
+                },
+                Self::Decode {
+                    type_name: t2,
+                    offset: o2,
+                    reason: r2,
+                },
+            ) => t1 == t2 && o1 == o2 && r1 == r2,
+            (Self::BackupNotFound { path: p1 }, Self::BackupNotFound { path: p2 }) => p1 == p2,
+            (Self::SchemaMismatch { table: t1 }, Self::SchemaMismatch { table: t2 }) => t1 == t2,
+            (
+                Self::Validation {
+                    table: t1,
+                    field: f1,
+                    message: m1,
+                },
+                Self::Validation {
+                    table: t2,
+                    field: f2,
+                    message: m2,
+                },
+            ) => t1 == t2 && f1 == f2 && m1 == m2,
+            (
+                Self::Remote {
+                    kind: k1,
+                    message: m1,
+                },
+                Self::Remote {
+                    kind: k2,
+                    message: m2,
+                },
+            ) => k1 == k2 && m1 == m2,
+            _ => false,
+        }
+    }
+}
+
+impl From<io::Error> for DbError {
+    fn from(source: io::Error) -> Self {
+        Self::Io { path: None, source }
+    }
+}
+
+/// Describes an id for error messages, ids don't have to implement `Debug`.
+pub(crate) fn id_string(id: &impl Storable) -> String {
+    Value::of(id).map_or_else(|_| "?".to_string(), |id| id.to_string())
+}
+
+impl Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IdExists { table, id } => {
+                write!(f, "an entity of type {table} with id {id} already exists")
+            }
+            Self::TypeNotFound { table } => write!(f, "no entities of type {table} are stored"),
+            Self::IdNotFound { table, id } => {
+                write!(f, "there is no entity of type {table} with id {id}")
+            }
+            Self::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "could not access {}: {source}", path.display()),
+            Self::Io { path: None, source } => write!(f, "io error: {source}"),
+            Self::InvalidFileVersion { found } => {
+                write!(f, "the data was written by incompatible version {found}")
+            }
+            Self::SchemaNotFound { table } => write!(f, "no schema is stored for table {table}"),
+            Self::ParseError(msg) => write!(f, "{msg}"),
+            Self::ChecksumMismatch { table } => {
+                write!(f, "the checksum of table {table} does not match its data")
+            }
+            Self::Decode {
+                type_name,
+                offset,
+                reason,
+            } => write!(
+                f,
+                "could not decode {type_name} at offset {offset}: {reason}"
+            ),
+            Self::BackupNotFound { path } => {
+                write!(f, "no snapshot to restore in {}", path.display())
+            }
+            Self::SchemaMismatch { table } => write!(
+                f,
+                "the stored data of {table} is incompatible with its current definition"
+            ),
+            Self::Validation {
+                table,
+                field: Some(field),
+                message,
+            } => write!(f, "invalid {table}.{field}: {message}"),
+            Self::Validation {
+                table,
+                field: None,
+                message,
+            } => write!(f, "invalid {table}: {message}"),
+            Self::Remote { message, .. } => write!(f, "server error: {message}"),
+        }
+    }
+}
+
+impl Error for DbError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
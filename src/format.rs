@@ -0,0 +1,387 @@
+//! Text formats used to import and export table rows.
+
+use std::{
+    io::{BufRead, BufReader, Lines, Read, Write},
+    str::FromStr,
+};
+
+use crate::{
+    db::{DbError, DbResult},
+    json::Json,
+    schema::{FieldType, TableSchema},
+    value::{Value, from_hex, hex},
+};
+
+/// A text format rows can be exported to and imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A single json array containing one object per row.
+    Json,
+    /// One json object per line.
+    NdJson,
+    /// Comma separated values with a header line. Lists and
+    /// nested structs are written as json, `None` as an empty cell.
+    /// An empty cell of an optional string is therefore read as `None`,
+    /// just like a missing column of an optional field.
+    Csv,
+}
+
+/// How the ids of imported entities are chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdMode {
+    /// Generates new ids for entities with [generated ids](crate::entity::Entity::GENERATE_ID)
+    /// and keeps the imported ids otherwise.
+    #[default]
+    Auto,
+    /// Keeps the imported ids. Importing an id that already exists is an error.
+    Preserve,
+    /// Generates new ids for all imported entities.
+    Regenerate,
+}
+
+impl FromStr for Format {
+    type Err = DbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::NdJson),
+            "csv" => Ok(Self::Csv),
+            _ => Err(DbError::ParseError(format!("unknown format: {s}"))),
+        }
+    }
+}
+
+/// Converts a value to json.
+pub(crate) fn to_json(value: &Value) -> Json {
+    match value {
+        Value::String(s) => Json::String(s.clone()),
+        Value::List(values) => Json::Array(values.iter().map(to_json).collect()),
+        Value::Struct(fields) => Json::Object(
+            fields
+                .iter()
+                .map(|(name, v)| (name.clone(), to_json(v)))
+                .collect(),
+        ),
+        Value::Bytes(bytes) => Json::String(hex(bytes)),
+        Value::Null => Json::Null,
+        number => Json::Number(number.to_string()),
+    }
+}
+
+fn parse_number(ty: &FieldType, src: &str) -> DbResult<Value> {
+    let err = |_| DbError::ParseError(format!("invalid {ty}: {src}"));
+    Ok(match ty {
+        FieldType::U8 => Value::U8(src.parse().map_err(err)?),
+        FieldType::U16 => Value::U16(src.parse().map_err(err)?),
+        FieldType::U32 => Value::U32(src.parse().map_err(err)?),
+        FieldType::U64 => Value::U64(src.parse().map_err(err)?),
+        FieldType::U128 => Value::U128(src.parse().map_err(err)?),
+        FieldType::Usize => Value::Usize(src.parse().map_err(err)?),
+        FieldType::I8 => Value::I8(src.parse().map_err(err)?),
+        FieldType::I16 => Value::I16(src.parse().map_err(err)?),
+        FieldType::I32 => Value::I32(src.parse().map_err(err)?),
+        FieldType::I64 => Value::I64(src.parse().map_err(err)?),
+        FieldType::I128 => Value::I128(src.parse().map_err(err)?),
+        FieldType::Isize => Value::Isize(src.parse().map_err(err)?),
+        _ => return Err(DbError::ParseError(format!("{ty} is not a number"))),
+    })
+}
+
+/// Converts json to a value of type `ty`.
+///
+/// Missing fields of structs and `null` are `None` for optional fields.
+pub(crate) fn from_json(ty: &FieldType, json: &Json) -> DbResult<Value> {
+    match (ty, json) {
+        (FieldType::Option(_), Json::Null) => Ok(Value::Null),
+        (FieldType::Option(inner), json) => from_json(inner, json),
+        (FieldType::String, Json::String(s)) => Ok(Value::String(s.clone())),
+        (FieldType::Serde(_), Json::String(s)) => from_hex(s)
+            .map(Value::Bytes)
+            .ok_or_else(|| DbError::ParseError(format!("invalid hex bytes: {s}"))),
+        (FieldType::List(inner), Json::Array(values)) => Ok(Value::List(
+            values
+                .iter()
+                .map(|v| from_json(inner, v))
+                .collect::<DbResult<_>>()?,
+        )),
+        (FieldType::Struct { fields, .. }, Json::Object(_)) => Ok(Value::Struct(
+            fields
+                .iter()
+                .map(|f| {
+                    let v = match (json.get(&f.name), &f.ty) {
+                        (Some(v), _) => v,
+                        (None, FieldType::Option(_)) => &Json::Null,
+                        (None, _) => {
+                            return Err(DbError::ParseError(format!("missing field: {}", f.name)));
+                        }
+                    };
+                    Ok((f.name.clone(), from_json(&f.ty, v)?))
+                })
+                .collect::<DbResult<_>>()?,
+        )),
+        (_, Json::Number(n)) => parse_number(ty, n),
+        _ => Err(DbError::ParseError(format!("expected {ty}, found {json}"))),
+    }
+}
+
+fn csv_cell(value: &Value) -> String {
+    let raw = match value {
+        Value::String(s) => s.clone(),
+        Value::List(_) | Value::Struct(_) => to_json(value).to_string(),
+        Value::Bytes(bytes) => hex(bytes),
+        Value::Null => String::new(),
+        number => number.to_string(),
+    };
+
+    if raw.contains([',', '"', '\n', '\r']) || raw.trim() != raw {
+        format!("\"{}\"", raw.replace('"', "\"\""))
+    } else {
+        raw
+    }
+}
+
+/// Parses a value from a csv cell or another text like an url parameter.
+///
+/// An empty text is `None` for optional fields.
+pub(crate) fn from_text(ty: &FieldType, cell: &str) -> DbResult<Value> {
+    match ty {
+        FieldType::Option(_) if cell.is_empty() => Ok(Value::Null),
+        FieldType::Option(inner) => from_text(inner, cell),
+        FieldType::String => Ok(Value::String(cell.to_string())),
+        FieldType::Serde(_) => from_json(ty, &Json::String(cell.to_string())),
+        FieldType::List(_) | FieldType::Struct { .. } => {
+            from_json(ty, &Json::parse(cell).map_err(DbError::ParseError)?)
+        }
+        _ => parse_number(ty, cell.trim()),
+    }
+}
+
+/// Splits csv text into records.
+fn csv_records(src: &str) -> DbResult<Vec<Vec<String>>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = src.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => cell.push(c),
+            (false, '"') if cell.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut cell)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut cell));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => cell.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(DbError::ParseError("unterminated quote in csv".to_string()));
+    }
+    if !cell.is_empty() || !record.is_empty() {
+        record.push(cell);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Writes rows of a table one at a time.
+pub struct RowWriter<'a, W: Write> {
+    writer: W,
+    schema: &'a TableSchema,
+    format: Format,
+    rows: usize,
+}
+
+impl<'a, W: Write> RowWriter<'a, W> {
+    pub fn new(mut writer: W, schema: &'a TableSchema, format: Format) -> DbResult<Self> {
+        match format {
+            Format::Json => write!(writer, "[")?,
+            Format::NdJson => {}
+            Format::Csv => {
+                let header: Vec<_> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+                writeln!(writer, "{}", header.join(","))?;
+            }
+        }
+
+        Ok(Self {
+            writer,
+            schema,
+            format,
+            rows: 0,
+        })
+    }
+
+    pub fn write_row(&mut self, row: &Value) -> DbResult<()> {
+        match self.format {
+            Format::Json => {
+                if self.rows != 0 {
+                    write!(self.writer, ",")?;
+                }
+                write!(self.writer, "\n{}", to_json(row))?;
+            }
+            Format::NdJson => writeln!(self.writer, "{}", to_json(row))?,
+            Format::Csv => {
+                let cells: Vec<_> = self
+                    .schema
+                    .fields
+                    .iter()
+                    .map(|f| row.field(&f.name).map(csv_cell).unwrap_or_default())
+                    .collect();
+                writeln!(self.writer, "{}", cells.join(","))?;
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Finishes the output and returns the number of written rows.
+    pub fn finish(mut self) -> DbResult<usize> {
+        if self.format == Format::Json {
+            writeln!(self.writer, "\n]")?;
+        }
+        self.writer.flush()?;
+        Ok(self.rows)
+    }
+}
+
+/// Reads rows of a table one at a time.
+///
+/// Ndjson and csv are read line by line, a json array is parsed completely
+/// before the first row is returned.
+pub struct RowReader<'a, R: Read> {
+    lines: Lines<BufReader<R>>,
+    schema: &'a TableSchema,
+    row_type: FieldType,
+    format: Format,
+    /// The column names of a csv file.
+    header: Vec<String>,
+    json_rows: std::vec::IntoIter<Json>,
+}
+
+impl<'a, R: Read> RowReader<'a, R> {
+    pub fn new(reader: R, schema: &'a TableSchema, format: Format) -> DbResult<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut json_rows = vec![];
+        if format == Format::Json {
+            let mut src = String::new();
+            reader.read_to_string(&mut src)?;
+            match Json::parse(&src).map_err(DbError::ParseError)? {
+                Json::Array(rows) => json_rows = rows,
+                _ => return Err(DbError::ParseError("expected a json array".to_string())),
+            }
+        }
+
+        let mut rows = Self {
+            lines: reader.lines(),
+            row_type: schema.row_type(),
+            schema,
+            format,
+            header: vec![],
+            json_rows: json_rows.into_iter(),
+        };
+        if format == Format::Csv {
+            rows.header = rows.next_record()?.unwrap_or_default();
+        }
+        Ok(rows)
+    }
+
+    /// Reads the next non empty line.
+    fn next_line(&mut self) -> DbResult<Option<String>> {
+        for line in self.lines.by_ref() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                return Ok(Some(line));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the lines of the next csv record, which can span
+    /// multiple lines if a quoted cell contains a line break.
+    fn next_record(&mut self) -> DbResult<Option<Vec<String>>> {
+        let Some(mut src) = self.next_line()? else {
+            return Ok(None);
+        };
+        while src.matches('"').count() % 2 == 1 {
+            match self.lines.next().transpose()? {
+                Some(line) => {
+                    src.push('\n');
+                    src.push_str(&line);
+                }
+                None => break,
+            }
+        }
+        Ok(csv_records(&src)?.pop())
+    }
+
+    fn csv_row(&self, record: Vec<String>) -> DbResult<Value> {
+        Ok(Value::Struct(
+            self.schema
+                .fields
+                .iter()
+                .map(|f| {
+                    let cell = self
+                        .header
+                        .iter()
+                        .position(|h| h == &f.name)
+                        .and_then(|i| record.get(i));
+                    let value = match (cell, &f.ty) {
+                        (Some(cell), _) => from_text(&f.ty, cell)?,
+                        (None, FieldType::Option(_)) => Value::Null,
+                        (None, _) => {
+                            return Err(DbError::ParseError(format!("missing column: {}", f.name)));
+                        }
+                    };
+                    Ok((f.name.clone(), value))
+                })
+                .collect::<DbResult<_>>()?,
+        ))
+    }
+
+    fn next_row(&mut self) -> DbResult<Option<Value>> {
+        match self.format {
+            Format::Json => self
+                .json_rows
+                .next()
+                .map(|row| from_json(&self.row_type, &row))
+                .transpose(),
+            Format::NdJson => self
+                .next_line()?
+                .map(|line| {
+                    from_json(
+                        &self.row_type,
+                        &Json::parse(&line).map_err(DbError::ParseError)?,
+                    )
+                })
+                .transpose(),
+            Format::Csv => self
+                .next_record()?
+                .map(|record| self.csv_row(record))
+                .transpose(),
+        }
+    }
+}
+
+impl<R: Read> Iterator for RowReader<'_, R> {
+    type Item = DbResult<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_row().transpose()
+    }
+}
+
+/// Reads all rows in `format` and converts them to the row type of `schema`.
+pub fn read_rows(reader: impl Read, schema: &TableSchema, format: Format) -> DbResult<Vec<Value>> {
+    RowReader::new(reader, schema, format)?.collect()
+}
@@ -0,0 +1,464 @@
+//! Full-text search over the `#[fulltext]` fields of an entity.
+//!
+//! Text is split into words at every character that isn't alphanumeric and
+//! the words are lowercased, there is no stemming. Every table with full-text
+//! fields has an inverted index next to its data file that maps each word to
+//! the rows and positions it occurs at. The index is rebuilt whenever the
+//! table is written and remembers a checksum of the data it was built from,
+//! so an index that doesn't match the table anymore, for example after a
+//! restore, is rebuilt by the next search.
+//!
+//! Queries are searched with [Database::search](crate::db::Database::search)
+//! and consist of terms that all have to match:
+//!
+//! - `word` matches the word in any case
+//! - `pre*` matches every word starting with `pre`
+//! - `"two words"` matches the words next to each other, as does `two-words`
+//!
+//! Matching entities are ranked by their [BM25] score.
+//!
+//! [BM25]: https://en.wikipedia.org/wiki/Okapi_BM25
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    byte_reader::ByteReader,
+    db::{DbError, DbResult, Fingerprint, fingerprint},
+    entity::Entity,
+    entity_meta::DynEntityMeta,
+    schema::TableSchema,
+    storable::Storable,
+    value::Value,
+};
+
+/// Changes whenever the encoding of the index changes, older indexes are rebuilt.
+const INDEX_VERSION: u32 = 1;
+
+/// How quickly repeated words stop raising the score.
+const K1: f64 = 1.2;
+/// How much the score is normalized by the length of the text.
+const B: f64 = 0.75;
+
+/// An entity found by a search and its relevance, higher is better.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit<T: Entity> {
+    pub entity: T,
+    pub score: f64,
+}
+
+/// Splits text into lowercase words.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pattern {
+    Word(String),
+    Prefix(String),
+}
+
+/// A parsed search query, every term is a phrase of one or more words.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SearchQuery {
+    terms: Vec<Vec<Pattern>>,
+}
+
+impl SearchQuery {
+    pub(crate) fn parse(src: &str) -> DbResult<Self> {
+        let mut terms = vec![];
+        let mut rest = src;
+        loop {
+            rest = rest.trim_start();
+            let phrase = if let Some(quoted) = rest.strip_prefix('"') {
+                let (phrase, after) = quoted.split_once('"').ok_or_else(|| {
+                    DbError::ParseError(format!("unterminated phrase in search query: {src}"))
+                })?;
+                rest = after;
+                phrase
+            } else {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == '"')
+                    .unwrap_or(rest.len());
+                let (word, after) = rest.split_at(end);
+                rest = after;
+                word
+            };
+            if phrase.is_empty() && rest.is_empty() {
+                break;
+            }
+
+            let mut patterns = vec![];
+            for chunk in phrase.split_whitespace() {
+                let mut words: Vec<_> = tokenize(chunk).into_iter().map(Pattern::Word).collect();
+                if chunk.ends_with('*')
+                    && let Some(Pattern::Word(last)) = words.pop()
+                {
+                    words.push(Pattern::Prefix(last));
+                }
+                patterns.append(&mut words);
+            }
+            if !patterns.is_empty() {
+                terms.push(patterns);
+            }
+        }
+        Ok(Self { terms })
+    }
+}
+
+/// The rows and positions a word occurs at.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Postings {
+    rows: Vec<u32>,
+    positions: Vec<Vec<u32>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FieldIndex {
+    /// The number of words in every row.
+    lengths: Vec<u32>,
+    words: BTreeMap<String, Postings>,
+}
+
+impl FieldIndex {
+    fn build<'a>(texts: impl Iterator<Item = &'a str>) -> Self {
+        let mut lengths = vec![];
+        let mut words = BTreeMap::<_, Postings>::new();
+        for (row, text) in texts.enumerate() {
+            let tokens = tokenize(text);
+            lengths.push(tokens.len() as u32);
+            for (position, token) in tokens.into_iter().enumerate() {
+                let postings = words.entry(token).or_default();
+                if postings.rows.last() != Some(&(row as u32)) {
+                    postings.rows.push(row as u32);
+                    postings.positions.push(vec![]);
+                }
+                postings.positions.last_mut().unwrap().push(position as u32);
+            }
+        }
+        Self { lengths, words }
+    }
+
+    /// The sorted positions of the words matching `pattern` in every row.
+    fn positions(&self, pattern: &Pattern) -> HashMap<u32, Vec<u32>> {
+        let postings: Vec<_> = match pattern {
+            Pattern::Word(word) => self.words.get(word).into_iter().collect(),
+            Pattern::Prefix(prefix) => self
+                .words
+                .range(prefix.clone()..)
+                .take_while(|(word, _)| word.starts_with(prefix.as_str()))
+                .map(|(_, postings)| postings)
+                .collect(),
+        };
+
+        let mut res = HashMap::<_, Vec<_>>::new();
+        for postings in postings {
+            for (row, positions) in postings.rows.iter().zip(&postings.positions) {
+                res.entry(*row).or_default().extend(positions);
+            }
+        }
+        for positions in res.values_mut() {
+            positions.sort_unstable();
+        }
+        res
+    }
+
+    /// How often the phrase occurs in every row it occurs in.
+    fn frequencies(&self, phrase: &[Pattern]) -> HashMap<u32, u32> {
+        let patterns: Vec<_> = phrase.iter().map(|p| self.positions(p)).collect();
+        let Some((first, rest)) = patterns.split_first() else {
+            return HashMap::new();
+        };
+
+        first
+            .iter()
+            .filter_map(|(row, starts)| {
+                let count = starts
+                    .iter()
+                    .filter(|&&start| {
+                        rest.iter().zip(1..).all(|(positions, offset)| {
+                            positions
+                                .get(row)
+                                .is_some_and(|p| p.binary_search(&(start + offset)).is_ok())
+                        })
+                    })
+                    .count() as u32;
+                (count > 0).then_some((*row, count))
+            })
+            .collect()
+    }
+
+    /// The rows matching every term of the query and their scores.
+    fn search(&self, query: &SearchQuery) -> DbResult<Vec<(u32, f64)>> {
+        let rows = self.lengths.len() as f64;
+        let average_len =
+            (self.lengths.iter().map(|&l| l as f64).sum::<f64>() / rows.max(1.0)).max(1.0);
+
+        let mut scores: Option<HashMap<u32, f64>> = None;
+        for term in &query.terms {
+            let frequencies = self.frequencies(term);
+            let matches = frequencies.len() as f64;
+            let idf = (1.0 + (rows - matches + 0.5) / (matches + 0.5)).ln();
+
+            let term_scores = frequencies
+                .into_iter()
+                .map(|(row, frequency)| {
+                    let frequency = frequency as f64;
+                    let len = *self
+                        .lengths
+                        .get(row as usize)
+                        .ok_or_else(|| DbError::Decode {
+                            type_name: "FieldIndex",
+                            offset: 0,
+                            reason: format!("no length for row {row}"),
+                        })? as f64;
+                    let score = idf * frequency * (K1 + 1.0)
+                        / (frequency + K1 * (1.0 - B + B * len / average_len));
+                    Ok((row, score))
+                })
+                .collect::<DbResult<Vec<_>>>()?;
+
+            scores = Some(match scores {
+                None => term_scores.into_iter().collect(),
+                Some(scores) => term_scores
+                    .into_iter()
+                    .filter_map(|(row, score)| Some((row, scores.get(&row)? + score)))
+                    .collect(),
+            });
+        }
+
+        let mut res: Vec<_> = scores.unwrap_or_default().into_iter().collect();
+        res.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        Ok(res)
+    }
+
+    fn encoded(&self) -> Vec<u8> {
+        let (words, postings): (Vec<_>, Vec<_>) = self.words.iter().unzip();
+        let mut res = self.lengths.encoded();
+        res.append(&mut words.into_iter().cloned().collect::<Vec<_>>().encoded());
+        res.append(
+            &mut postings
+                .iter()
+                .map(|p| p.rows.clone())
+                .collect::<Vec<_>>()
+                .encoded(),
+        );
+        res.append(
+            &mut postings
+                .into_iter()
+                .map(|p| p.positions.clone())
+                .collect::<Vec<_>>()
+                .encoded(),
+        );
+        res
+    }
+
+    fn decoded(reader: &mut ByteReader) -> DbResult<Self> {
+        let lengths = reader.read::<Vec<u32>>()?;
+        let words = reader.read::<Vec<String>>()?;
+        let rows = reader.read::<Vec<Vec<u32>>>()?;
+        let positions = reader.read::<Vec<Vec<Vec<u32>>>>()?;
+
+        let consistent = words.len() == rows.len()
+            && rows.len() == positions.len()
+            && rows.iter().zip(&positions).all(|(rows, positions)| {
+                rows.len() == positions.len()
+                    && rows.iter().all(|&row| (row as usize) < lengths.len())
+            });
+        if !consistent {
+            return Err(reader.error("FieldIndex", "the postings don't match the rows"));
+        }
+
+        Ok(Self {
+            lengths,
+            words: words
+                .into_iter()
+                .zip(rows.into_iter().zip(positions))
+                .map(|(word, (rows, positions))| (word, Postings { rows, positions }))
+                .collect(),
+        })
+    }
+}
+
+/// The inverted index of all full-text fields of a table.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FullTextIndex {
+    /// The fingerprint of the table data the index was built from.
+    built_from: Fingerprint,
+    fields: Vec<(String, FieldIndex)>,
+}
+
+impl FullTextIndex {
+    /// An index of `fields` that hasn't been built yet.
+    pub(crate) fn empty(fields: &[impl AsRef<str>]) -> Self {
+        Self {
+            built_from: [0; 12],
+            fields: fields
+                .iter()
+                .map(|f| {
+                    (
+                        f.as_ref().to_string(),
+                        FieldIndex::build(std::iter::empty()),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Indexes `fields` of the encoded table `data`, which is stored with `schema`.
+    pub(crate) fn build(
+        fields: &[impl AsRef<str>],
+        schema: &TableSchema,
+        data: &[u8],
+    ) -> DbResult<Self> {
+        let table = DynEntityMeta::decoded(schema, ByteReader::new(data).reader_for_block()?)?;
+        Ok(Self {
+            built_from: fingerprint(data),
+            fields: fields
+                .iter()
+                .map(|field| {
+                    let texts = table
+                        .entities
+                        .iter()
+                        .map(|row| match row.field(field.as_ref()) {
+                            Some(Value::String(text)) => text.as_str(),
+                            _ => "",
+                        });
+                    (field.as_ref().to_string(), FieldIndex::build(texts))
+                })
+                .collect(),
+        })
+    }
+
+    pub(crate) fn fields(&self) -> Vec<&str> {
+        self.fields.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Whether the index was built from the table `data` and covers exactly `fields`.
+    pub(crate) fn is_current(&self, data: &[u8], fields: &[&str]) -> bool {
+        self.built_from == fingerprint(data) && self.fields() == fields
+    }
+
+    /// The rows matching `query` in `field` and their scores, best first.
+    pub(crate) fn search(&self, field: &str, query: &SearchQuery) -> DbResult<Vec<(u32, f64)>> {
+        match self.fields.iter().find(|(name, _)| name == field) {
+            Some((_, index)) => index.search(query),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub(crate) fn encoded(&self) -> Vec<u8> {
+        let mut res = INDEX_VERSION.encoded();
+        res.append(&mut self.built_from.to_vec().encoded());
+        res.append(
+            &mut self
+                .fields()
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+                .encoded(),
+        );
+        for (_, index) in &self.fields {
+            res.append(&mut index.encoded());
+        }
+        res
+    }
+
+    /// Decodes an index, `None` if it was encoded by another version.
+    pub(crate) fn decoded(data: &[u8]) -> DbResult<Option<Self>> {
+        let mut reader = ByteReader::new(data);
+        if reader.read::<u32>()? != INDEX_VERSION {
+            return Ok(None);
+        }
+        let built_from = reader
+            .read::<Vec<u8>>()?
+            .try_into()
+            .map_err(|_| reader.error("FullTextIndex", "invalid fingerprint"))?;
+        let fields = reader
+            .read::<Vec<String>>()?
+            .into_iter()
+            .map(|name| Ok((name, FieldIndex::decoded(&mut reader)?)))
+            .collect::<DbResult<_>>()?;
+        Ok(Some(Self { built_from, fields }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FieldIndex, Pattern, SearchQuery, tokenize};
+    use crate::byte_reader::ByteReader;
+
+    fn word(w: &str) -> Pattern {
+        Pattern::Word(w.to_string())
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            tokenize("Hello, World! it's 2024"),
+            ["hello", "world", "it", "s", "2024"]
+        );
+        assert_eq!(tokenize("ÄPFEL und Birnen"), ["äpfel", "und", "birnen"]);
+        assert!(tokenize(" -- ").is_empty());
+    }
+
+    #[test]
+    fn queries() {
+        let query = SearchQuery::parse(r#"Rust "fast  Database" data* e-mail"#).unwrap();
+        assert_eq!(
+            query.terms,
+            [
+                vec![word("rust")],
+                vec![word("fast"), word("database")],
+                vec![Pattern::Prefix("data".to_string())],
+                vec![word("e"), word("mail")],
+            ]
+        );
+
+        assert!(SearchQuery::parse("  ").unwrap().terms.is_empty());
+        assert!(SearchQuery::parse(r#"a "b"#).is_err());
+    }
+
+    #[test]
+    fn ranking() {
+        let index = FieldIndex::build(
+            [
+                "the quick brown fox",
+                "a fox, a fox, a fox",
+                "brown bears and brown foxes",
+                "nothing to see here",
+            ]
+            .into_iter(),
+        );
+        let rows = |query: &str| -> Vec<u32> {
+            index
+                .search(&SearchQuery::parse(query).unwrap())
+                .unwrap()
+                .into_iter()
+                .map(|(row, _)| row)
+                .collect()
+        };
+
+        // repeated words rank higher
+        assert_eq!(rows("fox"), [1, 0]);
+        assert_eq!(rows("fox*"), [1, 0, 2]);
+        assert_eq!(rows("brown fox"), [0]);
+        assert_eq!(rows(r#""brown fox""#), [0]);
+        assert_eq!(rows(r#""fox brown""#), [] as [u32; 0]);
+        assert_eq!(rows(r#""brown fox*""#), [0, 2]);
+        assert_eq!(rows("elephant"), [] as [u32; 0]);
+    }
+
+    #[test]
+    fn damaged_indexes() {
+        let mut index = FieldIndex::build(["a fox", "no", "fox"].into_iter());
+        index.lengths.truncate(1);
+        let query = SearchQuery::parse("fox").unwrap();
+        assert!(index.search(&query).is_err());
+
+        let encoded = index.encoded();
+        assert!(FieldIndex::decoded(&mut ByteReader::new(&encoded)).is_err());
+    }
+}
@@ -1,11 +1,19 @@
 //! Polars inspired querying
 
 use std::{
+    any::type_name,
+    cell::{Cell, OnceCell},
+    collections::HashSet,
+    fmt::Display,
+    hash::Hash,
     marker::PhantomData,
-    ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Rem, Shl, Shr, Sub},
+    ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Not, Rem, Shl, Shr, Sub},
 };
 
-use crate::{db::Database, entity::Entity};
+use crate::{
+    db::{Database, DbError},
+    entity::Entity,
+};
 
 macro_rules! int_func_impl {
     ($name:ident, $op:ident, $opop:ident) => {
@@ -23,10 +31,56 @@ macro_rules! int_func_impl {
     };
 }
 
+macro_rules! ord_func_impl {
+    ($name:ident, $opop:ident) => {
+        fn $name<B>(self, rhs: B) -> BinExpr<E, $opop<E, Self::Output, Self, B>, Self, B>
+        where
+            Self::Output: PartialOrd,
+            B: GenExpr<E, Output = Self::Output>,
+        {
+            BinExpr {
+                a: self,
+                b: rhs,
+                _int: PhantomData,
+            }
+        }
+    };
+}
+
+macro_rules! str_func_impl {
+    ($(#[$doc:meta])* $name:ident, $opop:ident) => {
+        $(#[$doc])*
+        fn $name<B>(self, rhs: B) -> BinExpr<E, $opop<E, Self, B>, Self, B>
+        where
+            Self::Output: AsRef<str>,
+            B: GenExpr<E>,
+            B::Output: AsRef<str>,
+        {
+            BinExpr {
+                a: self,
+                b: rhs,
+                _int: PhantomData,
+            }
+        }
+    };
+}
+
+// `len` is the length of a string expression, an `is_empty` would not add anything
+#[allow(clippy::len_without_is_empty)]
 pub trait GenExpr<E: Entity>: Sized {
     type Output;
 
-    fn exec(&self, db: &Database, row_id: E::Id) -> Self::Output;
+    fn exec(&self, db: &Database, row: &E) -> Self::Output;
+
+    /// Describes the expression for [explaining](crate::query::DbIterator::explain) queries.
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Opaque(std::any::type_name::<Self>())
+    }
+
+    /// The error of a [subquery] that couldn't be run, it is treated as empty then.
+    fn take_error(&self) -> Option<DbError> {
+        None
+    }
 
     fn eq<B: GenExpr<E>>(self, rhs: B) -> BinExpr<E, EqOp<E, Self, B>, Self, B>
     where
@@ -119,13 +173,187 @@ pub trait GenExpr<E: Entity>: Sized {
     int_func_impl!(rem, Rem, RemOp);
     int_func_impl!(shl, Shl, ShlOp);
     int_func_impl!(shr, Shr, ShrOp);
+
+    ord_func_impl!(lt, LtOp);
+    ord_func_impl!(gt, GtOp);
+    ord_func_impl!(lte, LteOp);
+    ord_func_impl!(gte, GteOp);
+
+    /// Checks whether the value lies within `low..=high`.
+    fn between<L, H>(self, low: L, high: H) -> BetweenExpr<E, Self, L, H>
+    where
+        Self::Output: PartialOrd,
+        L: GenExpr<E, Output = Self::Output>,
+        H: GenExpr<E, Output = Self::Output>,
+    {
+        BetweenExpr {
+            a: self,
+            low,
+            high,
+            _int: PhantomData,
+        }
+    }
+
+    /// Checks whether the value is one of `values`.
+    fn in_list(self, values: impl IntoIterator<Item = Self::Output>) -> InListExpr<E, Self>
+    where
+        Self::Output: PartialEq,
+    {
+        InListExpr {
+            a: self,
+            values: values.into_iter().collect(),
+            _int: PhantomData,
+        }
+    }
+
+    /// Checks whether the value is one of the values selected by a [subquery].
+    ///
+    /// The subquery is run once, the first time the expression is evaluated,
+    /// and its values are kept in a hash set which every row is looked up in.
+    fn in_query<S, Q, C>(self, values: SubQuerySelect<S, Q, C>) -> InQueryExpr<E, Self, S, Q, C>
+    where
+        S: Entity,
+        Q: GenExpr<S, Output = bool>,
+        C: GenExpr<S, Output = Self::Output>,
+        Self::Output: Hash + Eq,
+    {
+        InQueryExpr {
+            a: self,
+            query: values,
+            values: OnceCell::new(),
+            error: Cell::new(None),
+            _int: PhantomData,
+        }
+    }
+
+    /// Checks whether an optional value is `None`.
+    #[allow(clippy::wrong_self_convention)]
+    fn is_null<T>(self) -> UnaryExpr<E, IsNullOp<E, T, Self>, Self>
+    where
+        Self: GenExpr<E, Output = Option<T>>,
+    {
+        UnaryExpr {
+            a: self,
+            _int: PhantomData,
+        }
+    }
+
+    /// Checks whether an optional value is `Some`.
+    #[allow(clippy::wrong_self_convention)]
+    fn is_some<T>(self) -> UnaryExpr<E, IsSomeOp<E, T, Self>, Self>
+    where
+        Self: GenExpr<E, Output = Option<T>>,
+    {
+        UnaryExpr {
+            a: self,
+            _int: PhantomData,
+        }
+    }
+
+    /// Negates a condition, or inverts the bits of an integer.
+    fn not(self) -> UnaryExpr<E, NotOp<E, Self>, Self>
+    where
+        Self::Output: Not,
+    {
+        UnaryExpr {
+            a: self,
+            _int: PhantomData,
+        }
+    }
+
+    str_func_impl!(
+        /// Checks whether the string contains `rhs`.
+        contains,
+        ContainsOp
+    );
+    str_func_impl!(
+        /// Checks whether the string starts with `rhs`.
+        starts_with,
+        StartsWithOp
+    );
+    str_func_impl!(
+        /// Checks whether the string ends with `rhs`.
+        ends_with,
+        EndsWithOp
+    );
+    str_func_impl!(
+        /// Compares two strings ignoring the case of their characters.
+        eq_ignore_case,
+        EqIgnoreCaseOp
+    );
+    str_func_impl!(
+        /// Matches the string against an sql `LIKE` pattern where `%` matches any
+        /// number of characters and `_` a single character. A `\` matches the
+        /// following character literally.
+        like,
+        LikeOp
+    );
+
+    /// The length of the string in bytes.
+    fn len(self) -> UnaryExpr<E, LenOp<E, Self>, Self>
+    where
+        Self::Output: AsRef<str>,
+    {
+        UnaryExpr {
+            a: self,
+            _int: PhantomData,
+        }
+    }
+
+    /// Checks whether the string matches the regular expression.
+    #[cfg(feature = "regex")]
+    fn matches(self, regex: regex::Regex) -> RegexExpr<E, Self>
+    where
+        Self::Output: AsRef<str>,
+    {
+        RegexExpr {
+            a: self,
+            regex,
+            _int: PhantomData,
+        }
+    }
 }
 
 pub trait BinOp<E: Entity> {
+    /// The operator or the name of the method used when the expression is described.
+    const SYMBOL: &'static str;
     type Output;
     type Lhs: GenExpr<E>;
     type Rhs: GenExpr<E>;
-    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output;
+    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output;
+}
+
+pub trait UnaryOp<E: Entity> {
+    /// The operator or the name of the method used when the expression is described.
+    const SYMBOL: &'static str;
+    type Output;
+    type Inner: GenExpr<E>;
+    fn exec(inner: &Self::Inner, db: &Database, row: &E) -> Self::Output;
+}
+
+pub struct UnaryExpr<E: Entity, O, A>
+where
+    O: UnaryOp<E, Inner = A>,
+    A: GenExpr<E>,
+{
+    a: A,
+    _int: PhantomData<(E, O)>,
+}
+
+impl<E: Entity, O: UnaryOp<E, Inner = A>, A: GenExpr<E>> GenExpr<E> for UnaryExpr<E, O, A> {
+    type Output = O::Output;
+
+    fn exec(&self, db: &Database, row: &E) -> Self::Output {
+        O::exec(&self.a, db, row)
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Unary(O::SYMBOL, Box::new(self.a.info()))
+    }
+
+    fn take_error(&self) -> Option<DbError> {
+        self.a.take_error()
+    }
 }
 
 pub struct BinExpr<E: Entity, O, A, B>
@@ -144,8 +372,16 @@ impl<E: Entity, O: BinOp<E, Lhs = A, Rhs = B>, A: GenExpr<E>, B: GenExpr<E>> Gen
 {
     type Output = O::Output;
 
-    fn exec(&self, db: &Database, row_id: E::Id) -> Self::Output {
-        O::exec(&self.a, &self.b, db, row_id)
+    fn exec(&self, db: &Database, row: &E) -> Self::Output {
+        O::exec(&self.a, &self.b, db, row)
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Binary(O::SYMBOL, Box::new(self.a.info()), Box::new(self.b.info()))
+    }
+
+    fn take_error(&self) -> Option<DbError> {
+        self.a.take_error().or_else(|| self.b.take_error())
     }
 }
 
@@ -160,11 +396,12 @@ where
     A::Output: PartialEq<B::Output>,
     B::Output: PartialEq<A::Output>,
 {
+    const SYMBOL: &'static str = "==";
     type Lhs = A;
     type Rhs = B;
     type Output = bool;
-    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output {
-        lhs.exec(db, row_id) == rhs.exec(db, row_id)
+    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+        lhs.exec(db, row) == rhs.exec(db, row)
     }
 }
 
@@ -179,11 +416,12 @@ where
     A::Output: PartialEq<B::Output>,
     B::Output: PartialEq<A::Output>,
 {
+    const SYMBOL: &'static str = "!=";
     type Lhs = A;
     type Rhs = B;
     type Output = bool;
-    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output {
-        lhs.exec(db, row_id) != rhs.exec(db, row_id)
+    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+        lhs.exec(db, row) != rhs.exec(db, row)
     }
 }
 
@@ -196,11 +434,12 @@ where
     A: GenExpr<E, Output = bool>,
     B: GenExpr<E, Output = bool>,
 {
+    const SYMBOL: &'static str = "||";
     type Lhs = A;
     type Rhs = B;
     type Output = bool;
-    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output {
-        lhs.exec(db, row_id) || rhs.exec(db, row_id)
+    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+        lhs.exec(db, row) || rhs.exec(db, row)
     }
 }
 
@@ -213,11 +452,12 @@ where
     A: GenExpr<E, Output = bool>,
     B: GenExpr<E, Output = bool>,
 {
+    const SYMBOL: &'static str = "&&";
     type Lhs = A;
     type Rhs = B;
     type Output = bool;
-    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output {
-        lhs.exec(db, row_id) && rhs.exec(db, row_id)
+    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+        lhs.exec(db, row) && rhs.exec(db, row)
     }
 }
 
@@ -232,12 +472,13 @@ where
     A::Output: BitOr<B::Output, Output = A::Output>,
     B::Output: BitOr<A::Output, Output = A::Output>,
 {
+    const SYMBOL: &'static str = "|";
     type Lhs = A;
     type Rhs = B;
     type Output = A::Output;
 
-    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output {
-        lhs.exec(db, row_id) | rhs.exec(db, row_id)
+    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+        lhs.exec(db, row) | rhs.exec(db, row)
     }
 }
 
@@ -252,12 +493,13 @@ where
     A::Output: BitAnd<B::Output, Output = A::Output>,
     B::Output: BitAnd<A::Output, Output = A::Output>,
 {
+    const SYMBOL: &'static str = "&";
     type Lhs = A;
     type Rhs = B;
     type Output = A::Output;
 
-    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output {
-        lhs.exec(db, row_id) & rhs.exec(db, row_id)
+    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+        lhs.exec(db, row) & rhs.exec(db, row)
     }
 }
 
@@ -272,12 +514,13 @@ where
     A::Output: BitXor<B::Output, Output = A::Output>,
     B::Output: BitXor<A::Output, Output = A::Output>,
 {
+    const SYMBOL: &'static str = "^";
     type Lhs = A;
     type Rhs = B;
     type Output = A::Output;
 
-    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output {
-        lhs.exec(db, row_id) ^ rhs.exec(db, row_id)
+    fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+        lhs.exec(db, row) ^ rhs.exec(db, row)
     }
 }
 
@@ -294,11 +537,12 @@ macro_rules! int_op_impl {
             A: GenExpr<E, Output = T>,
             B: GenExpr<E, Output = T>,
         {
+            const SYMBOL: &'static str = stringify!($calc);
             type Lhs = A;
             type Rhs = B;
             type Output = T;
-            fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output {
-                lhs.exec(db, row_id) $calc rhs.exec(db, row_id)
+            fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+                lhs.exec(db, row) $calc rhs.exec(db, row)
             }
         }
     };
@@ -325,11 +569,12 @@ macro_rules! ord_op_impl {
             A: GenExpr<E, Output = T>,
             B: GenExpr<E, Output = T>,
         {
+            const SYMBOL: &'static str = stringify!($calc);
             type Lhs = A;
             type Rhs = B;
             type Output = bool;
-            fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row_id: E::Id) -> Self::Output {
-                lhs.exec(db, row_id) $calc rhs.exec(db, row_id)
+            fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+                lhs.exec(db, row) $calc rhs.exec(db, row)
             }
         }
     };
@@ -340,6 +585,250 @@ ord_op_impl!(GtOp, >);
 ord_op_impl!(LteOp, <=);
 ord_op_impl!(GteOp, >=);
 
+pub struct BetweenExpr<E: Entity, A, L, H> {
+    a: A,
+    low: L,
+    high: H,
+    _int: PhantomData<E>,
+}
+
+impl<E: Entity, A, L, H> GenExpr<E> for BetweenExpr<E, A, L, H>
+where
+    A: GenExpr<E>,
+    A::Output: PartialOrd,
+    L: GenExpr<E, Output = A::Output>,
+    H: GenExpr<E, Output = A::Output>,
+{
+    type Output = bool;
+
+    fn exec(&self, db: &Database, row: &E) -> Self::Output {
+        let value = self.a.exec(db, row);
+        self.low.exec(db, row) <= value && value <= self.high.exec(db, row)
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Method(
+            "between",
+            vec![self.a.info(), self.low.info(), self.high.info()],
+        )
+    }
+
+    fn take_error(&self) -> Option<DbError> {
+        self.a
+            .take_error()
+            .or_else(|| self.low.take_error())
+            .or_else(|| self.high.take_error())
+    }
+}
+
+pub struct InListExpr<E: Entity, A: GenExpr<E>> {
+    a: A,
+    values: Vec<A::Output>,
+    _int: PhantomData<E>,
+}
+
+impl<E: Entity, A: GenExpr<E>> GenExpr<E> for InListExpr<E, A>
+where
+    A::Output: PartialEq,
+{
+    type Output = bool;
+
+    fn exec(&self, db: &Database, row: &E) -> Self::Output {
+        // `GenExpr::contains` would shadow the method of `Vec`
+        self.values[..].contains(&self.a.exec(db, row))
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Method(
+            "in_list",
+            vec![
+                self.a.info(),
+                ExprInfo::Const(format!(
+                    "[{}; {}]",
+                    const_name::<A::Output>(),
+                    self.values.len()
+                )),
+            ],
+        )
+    }
+
+    fn take_error(&self) -> Option<DbError> {
+        self.a.take_error()
+    }
+}
+
+pub struct IsNullOp<E, T, A> {
+    _int: PhantomData<(E, T, A)>,
+}
+
+impl<E: Entity, T, A: GenExpr<E, Output = Option<T>>> UnaryOp<E> for IsNullOp<E, T, A> {
+    const SYMBOL: &'static str = "is_null";
+    type Output = bool;
+    type Inner = A;
+    fn exec(inner: &Self::Inner, db: &Database, row: &E) -> Self::Output {
+        inner.exec(db, row).is_none()
+    }
+}
+
+pub struct IsSomeOp<E, T, A> {
+    _int: PhantomData<(E, T, A)>,
+}
+
+impl<E: Entity, T, A: GenExpr<E, Output = Option<T>>> UnaryOp<E> for IsSomeOp<E, T, A> {
+    const SYMBOL: &'static str = "is_some";
+    type Output = bool;
+    type Inner = A;
+    fn exec(inner: &Self::Inner, db: &Database, row: &E) -> Self::Output {
+        inner.exec(db, row).is_some()
+    }
+}
+
+pub struct NotOp<E, A> {
+    _int: PhantomData<(E, A)>,
+}
+
+impl<E: Entity, A: GenExpr<E>> UnaryOp<E> for NotOp<E, A>
+where
+    A::Output: Not,
+{
+    const SYMBOL: &'static str = "!";
+    type Output = <A::Output as Not>::Output;
+    type Inner = A;
+    fn exec(inner: &Self::Inner, db: &Database, row: &E) -> Self::Output {
+        !inner.exec(db, row)
+    }
+}
+
+macro_rules! str_op_impl {
+    ($name:ident, $symbol:literal, |$a:ident, $b:ident| $calc:expr) => {
+        pub struct $name<E, A, B> {
+            _int: PhantomData<(E, A, B)>,
+        }
+
+        impl<E, A, B> BinOp<E> for $name<E, A, B>
+        where
+            E: Entity,
+            A: GenExpr<E>,
+            B: GenExpr<E>,
+            A::Output: AsRef<str>,
+            B::Output: AsRef<str>,
+        {
+            const SYMBOL: &'static str = $symbol;
+            type Lhs = A;
+            type Rhs = B;
+            type Output = bool;
+            fn exec(lhs: &Self::Lhs, rhs: &Self::Rhs, db: &Database, row: &E) -> Self::Output {
+                let ($a, $b) = (lhs.exec(db, row), rhs.exec(db, row));
+                let ($a, $b) = ($a.as_ref(), $b.as_ref());
+                $calc
+            }
+        }
+    };
+}
+
+str_op_impl!(ContainsOp, "contains", |a, b| a.contains(b));
+str_op_impl!(StartsWithOp, "starts_with", |a, b| a.starts_with(b));
+str_op_impl!(EndsWithOp, "ends_with", |a, b| a.ends_with(b));
+str_op_impl!(EqIgnoreCaseOp, "eq_ignore_case", |a, b| a.to_lowercase()
+    == b.to_lowercase());
+str_op_impl!(LikeOp, "like", |a, b| like(a, b));
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LikeToken {
+    /// `%`
+    Any,
+    /// `_`
+    One,
+    Char(char),
+}
+
+/// Matches `text` against an sql `LIKE` pattern.
+pub(crate) fn like(text: &str, pattern: &str) -> bool {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => LikeToken::Any,
+            '_' => LikeToken::One,
+            '\\' => LikeToken::Char(chars.next().unwrap_or('\\')),
+            c => LikeToken::Char(c),
+        });
+    }
+    let text: Vec<_> = text.chars().collect();
+
+    // the position after the last `%` and the text position it was tried at
+    let mut backtrack = None;
+    let (mut t, mut p) = (0, 0);
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(LikeToken::Any) => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(LikeToken::One) => (t, p) = (t + 1, p + 1),
+            Some(LikeToken::Char(c)) if *c == text[t] => (t, p) = (t + 1, p + 1),
+            _ => match backtrack {
+                // let the last `%` match one more character
+                Some((after_any, tried)) => {
+                    (t, p) = (tried + 1, after_any);
+                    backtrack = Some((after_any, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[p..].iter().all(|t| *t == LikeToken::Any)
+}
+
+pub struct LenOp<E, A> {
+    _int: PhantomData<(E, A)>,
+}
+
+impl<E: Entity, A: GenExpr<E>> UnaryOp<E> for LenOp<E, A>
+where
+    A::Output: AsRef<str>,
+{
+    const SYMBOL: &'static str = "len";
+    type Output = usize;
+    type Inner = A;
+    fn exec(inner: &Self::Inner, db: &Database, row: &E) -> Self::Output {
+        inner.exec(db, row).as_ref().len()
+    }
+}
+
+#[cfg(feature = "regex")]
+pub struct RegexExpr<E: Entity, A: GenExpr<E>> {
+    a: A,
+    regex: regex::Regex,
+    _int: PhantomData<E>,
+}
+
+#[cfg(feature = "regex")]
+impl<E: Entity, A: GenExpr<E>> GenExpr<E> for RegexExpr<E, A>
+where
+    A::Output: AsRef<str>,
+{
+    type Output = bool;
+
+    fn exec(&self, db: &Database, row: &E) -> Self::Output {
+        self.regex.is_match(self.a.exec(db, row).as_ref())
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Method(
+            "matches",
+            vec![
+                self.a.info(),
+                ExprInfo::Const(format!("{:?}", self.regex.as_str())),
+            ],
+        )
+    }
+
+    fn take_error(&self) -> Option<DbError> {
+        self.a.take_error()
+    }
+}
+
 pub struct EqExpr<E: Entity, A, B>
 where
     A: GenExpr<E>,
@@ -359,13 +848,22 @@ where
 {
     type Output = bool;
 
-    fn exec(&self, db: &Database, row_id: E::Id) -> Self::Output {
-        self.a.exec(db, row_id) == self.b.exec(db, row_id)
+    fn exec(&self, db: &Database, row: &E) -> Self::Output {
+        self.a.exec(db, row) == self.b.exec(db, row)
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Binary("==", Box::new(self.a.info()), Box::new(self.b.info()))
+    }
+
+    fn take_error(&self) -> Option<DbError> {
+        self.a.take_error().or_else(|| self.b.take_error())
     }
 }
 
 pub trait ResolveAttrExpr<T>: Entity {
-    fn resolve(field_name: &'static str, row_id: Self::Id, db: &Database) -> T;
+    /// Gets the value of a field of the row.
+    fn resolve(field_name: &'static str, row: &Self) -> T;
 }
 
 pub struct AttrExpr<E: Entity, T> {
@@ -384,18 +882,381 @@ impl<E: Entity, T> AttrExpr<E, T> {
 
 impl<E: ResolveAttrExpr<T>, T> GenExpr<E> for AttrExpr<E, T> {
     type Output = T;
-    fn exec(&self, db: &Database, row_id: E::Id) -> Self::Output {
-        E::resolve(self.field_name, row_id, db)
+    fn exec(&self, _db: &Database, row: &E) -> Self::Output {
+        E::resolve(self.field_name, row)
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Field(self.field_name.to_string())
     }
 }
 
 impl<E: Entity, T: Copy> GenExpr<E> for T {
     type Output = T;
-    fn exec(&self, _db: &Database, _row_id: E::Id) -> Self::Output {
+    fn exec(&self, _db: &Database, _row: &E) -> Self::Output {
         *self
     }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Const(const_name::<T>())
+    }
+}
+
+/// A constant that isn't `Copy` like an owned `String`.
+///
+/// `Copy` constants like numbers and `&'static str` can be used directly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lit<T>(pub T);
+
+/// Creates a constant expression from any cloneable value.
+pub fn lit<T: Clone>(value: T) -> Lit<T> {
+    Lit(value)
+}
+
+impl<E: Entity, T: Clone> GenExpr<E> for Lit<T> {
+    type Output = T;
+    fn exec(&self, _db: &Database, _row: &E) -> Self::Output {
+        self.0.clone()
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Const(const_name::<T>())
+    }
+}
+
+/// Describes a constant of type `T` like `<Option<String>>`, without the
+/// module paths of the types.
+fn const_name<T>() -> String {
+    let name: String = type_name::<T>()
+        .split_inclusive(|c: char| "<>,;()[]& ".contains(c))
+        .map(|part| part.rsplit("::").next().unwrap_or(part))
+        .collect();
+    format!("<{name}>")
+}
+
+/// Starts a query on the table of `S` that can be used inside the
+/// expressions of another query, see [GenExpr::in_query] and [exists].
+///
+/// ```ignore
+/// db.query_mut::<Order>()?
+///     .filter(|o| {
+///         o.customer_id()
+///             .in_query(subquery::<Customer>().filter(|c| c.tier().gte(2)).ids())
+///     })
+/// ```
+///
+/// Subqueries can't refer to the row of the outer query. A correlated
+/// `EXISTS` on equal columns is written as `in_query` of the inner column,
+/// which is run as a hash semi-join instead of once per row.
+pub fn subquery<S: Entity>() -> SubQuery<S, All> {
+    SubQuery {
+        filter: All,
+        _int: PhantomData,
+    }
+}
+
+/// The filter of a [subquery] without conditions.
+pub struct All;
+
+impl<E: Entity> GenExpr<E> for All {
+    type Output = bool;
+
+    fn exec(&self, _db: &Database, _row: &E) -> Self::Output {
+        true
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Const("true".to_string())
+    }
+}
+
+/// The rows of `S` matching a filter, see [subquery].
+pub struct SubQuery<S: Entity, Q> {
+    filter: Q,
+    _int: PhantomData<S>,
+}
+
+impl<S: Entity> SubQuery<S, All> {
+    pub fn filter<Q, P>(self, predicate: P) -> SubQuery<S, Q>
+    where
+        Q: GenExpr<S, Output = bool>,
+        P: Fn(&S::ExprBase) -> Q,
+    {
+        SubQuery {
+            filter: predicate(&<S::ExprBase as ExprEntity<S>>::new()),
+            _int: PhantomData,
+        }
+    }
+}
+
+impl<S: Entity, Q: GenExpr<S, Output = bool>> SubQuery<S, Q> {
+    /// Selects one column of the matching rows.
+    pub fn select<C, P>(self, column: P) -> SubQuerySelect<S, Q, C>
+    where
+        C: GenExpr<S>,
+        P: Fn(&S::ExprBase) -> C,
+    {
+        SubQuerySelect {
+            column: column(&<S::ExprBase as ExprEntity<S>>::new()),
+            query: self,
+        }
+    }
+
+    /// Selects the ids of the matching rows.
+    pub fn ids(self) -> SubQuerySelect<S, Q, IdExpr<S>> {
+        SubQuerySelect {
+            column: IdExpr(PhantomData),
+            query: self,
+        }
+    }
+
+    /// Calls `visit` with every matching row until it returns `false`.
+    ///
+    /// A table that was never written has no rows.
+    fn run(&self, db: &Database, mut visit: impl FnMut(&S) -> bool) -> Result<(), DbError> {
+        let mut rows = match db.query::<S>() {
+            Ok(rows) => rows,
+            Err(DbError::TypeNotFound { .. }) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        while let Some(row) = rows.try_next()? {
+            if self.filter.exec(db, &row) && !visit(&row) {
+                break;
+            }
+        }
+        self.filter.take_error().map_or(Ok(()), Err)
+    }
+
+    fn info(&self, column: ExprInfo) -> ExprInfo {
+        ExprInfo::Query {
+            table: S::table_schema().name,
+            column: Box::new(column),
+            filter: Box::new(self.filter.info()),
+        }
+    }
+}
+
+/// One column of the rows of a [subquery].
+pub struct SubQuerySelect<S: Entity, Q, C> {
+    query: SubQuery<S, Q>,
+    column: C,
+}
+
+/// The id of a row, see [SubQuery::ids].
+pub struct IdExpr<E>(PhantomData<E>);
+
+impl<E: Entity> GenExpr<E> for IdExpr<E> {
+    type Output = E::Id;
+
+    fn exec(&self, _db: &Database, row: &E) -> Self::Output {
+        row.get_id()
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Field(E::ID_FIELD.to_string())
+    }
+}
+
+pub struct InQueryExpr<E: Entity, A: GenExpr<E>, S: Entity, Q, C> {
+    a: A,
+    query: SubQuerySelect<S, Q, C>,
+    values: OnceCell<HashSet<A::Output>>,
+    error: Cell<Option<DbError>>,
+    _int: PhantomData<E>,
+}
+
+impl<E: Entity, A: GenExpr<E>, S: Entity, Q, C> GenExpr<E> for InQueryExpr<E, A, S, Q, C>
+where
+    Q: GenExpr<S, Output = bool>,
+    C: GenExpr<S, Output = A::Output>,
+    A::Output: Hash + Eq,
+{
+    type Output = bool;
+
+    fn exec(&self, db: &Database, row: &E) -> Self::Output {
+        let values = self.values.get_or_init(|| {
+            let SubQuerySelect { query, column } = &self.query;
+            let mut values = HashSet::new();
+            let res = query.run(db, |row| {
+                values.insert(column.exec(db, row));
+                true
+            });
+            self.error.set(res.err().or_else(|| column.take_error()));
+            values
+        });
+        values.contains(&self.a.exec(db, row))
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Binary(
+            "in_query",
+            Box::new(self.a.info()),
+            Box::new(self.query.query.info(self.query.column.info())),
+        )
+    }
+
+    fn take_error(&self) -> Option<DbError> {
+        self.error.take().or_else(|| self.a.take_error())
+    }
+}
+
+/// Checks whether a [subquery] has any rows.
+///
+/// The subquery is run once, the first time the expression is evaluated,
+/// and stops at the first matching row.
+pub fn exists<E: Entity, S: Entity, Q: GenExpr<S, Output = bool>>(
+    query: SubQuery<S, Q>,
+) -> ExistsExpr<E, S, Q> {
+    ExistsExpr {
+        query,
+        found: OnceCell::new(),
+        error: Cell::new(None),
+        _int: PhantomData,
+    }
+}
+
+pub struct ExistsExpr<E: Entity, S: Entity, Q> {
+    query: SubQuery<S, Q>,
+    found: OnceCell<bool>,
+    error: Cell<Option<DbError>>,
+    _int: PhantomData<E>,
+}
+
+impl<E: Entity, S: Entity, Q: GenExpr<S, Output = bool>> GenExpr<E> for ExistsExpr<E, S, Q> {
+    type Output = bool;
+
+    fn exec(&self, db: &Database, _row: &E) -> Self::Output {
+        *self.found.get_or_init(|| {
+            let mut found = false;
+            let res = self.query.run(db, |_| {
+                found = true;
+                false
+            });
+            self.error.set(res.err());
+            found
+        })
+    }
+
+    fn info(&self) -> ExprInfo {
+        ExprInfo::Unary(
+            "exists",
+            Box::new(self.query.info(ExprInfo::Const("*".to_string()))),
+        )
+    }
+
+    fn take_error(&self) -> Option<DbError> {
+        self.error.take()
+    }
+}
+
+/// A description of an expression, see [GenExpr::info].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprInfo {
+    Field(String),
+    /// A constant. Constants of a [GenExpr] are described by their type like
+    /// `<u32>` since they don't have to implement `Debug`.
+    Const(String),
+    /// An operator like `!` or a method without arguments like `len`.
+    Unary(&'static str, Box<ExprInfo>),
+    /// An operator like `>=` or a method with one argument like `contains`.
+    Binary(&'static str, Box<ExprInfo>, Box<ExprInfo>),
+    /// A method with more arguments, the first one is the receiver.
+    Method(&'static str, Vec<ExprInfo>),
+    /// A [subquery] selecting `column` from the rows of `table` matching `filter`.
+    Query {
+        table: String,
+        column: Box<ExprInfo>,
+        filter: Box<ExprInfo>,
+    },
+    /// An expression that can't describe itself, holding its type name.
+    Opaque(&'static str),
+}
+
+impl ExprInfo {
+    fn is_method(name: &str) -> bool {
+        name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    /// Writes the expression in parentheses if it is an operator.
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unary(op, _) | Self::Binary(op, ..) if !Self::is_method(op) => {
+                write!(f, "({self})")
+            }
+            Self::Query { .. } => write!(f, "({self})"),
+            _ => write!(f, "{self}"),
+        }
+    }
+}
+
+impl Display for ExprInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Field(name) => write!(f, "{name}"),
+            Self::Const(value) => write!(f, "{value}"),
+            Self::Unary(op, inner) if Self::is_method(op) => {
+                inner.fmt_operand(f)?;
+                write!(f, ".{op}()")
+            }
+            Self::Unary(op, inner) => {
+                write!(f, "{op}")?;
+                inner.fmt_operand(f)
+            }
+            Self::Binary(op, lhs, rhs) if Self::is_method(op) => {
+                lhs.fmt_operand(f)?;
+                write!(f, ".{op}({rhs})")
+            }
+            Self::Binary(op, lhs, rhs) => {
+                lhs.fmt_operand(f)?;
+                write!(f, " {op} ")?;
+                rhs.fmt_operand(f)
+            }
+            Self::Method(name, args) => {
+                let Some((receiver, args)) = args.split_first() else {
+                    return write!(f, "{name}()");
+                };
+                receiver.fmt_operand(f)?;
+                write!(f, ".{name}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Query {
+                table,
+                column,
+                filter,
+            } => write!(f, "select {column} from {table} where {filter}"),
+            Self::Opaque(type_name) => write!(f, "<{type_name}>"),
+        }
+    }
 }
 
 pub trait ExprEntity<E: Entity> {
     fn new() -> Self;
 }
+
+#[cfg(test)]
+mod test {
+    use super::like;
+
+    #[test]
+    fn like_patterns() {
+        assert!(like("hello", "hello"));
+        assert!(like("hello", "h%"));
+        assert!(like("hello", "%llo"));
+        assert!(like("hello", "h_l%o"));
+        assert!(like("hello", "%"));
+        assert!(like("", "%"));
+        assert!(like("héllo", "h_llo"));
+        assert!(like("abcabcabd", "%abd"));
+        assert!(like("100%", "100\\%"));
+        assert!(!like("100", "100\\%"));
+        assert!(!like("hello", "h_"));
+        assert!(!like("hello", "Hello"));
+        assert!(!like("", "_"));
+    }
+}
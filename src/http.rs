@@ -0,0 +1,572 @@
+//! An HTTP/JSON endpoint for the tables of registered entity types.
+//!
+//! | request                         | response                                   |
+//! |---------------------------------|--------------------------------------------|
+//! | `GET /tables/{name}`            | all entities of the table as a json array  |
+//! | `GET /tables/{name}/{id}`       | the entity with the id                     |
+//! | `POST /tables/{name}`           | stores the entity in the body              |
+//! | `PUT /tables/{name}/{id}`       | replaces the entity with the id            |
+//! | `DELETE /tables/{name}/{id}`    | deletes the entity with the id             |
+//!
+//! `{name}` is the name of the entity struct. Entities are encoded as json
+//! objects like [Format::Json](crate::format::Format::Json) does. Generated ids
+//! can be left out when storing an entity and the id of a replaced entity is
+//! taken from the path.
+//!
+//! The entities listed by `GET /tables/{name}` can be filtered with query
+//! parameters which are translated into a [GenExpr] that all entities have to
+//! match: `field=value` keeps the entities where the field has the value and
+//! `field.ne`, `field.lt`, `field.lte`, `field.gt` and `field.gte` compare
+//! the field to the value instead. An empty value is `None` for optional
+//! fields, so `field=` keeps the entities where the field is `None` and
+//! `field.ne=` the ones where it is `Some`.
+//!
+//! Errors are returned as `{"error": "..."}` with a matching status code.
+//! Bodies larger than [MAX_BODY_LEN] are refused with `413`, request and
+//! header lines longer than [MAX_LINE_LEN] or more than [MAX_HEADERS] headers
+//! with `400` and `431`. Connections that stay idle for longer than [TIMEOUT]
+//! are closed and at most [MAX_CONNECTIONS] are handled at the same time,
+//! further ones wait until one of them is closed.
+//!
+//! ```rust,no_run
+//! use std::net::TcpListener;
+//!
+//! use somedb::{db::Database, entity, http::RestApi};
+//!
+//! #[entity]
+//! struct Sensor {
+//!     #[entity_id(auto_generate)]
+//!     id: u32,
+//!     room: String,
+//! }
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let mut api = RestApi::new(Database::default()?);
+//!     api.register::<Sensor>();
+//!     // GET http://127.0.0.1:8080/tables/Sensor?room=kitchen
+//!     api.serve(TcpListener::bind("127.0.0.1:8080")?)?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    marker::PhantomData,
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    db::{Database, DbError, DbResult, ErrorKind},
+    dyn_query::compare,
+    entity::Entity,
+    format::{from_json, from_text, to_json},
+    gen_query::{ExprInfo, GenExpr},
+    id::IdType,
+    json::Json,
+    query::DbIterator,
+    storable::Storable,
+    value::Value,
+};
+
+/// Serves the tables of the registered entity types over HTTP.
+pub struct RestApi {
+    db: Mutex<Database>,
+    tables: HashMap<String, Box<dyn RestTable>>,
+}
+
+impl RestApi {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db: Mutex::new(db),
+            tables: HashMap::new(),
+        }
+    }
+
+    /// Makes the table of `T` available at `/tables/{struct name}`.
+    pub fn register<T: Entity + 'static>(&mut self) -> &mut Self {
+        self.tables.insert(
+            T::table_schema().name,
+            Box::new(TypedTable::<T>(PhantomData)),
+        );
+        self
+    }
+
+    /// Handles the requests of all connections to the listener,
+    /// each connection on its own thread.
+    ///
+    /// This only returns if accepting connections fails.
+    pub fn serve(self, listener: TcpListener) -> io::Result<()> {
+        let api = Arc::new(self);
+        let connections = Arc::new((Mutex::new(0), Condvar::new()));
+        for stream in listener.incoming() {
+            let stream = stream?;
+            // the client notices when its connection fails
+            let _ = stream.set_read_timeout(Some(TIMEOUT));
+            let _ = stream.set_write_timeout(Some(TIMEOUT));
+
+            let (count, freed) = &*connections;
+            let mut count = count.lock().unwrap_or_else(|e| e.into_inner());
+            while *count >= MAX_CONNECTIONS {
+                count = freed.wait(count).unwrap_or_else(|e| e.into_inner());
+            }
+            *count += 1;
+
+            let api = api.clone();
+            let connections = connections.clone();
+            thread::spawn(move || {
+                let _ = api.handle_connection(stream);
+                let (count, freed) = &*connections;
+                *count.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+                freed.notify_one();
+            });
+        }
+        Ok(())
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(&stream);
+        let response = match read_request(&mut reader)? {
+            Ok(request) => self.handle(&request),
+            Err(response) => response,
+        };
+        response.write(&stream)?;
+
+        // closing a socket with unread data resets the connection, which
+        // can drop the response before the client has read it
+        stream.shutdown(Shutdown::Write)?;
+        io::copy(&mut reader.take(MAX_LINE_LEN as u64 * 8), &mut io::sink())?;
+        Ok(())
+    }
+
+    fn handle(&self, request: &Request) -> Response {
+        let (path, query) = request
+            .target
+            .split_once('?')
+            .unwrap_or((&request.target, ""));
+        let segments: Vec<_> = path
+            .strip_prefix("/tables/")
+            .map(|rest| rest.split('/').map(percent_decode).collect())
+            .unwrap_or_default();
+
+        let (name, id) = match segments.as_slice() {
+            [name] => (name, None),
+            [name, id] => (name, Some(id.as_str())),
+            _ => return Response::error(404, "not found"),
+        };
+        let Some(table) = self.tables.get(name) else {
+            return Response::error(404, &format!("no table named {name}"));
+        };
+
+        let mut db = self.db.lock().unwrap_or_else(|e| e.into_inner());
+        let body = || Json::parse(&request.body).map_err(DbError::ParseError);
+        let res = match (request.method.as_str(), id) {
+            ("GET", None) => table
+                .list(&mut db, &parse_query(query))
+                .map(|json| (200, json)),
+            ("GET", Some(id)) => table.get(&db, id).map(|json| match json {
+                Some(json) => (200, json),
+                None => not_found(id),
+            }),
+            ("POST", None) => body()
+                .and_then(|body| table.create(&mut db, body))
+                .map(|json| (201, json)),
+            ("PUT", Some(id)) => body()
+                .and_then(|body| table.replace(&mut db, id, body))
+                .map(|json| (200, json)),
+            ("DELETE", Some(id)) => table.delete(&mut db, id).map(|deleted| match deleted {
+                true => (204, Json::Null),
+                false => not_found(id),
+            }),
+            _ => return Response::error(405, "method not allowed"),
+        };
+
+        match res {
+            Ok((status, Json::Null)) => Response {
+                status,
+                body: String::new(),
+            },
+            Ok((status, json)) => Response {
+                status,
+                body: json.to_string(),
+            },
+            Err(e) => Response::error(status_of(&e), &e.to_string()),
+        }
+    }
+}
+
+fn not_found(id: &str) -> (u16, Json) {
+    let message = format!("no entity with id {id}");
+    (
+        404,
+        Json::Object(vec![("error".into(), Json::String(message))]),
+    )
+}
+
+fn status_of(err: &DbError) -> u16 {
+    match err.kind() {
+        ErrorKind::Parse | ErrorKind::Validation => 400,
+        ErrorKind::IdNotFound | ErrorKind::TypeNotFound => 404,
+        ErrorKind::IdExists => 409,
+        _ => 500,
+    }
+}
+
+/// The operations of a registered table without its rust type.
+trait RestTable: Send + Sync {
+    fn list(&self, db: &mut Database, filters: &[(String, String)]) -> DbResult<Json>;
+    fn get(&self, db: &Database, id: &str) -> DbResult<Option<Json>>;
+    fn create(&self, db: &mut Database, body: Json) -> DbResult<Json>;
+    fn replace(&self, db: &mut Database, id: &str, body: Json) -> DbResult<Json>;
+    fn delete(&self, db: &mut Database, id: &str) -> DbResult<bool>;
+}
+
+struct TypedTable<T>(PhantomData<fn() -> T>);
+
+impl<T: Entity> TypedTable<T> {
+    fn parse_id(id: &str) -> DbResult<T::Id> {
+        from_text(&T::Id::field_type(), id)?.to()
+    }
+
+    fn to_json(entity: &T) -> DbResult<Json> {
+        Ok(to_json(&Value::of(entity)?))
+    }
+
+    fn from_json(json: &Json) -> DbResult<T> {
+        from_json(&T::field_type(), json)?.to()
+    }
+}
+
+impl<T: Entity + 'static> RestTable for TypedTable<T> {
+    fn list(&self, db: &mut Database, filters: &[(String, String)]) -> DbResult<Json> {
+        let filter = Filter::<T>::parse(filters)?;
+        let entities = match db.query_mut::<T>() {
+            Ok(query) => query.filter(|_| filter.clone()).collect_vec(),
+            Err(e) if e.kind() == ErrorKind::TypeNotFound => vec![],
+            Err(e) => return Err(e),
+        };
+
+        Ok(Json::Array(
+            entities
+                .iter()
+                .map(Self::to_json)
+                .collect::<DbResult<_>>()?,
+        ))
+    }
+
+    fn get(&self, db: &Database, id: &str) -> DbResult<Option<Json>> {
+        db.find_by_id::<T>(Self::parse_id(id)?)?
+            .map(|entity| Self::to_json(&entity))
+            .transpose()
+    }
+
+    fn create(&self, db: &mut Database, body: Json) -> DbResult<Json> {
+        let body = match T::GENERATE_ID && body.get(T::ID_FIELD).is_none() {
+            true => with_field(body, T::ID_FIELD, to_json(&Value::of(&T::Id::initial())?)),
+            false => body,
+        };
+        Self::to_json(&db.store(Self::from_json(&body)?)?)
+    }
+
+    fn replace(&self, db: &mut Database, id: &str, body: Json) -> DbResult<Json> {
+        let id = Self::parse_id(id)?;
+        let entity = Self::from_json(&with_field(body, T::ID_FIELD, to_json(&Value::of(&id)?)))?;
+        db.update_entity(entity.clone())?;
+        Self::to_json(&entity)
+    }
+
+    fn delete(&self, db: &mut Database, id: &str) -> DbResult<bool> {
+        let id = Self::parse_id(id)?;
+        if db.find_by_id::<T>(id)?.is_none() {
+            return Ok(false);
+        }
+        db.delte_entity_by_id::<T>(id)?;
+        Ok(true)
+    }
+}
+
+/// Sets a field of a json object.
+fn with_field(json: Json, name: &str, value: Json) -> Json {
+    match json {
+        Json::Object(mut fields) => {
+            match fields.iter_mut().find(|(n, _)| n == name) {
+                Some(field) => field.1 = value,
+                None => fields.push((name.to_string(), value)),
+            }
+            Json::Object(fields)
+        }
+        json => json,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+impl Cmp {
+    /// The symbol of the matching [GenExpr] operator.
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Lte => "<=",
+            Self::Gt => ">",
+            Self::Gte => ">=",
+        }
+    }
+}
+
+/// The conditions of the query parameters, an entity matches if it fulfills all of them.
+struct Filter<E> {
+    conditions: Vec<(String, Cmp, Value)>,
+    _int: PhantomData<fn(E)>,
+}
+
+impl<E> Clone for Filter<E> {
+    fn clone(&self) -> Self {
+        Self {
+            conditions: self.conditions.clone(),
+            _int: PhantomData,
+        }
+    }
+}
+
+impl<E: Entity> Filter<E> {
+    fn parse(params: &[(String, String)]) -> DbResult<Self> {
+        let schema = E::table_schema();
+        let conditions = params
+            .iter()
+            .map(|(key, value)| {
+                let (field, cmp) = match key.rsplit_once('.') {
+                    Some((field, "ne")) => (field, Cmp::Ne),
+                    Some((field, "lt")) => (field, Cmp::Lt),
+                    Some((field, "lte")) => (field, Cmp::Lte),
+                    Some((field, "gt")) => (field, Cmp::Gt),
+                    Some((field, "gte")) => (field, Cmp::Gte),
+                    _ => (key.as_str(), Cmp::Eq),
+                };
+                let ty = &schema
+                    .field(field)
+                    .ok_or_else(|| DbError::ParseError(format!("unknown field: {field}")))?
+                    .ty;
+                Ok((field.to_string(), cmp, from_text(ty, value)?))
+            })
+            .collect::<DbResult<_>>()?;
+
+        Ok(Self {
+            conditions,
+            _int: PhantomData,
+        })
+    }
+}
+
+impl<E: Entity> GenExpr<E> for Filter<E> {
+    type Output = bool;
+
+    fn exec(&self, _db: &Database, row: &E) -> Self::Output {
+        let Ok(row) = Value::of(row) else {
+            return false;
+        };
+        self.conditions.iter().all(|(field, cmp, value)| {
+            row.field(field).is_some_and(|field| {
+                // `None` is only equal to `None` and never ordered
+                let ord = compare(field, value);
+                match cmp {
+                    Cmp::Eq => ord.is_some_and(Ordering::is_eq),
+                    Cmp::Ne => !ord.is_some_and(Ordering::is_eq),
+                    Cmp::Lt => ord.is_some_and(Ordering::is_lt),
+                    Cmp::Lte => ord.is_some_and(Ordering::is_le),
+                    Cmp::Gt => ord.is_some_and(Ordering::is_gt),
+                    Cmp::Gte => ord.is_some_and(Ordering::is_ge),
+                }
+            })
+        })
+    }
+
+    fn info(&self) -> ExprInfo {
+        self.conditions
+            .iter()
+            .map(|(field, cmp, value)| {
+                let value = match value {
+                    Value::String(s) => format!("{s:?}"),
+                    value => value.to_string(),
+                };
+                ExprInfo::Binary(
+                    cmp.symbol(),
+                    Box::new(ExprInfo::Field(field.clone())),
+                    Box::new(ExprInfo::Const(value)),
+                )
+            })
+            .reduce(|a, b| ExprInfo::Binary("&&", Box::new(a), Box::new(b)))
+            .unwrap_or_else(|| ExprInfo::Const("true".to_string()))
+    }
+}
+
+struct Request {
+    method: String,
+    target: String,
+    body: String,
+}
+
+/// The largest request body that is accepted.
+pub const MAX_BODY_LEN: usize = 16 << 20;
+
+/// The longest request line or header that is accepted.
+pub const MAX_LINE_LEN: usize = 8 << 10;
+
+/// The most headers a request can have.
+pub const MAX_HEADERS: usize = 100;
+
+/// How long reading a request or writing a response can stall.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many connections are handled at the same time.
+pub const MAX_CONNECTIONS: usize = 64;
+
+/// Reads a line of at most [MAX_LINE_LEN] bytes, `false` if it is longer.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<bool> {
+    line.clear();
+    let len = reader
+        .by_ref()
+        .take(MAX_LINE_LEN as u64 + 1)
+        .read_line(line)?;
+    Ok(len <= MAX_LINE_LEN)
+}
+
+/// Reads a request, the error response if it is malformed or too large.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Result<Request, Response>> {
+    let malformed = || Ok(Err(Response::error(400, "malformed request")));
+    let too_large = || Ok(Err(Response::error(431, "the headers are too large")));
+
+    let mut line = String::new();
+    if !read_line(reader, &mut line)? {
+        return malformed();
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return malformed();
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut content_length = 0;
+    for headers in 0.. {
+        if headers > MAX_HEADERS || !read_line(reader, &mut line)? {
+            return too_large();
+        }
+        if line.is_empty() {
+            return malformed();
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            let Ok(len) = value.trim().parse() else {
+                return malformed();
+            };
+            content_length = len;
+        }
+    }
+
+    if content_length > MAX_BODY_LEN {
+        let message = format!("the body must not be larger than {MAX_BODY_LEN} bytes");
+        return Ok(Err(Response::error(413, &message)));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    match String::from_utf8(body) {
+        Ok(body) => Ok(Ok(Request {
+            method,
+            target,
+            body,
+        })),
+        Err(_) => malformed(),
+    }
+}
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            body: Json::Object(vec![("error".into(), Json::String(message.into()))]).to_string(),
+        }
+    }
+
+    fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Content Too Large",
+            431 => "Request Header Fields Too Large",
+            _ => "Internal Server Error",
+        };
+        write!(
+            writer,
+            "HTTP/1.1 {} {reason}\r\ncontent-type: application/json\r\n\
+             content-length: {}\r\nconnection: close\r\n\r\n{}",
+            self.status,
+            self.body.len(),
+            self.body
+        )?;
+        writer.flush()
+    }
+}
+
+/// Splits a query string into decoded keys and values.
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// Decodes `%xx` escapes and `+` as a space.
+fn percent_decode(src: &str) -> String {
+    let src = src.as_bytes();
+    let escaped = |i: usize| {
+        let hex = std::str::from_utf8(src.get(i + 1..i + 3)?).ok()?;
+        u8::from_str_radix(hex, 16).ok()
+    };
+
+    let mut res = Vec::with_capacity(src.len());
+    let mut i = 0;
+    while i < src.len() {
+        match (src[i], escaped(i)) {
+            (b'+', _) => res.push(b' '),
+            (b'%', Some(byte)) => {
+                res.push(byte);
+                i += 2;
+            }
+            (byte, _) => res.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&res).into_owned()
+}
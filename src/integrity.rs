@@ -0,0 +1,321 @@
+//! Integrity checking and repair of stored tables.
+
+use std::fmt::Display;
+
+use crate::{
+    byte_reader::ByteReader,
+    catalog::TableInfo,
+    checksum::crc32,
+    entity_meta::{Checksums, encode_inner, parse_format_version, with_len},
+    schema::FieldType,
+    storable::Storable,
+    type_hash::TypeHash,
+    value::Value,
+};
+
+/// A single problem found in a table file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Corruption {
+    /// Byte offset in the table file.
+    pub offset: usize,
+    /// The index of the affected row if the problem is inside a row.
+    pub row: Option<usize>,
+    /// The path of the affected field, e.g. `address.street` or `tags[2]`.
+    pub field: Option<String>,
+    pub reason: String,
+}
+
+impl Display for Corruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "offset {}", self.offset)?;
+        if let Some(row) = self.row {
+            write!(f, ", row {row}")?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, ", field {field}")?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// The result of verifying a single table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableReport {
+    pub table: String,
+    pub type_hash: TypeHash,
+    /// The number of rows that could be read without problems.
+    pub intact_rows: usize,
+    /// The number of rows that are corrupted or could not be found
+    /// because the framing of the file is broken.
+    pub lost_rows: usize,
+    pub corruptions: Vec<Corruption>,
+}
+
+impl TableReport {
+    pub fn is_ok(&self) -> bool {
+        self.corruptions.is_empty()
+    }
+}
+
+/// Everything that could be read from a table file.
+pub(crate) struct Salvage<'a> {
+    pub report: TableReport,
+    /// The content of the last id block.
+    last_id: Option<Vec<u8>>,
+    rows: Vec<ByteReader<'a>>,
+}
+
+impl Salvage<'_> {
+    /// Encodes a new table file containing only the intact rows.
+    /// Returns `None` if the last id could not be recovered.
+    pub fn encoded(&self) -> Option<Vec<u8>> {
+        let last_id = self.last_id.clone()?;
+        Some(with_len(encode_inner(
+            with_len(last_id),
+            self.rows
+                .iter()
+                .map(|r| with_len(r.read_byte_slice().to_vec()))
+                .collect(),
+        )))
+    }
+}
+
+fn corruption(offset: usize, row: Option<usize>, reason: impl Into<String>) -> Corruption {
+    Corruption {
+        offset,
+        row,
+        field: None,
+        reason: reason.into(),
+    }
+}
+
+/// Decodes a value field by field to find the exact location of a problem.
+fn locate(ty: &FieldType, mut reader: ByteReader, path: &str) -> Option<(usize, String, String)> {
+    let mut locate_block = |ty: &FieldType, path: String| {
+        let offset = reader.position();
+        match reader.reader_for_block() {
+            Ok(block) => locate(ty, block, &path),
+            Err(_) => Some((offset, path, "invalid length".to_string())),
+        }
+    };
+
+    match ty {
+        FieldType::Struct { fields, .. } => fields.iter().find_map(|f| {
+            let path = if path.is_empty() {
+                f.name.clone()
+            } else {
+                format!("{path}.{}", f.name)
+            };
+            locate_block(&f.ty, path)
+        }),
+        FieldType::List(inner) => {
+            let mut i = 0;
+            while !reader.is_at_end() {
+                let offset = reader.position();
+                let problem = match reader.reader_for_block() {
+                    Ok(block) => locate(inner, block, &format!("{path}[{i}]")),
+                    Err(_) => Some((offset, format!("{path}[{i}]"), "invalid length".to_string())),
+                };
+                if problem.is_some() {
+                    return problem;
+                }
+                i += 1;
+            }
+            None
+        }
+        FieldType::Option(_) if reader.is_at_end() => None,
+        FieldType::Option(inner) => {
+            let offset = reader.position();
+            match reader.reader_for_block() {
+                Ok(block) => locate(inner, block, path).or_else(|| {
+                    let reason = "more than one value".to_string();
+                    (!reader.is_at_end()).then(|| (reader.position(), path.to_string(), reason))
+                }),
+                Err(_) => Some((offset, path.to_string(), "invalid length".to_string())),
+            }
+        }
+        _ => {
+            let offset = reader.position();
+            Value::decode(ty, reader)
+                .err()
+                .map(|_| (offset, path.to_string(), format!("invalid {ty}")))
+        }
+    }
+}
+
+/// Checks a table file and collects everything that can be salvaged.
+pub(crate) fn check_table<'a>(table: &TableInfo, data: &'a [u8]) -> Salvage<'a> {
+    let mut salvage = Salvage {
+        report: TableReport {
+            table: table.name(),
+            type_hash: table.type_hash,
+            intact_rows: 0,
+            lost_rows: 0,
+            corruptions: vec![],
+        },
+        last_id: None,
+        rows: vec![],
+    };
+    let corruptions = &mut salvage.report.corruptions;
+    let schema = table.schema.as_ref();
+
+    let mut outer = ByteReader::new(data);
+    let Ok(mut reader) = outer.reader_for_block() else {
+        corruptions.push(corruption(0, None, "invalid table length"));
+        return salvage;
+    };
+    let body = reader.read_byte_slice();
+    let body_start = reader.position();
+
+    let offset = reader.position();
+    let mut format = None;
+    match reader.reader_for_block().map(String::decoded) {
+        Ok(Ok(version)) if parse_format_version(&version).is_some() => {
+            format = parse_format_version(&version);
+        }
+        Ok(Ok(version)) => {
+            // the layout of other versions is unknown so nothing can be salvaged.
+            corruptions.push(corruption(
+                offset,
+                None,
+                format!("unsupported version {version}"),
+            ));
+            return salvage;
+        }
+        _ => corruptions.push(corruption(offset, None, "invalid version")),
+    }
+
+    let offset = reader.position();
+    match reader.reader_for_block() {
+        Ok(last_id) => {
+            let id_type = schema.and_then(|s| s.id_type());
+            if id_type.is_some_and(|ty| Value::decode(ty, last_id.clone()).is_err()) {
+                corruptions.push(Corruption {
+                    offset,
+                    row: None,
+                    field: Some("last_id".to_string()),
+                    reason: "invalid last id".to_string(),
+                });
+            } else {
+                salvage.last_id = Some(last_id.read_byte_slice().to_vec());
+            }
+        }
+        Err(_) => {
+            corruptions.push(corruption(offset, None, "invalid last id length"));
+            return salvage;
+        }
+    }
+
+    let offset = reader.position();
+    let Ok(mut entities) = reader.reader_for_block() else {
+        corruptions.push(corruption(offset, None, "invalid entities length"));
+        return salvage;
+    };
+
+    let mut rows = vec![];
+    while !entities.is_at_end() {
+        let offset = entities.position();
+        match entities.reader_for_block() {
+            Ok(row) => rows.push(row),
+            Err(_) => {
+                corruptions.push(corruption(
+                    offset,
+                    Some(rows.len()),
+                    "invalid row length, the following rows are lost",
+                ));
+                break;
+            }
+        }
+    }
+    let framing_broken = !entities.is_at_end();
+
+    let file_checksum = crc32(&body[..reader.position() - body_start]);
+    let offset = reader.position();
+    let checksums = if reader.is_at_end() {
+        if format.is_some_and(|f| f >= 2) {
+            corruptions.push(corruption(offset, None, "missing checksums"));
+        }
+        None
+    } else {
+        match reader.reader_for_block().and_then(Checksums::decoded) {
+            Ok(checksums) => Some(checksums),
+            Err(_) => {
+                corruptions.push(corruption(offset, None, "invalid checksums"));
+                None
+            }
+        }
+    };
+
+    if let Some(checksums) = &checksums {
+        if checksums.file != file_checksum {
+            corruptions.push(corruption(offset, None, "file checksum mismatch"));
+        }
+        if !framing_broken && checksums.records.len() != rows.len() {
+            corruptions.push(corruption(
+                offset,
+                None,
+                format!(
+                    "expected {} rows, found {}",
+                    checksums.records.len(),
+                    rows.len()
+                ),
+            ));
+        }
+    }
+
+    let row_type = schema.map(|s| s.row_type());
+    for (i, row) in rows.into_iter().enumerate() {
+        let offset = row.position();
+
+        if let Some(expected) = checksums.as_ref().and_then(|c| c.records.get(i))
+            && *expected != crc32(row.read_byte_slice())
+        {
+            let mut problem = corruption(offset, Some(i), "row checksum mismatch");
+            if let Some((offset, field, reason)) =
+                row_type.as_ref().and_then(|ty| locate(ty, row.clone(), ""))
+            {
+                problem.offset = offset;
+                problem.field = Some(field);
+                problem.reason = format!("row checksum mismatch, {reason}");
+            }
+            corruptions.push(problem);
+            continue;
+        }
+
+        if let Some((offset, field, reason)) =
+            row_type.as_ref().and_then(|ty| locate(ty, row.clone(), ""))
+        {
+            corruptions.push(Corruption {
+                offset,
+                row: Some(i),
+                field: Some(field),
+                reason,
+            });
+            continue;
+        }
+
+        salvage.rows.push(row);
+    }
+
+    // a broken last id can be recovered from the remaining rows
+    if salvage.last_id.is_none()
+        && let Some(schema) = schema
+        && let Some(row_type) = &row_type
+    {
+        salvage.last_id = salvage
+            .rows
+            .iter()
+            .filter_map(|r| Value::decode(row_type, r.clone()).ok())
+            .filter_map(|r| r.field(&schema.id_field).cloned())
+            .reduce(|a, b| if b > a { b } else { a })
+            .map(|id| id.encoded()[4..].to_vec());
+    }
+
+    salvage.report.intact_rows = salvage.rows.len();
+    salvage.report.lost_rows = checksums
+        .map(|c| c.records.len())
+        .unwrap_or(table.row_count)
+        .saturating_sub(salvage.rows.len());
+
+    salvage
+}
@@ -30,18 +30,39 @@
 //! }
 //! ```
 
+#[cfg(feature = "async")]
+pub mod async_db;
+pub mod backup;
 #[doc(hidden)]
 pub mod byte_reader;
+pub mod catalog;
+pub mod changes;
+mod checksum;
 pub mod db;
+pub mod dyn_query;
 pub mod entity;
 pub mod entity_meta;
+pub mod error;
+pub mod format;
+pub mod fulltext;
 pub mod gen_query;
+#[cfg(feature = "http")]
+pub mod http;
 pub mod id;
+pub mod integrity;
+mod json;
 pub mod query;
+pub mod remote;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod server;
 mod sha;
+pub mod sql;
 pub mod storable;
 #[doc(hidden)]
 pub mod type_hash;
+pub mod value;
 
 pub use somedb_macros::Entity;
 pub use somedb_macros::Storable;
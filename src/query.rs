@@ -1,49 +1,86 @@
-use std::marker::PhantomData;
+use std::{fmt::Display, marker::PhantomData};
 
 use crate::{
-    db::{Database, DbResult},
+    byte_reader::ByteReader,
+    db::{Database, DbError, DbResult},
     entity::Entity,
-    entity_meta::EntityMeta,
-    gen_query::{ExprEntity, GenExpr},
+    entity_meta::EntityStream,
+    gen_query::{ExprEntity, ExprInfo, GenExpr},
+    storable::Storable,
 };
 
-pub struct DbQuery<T: Entity> {
-    data: EntityMeta<T>,
-    index: usize,
+/// Streams the entities of a table from disk.
+///
+/// Iteration stops at the first error, which can be retrieved with
+/// [error](Self::error). Use [try_next](Self::try_next) to handle errors
+/// while iterating.
+///
+/// The table stays locked for reading until the query is dropped, which is
+/// why it borrows the database: writing through the same handle would wait
+/// for the lock forever.
+pub struct DbQuery<'a, T: Entity> {
+    rows: EntityStream,
+    error: Option<DbError>,
+    _int: PhantomData<(&'a Database, T)>,
 }
 
-impl<T: Entity> DbQuery<T> {
-    pub(crate) fn new(db: &Database) -> DbResult<Self> {
+impl<'a, T: Entity> DbQuery<'a, T> {
+    pub(crate) fn new(db: &'a Database) -> DbResult<Self> {
         Ok(Self {
-            data: db.raw_read_all::<T>()?,
-            index: 0,
+            rows: db.stream_table::<T>()?,
+            error: None,
+            _int: PhantomData,
         })
     }
+
+    /// Reads the next entity, `None` once all entities were read.
+    pub fn try_next(&mut self) -> DbResult<Option<T>> {
+        next_entity(&mut self.rows)
+    }
+
+    /// The error that ended the iteration early.
+    pub fn error(&self) -> Option<&DbError> {
+        self.error.as_ref()
+    }
 }
 
-impl<T: Entity> Iterator for DbQuery<T> {
+impl<T: Entity> Iterator for DbQuery<'_, T> {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.data.entities.len() {
+        if self.error.is_some() {
             return None;
         }
-        self.index += 1;
-        Some(self.data.entities[self.index - 1].clone())
+        self.try_next().unwrap_or_else(|e| {
+            self.error = Some(e);
+            None
+        })
     }
 }
 
+pub(crate) fn next_entity<T: Entity>(rows: &mut EntityStream) -> DbResult<Option<T>> {
+    let Some(reader) = rows.next_entity()? else {
+        return Ok(None);
+    };
+    let mut entity = T::decoded(reader)?;
+    entity.after_load().map_err(DbError::validation::<T>)?;
+    Ok(Some(entity))
+}
+
 pub struct DbQueryMut<'a, T: Entity> {
     db: &'a mut Database,
-    data: EntityMeta<T>,
-    index: usize,
+    rows: EntityStream,
+    last_id: T::Id,
+    error: Option<DbError>,
 }
 
 impl<'a, T: 'a + Entity> DbQueryMut<'a, T> {
     pub(crate) fn new(db: &'a mut Database) -> DbResult<Self> {
+        let rows = db.stream_table::<T>()?;
         Ok(Self {
-            data: db.raw_read_all::<T>()?,
+            last_id: T::Id::decoded(ByteReader::new(&rows.last_id))?,
+            rows,
             db,
-            index: 0,
+            error: None,
         })
     }
 }
@@ -51,11 +88,13 @@ impl<'a, T: 'a + Entity> DbQueryMut<'a, T> {
 impl<'a, T: 'a + Entity> DbIterator for DbQueryMut<'a, T> {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.data.entities.len() {
+        if self.error.is_some() {
             return None;
         }
-        self.index += 1;
-        Some(self.data.entities[self.index - 1].clone())
+        next_entity(&mut self.rows).unwrap_or_else(|e| {
+            self.error = Some(e);
+            None
+        })
     }
 
     fn get_db_mut(&mut self) -> &mut Database {
@@ -67,7 +106,23 @@ impl<'a, T: 'a + Entity> DbIterator for DbQueryMut<'a, T> {
     }
 
     fn get_last_id(&self) -> <Self::Item as Entity>::Id {
-        self.data.last_id
+        self.last_id
+    }
+
+    fn take_error(&mut self) -> Option<DbError> {
+        self.error.take()
+    }
+
+    fn scans_table(&self) -> bool {
+        true
+    }
+
+    fn explain(&self) -> Plan {
+        Plan::FullScan {
+            table: T::table_schema().name,
+            id_field: T::ID_FIELD,
+            rows: self.db.row_count(&T::type_hash()).ok(),
+        }
     }
 }
 
@@ -79,16 +134,32 @@ pub trait DbIterator: Sized {
     fn get_db_mut(&mut self) -> &mut Database;
     fn get_db(&self) -> &Database;
 
+    /// Takes the error that ended the iteration early.
+    fn take_error(&mut self) -> Option<DbError>;
+
+    /// Describes how the query is run without running it.
+    fn explain(&self) -> Plan;
+
+    /// Whether this returns the stored entities of the table unchanged,
+    /// so a filter on the id can stop at the first match.
+    #[doc(hidden)]
+    fn scans_table(&self) -> bool {
+        false
+    }
+
     fn filter<Q, P>(self, predicate: P) -> DbFilter<Q, P, Self>
     where
         Q: GenExpr<Self::Item, Output = bool>,
         P: Fn(&<Self::Item as Entity>::ExprBase) -> Q,
     {
+        let query = predicate(&<<Self::Item as Entity>::ExprBase as ExprEntity<
+            Self::Item,
+        >>::new());
         DbFilter {
+            lookup: self.scans_table() && id_lookup(&query.info(), Self::Item::ID_FIELD).is_some(),
+            done: false,
             inner: self,
-            query: predicate(&<<Self::Item as Entity>::ExprBase as ExprEntity<
-                Self::Item,
-            >>::new()),
+            query,
             _int: PhantomData,
         }
     }
@@ -113,9 +184,16 @@ pub trait DbIterator: Sized {
         while let Some(e) = self.next() {
             entities.push(e);
         }
+        // the table would lose the entities that couldn't be read
+        if let Some(e) = self.take_error() {
+            return Err(e);
+        }
         let last_id = self.get_last_id();
         let db = self.get_db_mut();
-        db.raw_write_all::<Self::Item>(EntityMeta { last_id, entities })?;
+        let mut raw = db.raw_read_all::<Self::Item>()?;
+        raw.replace(entities)?;
+        raw.last_id = last_id;
+        db.raw_write_all(raw)?;
 
         Ok(())
     }
@@ -129,6 +207,9 @@ where
 {
     inner: I,
     query: Q,
+    /// Whether the predicate looks up an id of the table, see [Plan::IdLookup].
+    lookup: bool,
+    done: bool,
     _int: PhantomData<P>,
 }
 
@@ -140,9 +221,14 @@ where
 {
     type Item = I::Item;
     fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
         while let Some(inner_next) = self.inner.next() {
             let db = self.get_db();
-            if self.query.exec(db, inner_next.get_id()) {
+            if self.query.exec(db, &inner_next) {
+                // ids are unique, no other entity can match
+                self.done = self.lookup;
                 return Some(inner_next);
             }
         }
@@ -160,6 +246,41 @@ where
     fn get_last_id(&self) -> <Self::Item as Entity>::Id {
         self.inner.get_last_id()
     }
+
+    fn take_error(&mut self) -> Option<DbError> {
+        self.inner.take_error().or_else(|| self.query.take_error())
+    }
+
+    fn explain(&self) -> Plan {
+        let predicate = self.query.info();
+        let input = self.inner.explain();
+        let lookup = match &input {
+            Plan::FullScan {
+                table,
+                id_field,
+                rows,
+            } if self.lookup => id_lookup(&predicate, id_field).map(|(id, rest)| {
+                let lookup = Plan::IdLookup {
+                    table: table.clone(),
+                    id_field,
+                    id,
+                    rows: *rows,
+                };
+                match rest {
+                    Some(predicate) => Plan::Filter {
+                        predicate,
+                        input: Box::new(lookup),
+                    },
+                    None => lookup,
+                }
+            }),
+            _ => None,
+        };
+        lookup.unwrap_or_else(|| Plan::Filter {
+            predicate,
+            input: Box::new(input),
+        })
+    }
 }
 
 pub struct DbMap<I, P>
@@ -192,4 +313,163 @@ where
     fn get_last_id(&self) -> <Self::Item as Entity>::Id {
         self.inner.get_last_id()
     }
+
+    fn take_error(&mut self) -> Option<DbError> {
+        self.inner.take_error()
+    }
+    fn explain(&self) -> Plan {
+        Plan::Map {
+            input: Box::new(self.inner.explain()),
+        }
+    }
+}
+
+/// The steps of a query as returned by [DbIterator::explain], the
+/// last step is the outermost one.
+///
+/// There is no step for ranges of an index since tables only have the
+/// [fulltext index](crate::db::Database::search), which queries don't use.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Plan {
+    /// Reads every entity of the table.
+    FullScan {
+        table: String,
+        id_field: &'static str,
+        /// The number of stored entities, `None` if the table could not be read.
+        rows: Option<usize>,
+    },
+    /// Reads the entities of the table up to the one with the id, used
+    /// when a filter directly on the table compares the id with a constant.
+    IdLookup {
+        table: String,
+        id_field: &'static str,
+        /// The constant the id is compared with.
+        id: ExprInfo,
+        /// The number of stored entities, `None` if the table could not be read.
+        rows: Option<usize>,
+    },
+    /// Only keeps the entities matching the predicate.
+    Filter {
+        predicate: ExprInfo,
+        input: Box<Plan>,
+    },
+    /// Changes every entity.
+    Map { input: Box<Plan> },
+}
+
+impl Plan {
+    /// The estimated number of entities returned by this step.
+    ///
+    /// Filters are estimated with fixed selectivities for each kind of
+    /// predicate, comparing the id with a constant matches a single entity.
+    pub fn estimated_rows(&self) -> Option<usize> {
+        match self {
+            Self::FullScan { rows, .. } => *rows,
+            Self::IdLookup { rows, .. } => rows.map(|rows| rows.min(1)),
+            Self::Filter { predicate, input } => {
+                let rows = input.estimated_rows()?;
+                let selectivity = selectivity(predicate, input.id_field(), rows);
+                Some((rows as f64 * selectivity).round() as usize)
+            }
+            Self::Map { input } => input.estimated_rows(),
+        }
+    }
+
+    fn id_field(&self) -> &'static str {
+        match self {
+            Self::FullScan { id_field, .. } | Self::IdLookup { id_field, .. } => id_field,
+            Self::Filter { input, .. } | Self::Map { input } => input.id_field(),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let rows = match self.estimated_rows() {
+            Some(rows) => rows.to_string(),
+            None => "?".to_string(),
+        };
+        write!(f, "{:1$}", "", depth * 2)?;
+
+        match self {
+            Self::FullScan { table, .. } => writeln!(f, "full scan of {table}, {rows} rows"),
+            Self::IdLookup {
+                table,
+                id_field,
+                id,
+                ..
+            } => writeln!(f, "lookup of {table} by {id_field} == {id}, {rows} rows"),
+            Self::Filter { predicate, input } => {
+                writeln!(f, "filter {predicate}, ~{rows} rows")?;
+                input.fmt_indented(f, depth + 1)
+            }
+            Self::Map { input } => {
+                writeln!(f, "map, ~{rows} rows")?;
+                input.fmt_indented(f, depth + 1)
+            }
+        }
+    }
+}
+
+/// Prints the plan as a tree with the outermost step first.
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Finds a comparison of the id with a constant in the conjunctions of the
+/// predicate. Returns the constant and the rest of the predicate.
+fn id_lookup(predicate: &ExprInfo, id_field: &str) -> Option<(ExprInfo, Option<ExprInfo>)> {
+    let and = |rest: Option<ExprInfo>, other: &ExprInfo| match rest {
+        Some(rest) => ExprInfo::Binary("&&", Box::new(rest), Box::new(other.clone())),
+        None => other.clone(),
+    };
+    match predicate {
+        ExprInfo::Binary("==", a, b) => match (a.as_ref(), b.as_ref()) {
+            (ExprInfo::Field(f), id @ ExprInfo::Const(_))
+            | (id @ ExprInfo::Const(_), ExprInfo::Field(f))
+                if f == id_field =>
+            {
+                Some((id.clone(), None))
+            }
+            _ => None,
+        },
+        ExprInfo::Binary("&&", a, b) => match id_lookup(a, id_field) {
+            Some((id, rest)) => Some((id, Some(and(rest, b)))),
+            None => id_lookup(b, id_field).map(|(id, rest)| (id, Some(and(rest, a)))),
+        },
+        _ => None,
+    }
+}
+
+/// Estimates the fraction of the `rows` entities that match the predicate.
+fn selectivity(predicate: &ExprInfo, id_field: &str, rows: usize) -> f64 {
+    match predicate {
+        ExprInfo::Binary("&&", a, b) => {
+            selectivity(a, id_field, rows) * selectivity(b, id_field, rows)
+        }
+        ExprInfo::Binary("||", a, b) => {
+            let (a, b) = (
+                selectivity(a, id_field, rows),
+                selectivity(b, id_field, rows),
+            );
+            a + b - a * b
+        }
+        ExprInfo::Unary("!", inner) => 1.0 - selectivity(inner, id_field, rows),
+        ExprInfo::Binary("==", a, b) => match (a.as_ref(), b.as_ref()) {
+            (ExprInfo::Field(f), ExprInfo::Const(_)) | (ExprInfo::Const(_), ExprInfo::Field(f))
+                if f == id_field =>
+            {
+                1.0 / rows.max(1) as f64
+            }
+            _ => 0.1,
+        },
+        ExprInfo::Binary("!=", ..) => 0.9,
+        ExprInfo::Binary("<" | "<=" | ">" | ">=", ..) => 1.0 / 3.0,
+        ExprInfo::Method("between", _) => 0.25,
+        ExprInfo::Method("in_list", _) | ExprInfo::Unary("is_null", _) => 0.1,
+        ExprInfo::Binary("in_query", ..) => 0.25,
+        ExprInfo::Binary("contains" | "starts_with" | "ends_with" | "like", ..)
+        | ExprInfo::Method("matches", _) => 0.25,
+        _ => 0.5,
+    }
 }
@@ -0,0 +1,711 @@
+//! Access to a database served by the `somedb-server` binary.
+//!
+//! A [RemoteDatabase] offers the same operations as a [Database](crate::db::Database),
+//! so processes on different hosts can share one database without relying on the
+//! lock files of a shared directory. The server doesn't know the rust types of
+//! the tables: clients read a whole table, apply their change including the
+//! [hooks](crate::entity::EntityHooks) and constraints of the entity, and send
+//! the new table back. The server checks the constraints persisted with the schema
+//! again and only replaces a table if nobody else changed it in the meantime,
+//! otherwise the client retries the change with the current table.
+//!
+//! ```rust
+//! use std::{net::TcpListener, thread};
+//!
+//! use somedb::{db::Database, entity, remote::RemoteDatabase, server};
+//!
+//! #[entity]
+//! #[derive(Debug, PartialEq)]
+//! struct Note {
+//!     #[entity_id(auto_generate)]
+//!     id: u32,
+//!     text: String,
+//! }
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let listener = TcpListener::bind("127.0.0.1:0")?;
+//!     let addr = listener.local_addr()?;
+//!     let db = Database::new("remote_doc_sdb/", true)?;
+//!     thread::spawn(move || server::serve_tcp(db, listener));
+//!
+//!     let mut db = RemoteDatabase::connect(addr)?;
+//!     let note = db.store(Note { id: 0, text: "hello".into() })?;
+//!     assert_eq!(db.find_by_id::<Note>(note.id)?, Some(note));
+//!     Ok(())
+//! }
+//! ```
+//!
+//! ## Protocol
+//! Every request and response is a length prefixed block that starts with
+//! an encoded `u8` tag followed by the encoded fields of the message, using
+//! the same encoding as the stored tables. A connection handles one request
+//! at a time, every request gets exactly one response.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::Path};
+
+use crate::{
+    byte_reader::ByteReader,
+    catalog::TableInfo,
+    db::{
+        DbError, DbResult, ErrorKind, Fingerprint, check_compatible, check_constraints, fingerprint,
+    },
+    dyn_query::Expr,
+    entity::Entity,
+    entity_meta::EntityMeta,
+    schema::TableSchema,
+    storable::Storable,
+    type_hash::TypeHash,
+    value::Value,
+};
+
+/// The error kinds in the order of their tags.
+const ERROR_KINDS: [ErrorKind; 12] = [
+    ErrorKind::IdExists,
+    ErrorKind::TypeNotFound,
+    ErrorKind::IdNotFound,
+    ErrorKind::Io,
+    ErrorKind::InvalidFileVersion,
+    ErrorKind::SchemaNotFound,
+    ErrorKind::Parse,
+    ErrorKind::ChecksumMismatch,
+    ErrorKind::Decode,
+    ErrorKind::BackupNotFound,
+    ErrorKind::SchemaMismatch,
+    ErrorKind::Validation,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Request {
+    /// Lists the stored tables.
+    Catalog,
+    /// Reads the schema and the file of a table.
+    Read { table: TypeHash },
+    /// Replaces the file of a table if it still has the `expected` fingerprint.
+    Write {
+        table: TypeHash,
+        schema: TableSchema,
+        expected: Fingerprint,
+        data: Vec<u8>,
+    },
+    /// Deletes a table if its file still has the `expected` fingerprint.
+    Delete {
+        table: TypeHash,
+        expected: Fingerprint,
+    },
+    /// Reads the schema and the rows of a table matching a [dynamic expression](Expr)
+    /// in its text form.
+    Query { table: TypeHash, filter: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Response {
+    Tables(Vec<TableInfo>),
+    /// The schema and the file of a table, `None` if the table isn't stored.
+    Table(Option<(Option<TableSchema>, Vec<u8>)>),
+    /// Whether a table was written or deleted.
+    Done(bool),
+    Error {
+        kind: ErrorKind,
+        message: String,
+    },
+}
+
+impl Request {
+    pub(crate) fn encoded(&self) -> Vec<u8> {
+        let mut res = vec![];
+        match self {
+            Self::Catalog => res.append(&mut 0u8.encoded()),
+            Self::Read { table } => {
+                res.append(&mut 1u8.encoded());
+                res.append(&mut bytes(table.as_bytes()));
+            }
+            Self::Write {
+                table,
+                schema,
+                expected,
+                data,
+            } => {
+                res.append(&mut 2u8.encoded());
+                res.append(&mut bytes(table.as_bytes()));
+                res.append(&mut schema.encoded());
+                res.append(&mut bytes(expected));
+                res.append(&mut bytes(data));
+            }
+            Self::Delete { table, expected } => {
+                res.append(&mut 3u8.encoded());
+                res.append(&mut bytes(table.as_bytes()));
+                res.append(&mut bytes(expected));
+            }
+            Self::Query { table, filter } => {
+                res.append(&mut 4u8.encoded());
+                res.append(&mut bytes(table.as_bytes()));
+                res.append(&mut filter.encoded());
+            }
+        }
+        bytes(&res)
+    }
+
+    pub(crate) fn decoded(mut reader: ByteReader) -> DbResult<Self> {
+        let offset = reader.position();
+        Ok(match reader.read::<u8>()? {
+            0 => Self::Catalog,
+            1 => Self::Read {
+                table: read_type_hash(&mut reader)?,
+            },
+            2 => Self::Write {
+                table: read_type_hash(&mut reader)?,
+                schema: reader.read()?,
+                expected: read_fingerprint(&mut reader)?,
+                data: reader.reader_for_block()?.read_byte_slice().to_vec(),
+            },
+            3 => Self::Delete {
+                table: read_type_hash(&mut reader)?,
+                expected: read_fingerprint(&mut reader)?,
+            },
+            4 => Self::Query {
+                table: read_type_hash(&mut reader)?,
+                filter: reader.read()?,
+            },
+            tag => return Err(unknown_tag("Request", offset, tag)),
+        })
+    }
+}
+
+impl Response {
+    pub(crate) fn error(err: DbError) -> Self {
+        Self::Error {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+
+    pub(crate) fn encoded(&self) -> Vec<u8> {
+        let mut res = vec![];
+        match self {
+            Self::Tables(tables) => {
+                res.append(&mut 0u8.encoded());
+                for table in tables {
+                    res.append(&mut encode_table_info(table));
+                }
+            }
+            Self::Table(table) => {
+                res.append(&mut 1u8.encoded());
+                res.append(&mut option(table.as_ref().map(|(schema, data)| {
+                    let mut res = option(schema.as_ref().map(|s| s.encoded()));
+                    res.append(&mut bytes(data));
+                    res
+                })));
+            }
+            Self::Done(done) => {
+                res.append(&mut 2u8.encoded());
+                res.append(&mut (*done as u8).encoded());
+            }
+            Self::Error { kind, message } => {
+                res.append(&mut 3u8.encoded());
+                let kind = ERROR_KINDS.iter().position(|k| k == kind).unwrap_or(3);
+                res.append(&mut (kind as u8).encoded());
+                res.append(&mut message.encoded());
+            }
+        }
+        bytes(&res)
+    }
+
+    pub(crate) fn decoded(mut reader: ByteReader) -> DbResult<Self> {
+        let offset = reader.position();
+        Ok(match reader.read::<u8>()? {
+            0 => {
+                let mut tables = vec![];
+                while !reader.is_at_end() {
+                    tables.push(decode_table_info(reader.reader_for_block()?)?);
+                }
+                Self::Tables(tables)
+            }
+            1 => Self::Table(read_option(&mut reader, |reader| {
+                let schema = read_option(reader, |reader| reader.read::<TableSchema>())?;
+                let data = reader.reader_for_block()?.read_byte_slice().to_vec();
+                Ok((schema, data))
+            })?),
+            2 => Self::Done(reader.read::<u8>()? == 1),
+            3 => {
+                let offset = reader.position();
+                let kind = reader.read::<u8>()?;
+                Self::Error {
+                    kind: *ERROR_KINDS
+                        .get(kind as usize)
+                        .ok_or_else(|| unknown_tag("ErrorKind", offset, kind))?,
+                    message: reader.read()?,
+                }
+            }
+            tag => return Err(unknown_tag("Response", offset, tag)),
+        })
+    }
+}
+
+fn unknown_tag(type_name: &'static str, offset: usize, tag: u8) -> DbError {
+    DbError::Decode {
+        type_name,
+        offset,
+        reason: format!("unknown tag {tag}"),
+    }
+}
+
+/// Encodes raw bytes as a block.
+fn bytes(data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::from((data.len() as u32).to_be_bytes());
+    res.extend_from_slice(data);
+    res
+}
+
+/// Encodes an optional value that is already encoded.
+fn option(value: Option<Vec<u8>>) -> Vec<u8> {
+    match value {
+        Some(mut value) => {
+            let mut res = 1u8.encoded();
+            res.append(&mut value);
+            res
+        }
+        None => 0u8.encoded(),
+    }
+}
+
+fn read_option<'a, T>(
+    reader: &mut ByteReader<'a>,
+    read: impl FnOnce(&mut ByteReader<'a>) -> DbResult<T>,
+) -> DbResult<Option<T>> {
+    match reader.read::<u8>()? {
+        0 => Ok(None),
+        _ => read(reader).map(Some),
+    }
+}
+
+fn read_type_hash(reader: &mut ByteReader) -> DbResult<TypeHash> {
+    let hash = reader.reader_for_block()?.read_exact("TypeHash")?;
+    Ok(unsafe { TypeHash::from_raw(hash) })
+}
+
+fn read_fingerprint(reader: &mut ByteReader) -> DbResult<Fingerprint> {
+    reader.reader_for_block()?.read_exact("Fingerprint")
+}
+
+fn encode_table_info(table: &TableInfo) -> Vec<u8> {
+    let mut res = bytes(table.type_hash.as_bytes());
+    res.append(&mut table.path.to_string_lossy().to_string().encoded());
+    res.append(&mut table.file_size.encoded());
+    res.append(&mut option(table.format_version.map(|v| v.encoded())));
+    res.append(&mut (table.row_count as u64).encoded());
+    res.append(&mut option(table.schema.as_ref().map(|s| s.encoded())));
+    res.append(&mut option(table.last_id.as_ref().map(|id| id.encoded())));
+    bytes(&res)
+}
+
+fn decode_table_info(mut reader: ByteReader) -> DbResult<TableInfo> {
+    let type_hash = read_type_hash(&mut reader)?;
+    let path = PathBuf::from(reader.read::<String>()?);
+    let file_size = reader.read::<u64>()?;
+    let format_version = read_option(&mut reader, |reader| reader.read::<u32>())?;
+    let row_count = reader.read::<u64>()? as usize;
+    let schema = read_option(&mut reader, |reader| reader.read::<TableSchema>())?;
+    let last_id = read_option(&mut reader, |reader| {
+        let id_type = schema
+            .as_ref()
+            .and_then(|s| s.id_type())
+            .ok_or_else(|| reader.error("TableInfo", "last id without a schema"))?;
+        Value::decode(id_type, reader.reader_for_block()?)
+    })?;
+
+    Ok(TableInfo {
+        type_hash,
+        path,
+        file_size,
+        format_version,
+        row_count,
+        schema,
+        last_id,
+    })
+}
+
+/// The largest message that is sent or received, this bounds the size of
+/// the tables that can be used remotely.
+pub const MAX_MESSAGE_LEN: usize = 1 << 30;
+
+fn too_large(len: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("message of {len} bytes exceeds the maximum of {MAX_MESSAGE_LEN} bytes"),
+    )
+}
+
+/// Writes a message that is already encoded as a block.
+pub(crate) fn send(stream: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    if message.len() - 4 > MAX_MESSAGE_LEN {
+        return Err(too_large(message.len() - 4));
+    }
+    stream.write_all(message)?;
+    stream.flush()
+}
+
+/// Reads the content of the next message, `None` if the connection was closed.
+pub(crate) fn receive(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(too_large(len));
+    }
+    // the buffer only grows with the data that actually arrives
+    let mut message = Vec::new();
+    stream.take(len as u64).read_to_end(&mut message)?;
+    if message.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some(message))
+}
+
+#[derive(Debug)]
+enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// A client of a database served by `somedb-server`.
+///
+/// See the [module](self) documentation for how changes are applied.
+#[derive(Debug)]
+pub struct RemoteDatabase {
+    connection: Mutex<Connection>,
+}
+
+impl RemoteDatabase {
+    /// Connects to a server listening on a tcp socket.
+    pub fn connect(addr: impl ToSocketAddrs) -> DbResult<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            connection: Mutex::new(Connection::Tcp(stream)),
+        })
+    }
+
+    /// Connects to a server listening on a unix socket.
+    #[cfg(unix)]
+    pub fn connect_unix(path: impl AsRef<Path>) -> DbResult<Self> {
+        let stream = UnixStream::connect(&path).map_err(DbError::io_at(path.as_ref()))?;
+        Ok(Self {
+            connection: Mutex::new(Connection::Unix(stream)),
+        })
+    }
+
+    fn request(&self, request: &Request) -> DbResult<Response> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+
+        send(&mut *connection, &request.encoded())?;
+        let message = receive(&mut *connection)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the server closed the connection",
+            )
+        })?;
+
+        match Response::decoded(ByteReader::new(&message))? {
+            Response::Error { kind, message } => Err(DbError::Remote { kind, message }),
+            response => Ok(response),
+        }
+    }
+
+    fn unexpected(response: Response) -> DbError {
+        DbError::ParseError(format!("unexpected response from the server: {response:?}"))
+    }
+
+    /// Reads the table of `T` and the fingerprint of its file, `None` if it isn't stored.
+    fn read_table<T: Entity>(&self) -> DbResult<Option<(EntityMeta<T>, Fingerprint)>> {
+        let table = T::type_hash();
+        match self.request(&Request::Read { table })? {
+            Response::Table(None) => Ok(None),
+            Response::Table(Some((schema, data))) => {
+                check_compatible::<T>(schema.as_ref())?;
+                Ok(Some((EntityMeta::load(&data)?, fingerprint(&data))))
+            }
+            response => Err(Self::unexpected(response)),
+        }
+    }
+
+    /// Applies `change` to the table of `T` until the changed table
+    /// was written without a conflicting change of another client.
+    ///
+    /// Tables that aren't stored are created if `create` is set.
+    fn change<T: Entity, R>(
+        &self,
+        create: bool,
+        mut change: impl FnMut(&mut EntityMeta<T>) -> DbResult<R>,
+    ) -> DbResult<R> {
+        loop {
+            let (mut raw, expected) = match self.read_table::<T>()? {
+                Some(table) => table,
+                None if create => (EntityMeta::empty(), fingerprint(&[])),
+                None => return Err(DbError::type_not_found::<T>()),
+            };
+
+            let res = change(&mut raw)?;
+            check_constraints(&raw.entities)?;
+
+            let request = Request::Write {
+                table: T::type_hash(),
+                schema: T::table_schema(),
+                expected,
+                data: raw.encoded(),
+            };
+            match self.request(&request)? {
+                Response::Done(true) => return Ok(res),
+                Response::Done(false) => continue,
+                response => return Err(Self::unexpected(response)),
+            }
+        }
+    }
+
+    pub fn store<T: Entity>(&mut self, data: T) -> DbResult<T> {
+        self.change(true, |raw| raw.insert(data.clone()))
+    }
+
+    pub fn write_all<T: Entity>(&mut self, entities: Vec<T>) -> DbResult<()> {
+        self.change(true, |raw| raw.replace(entities.clone()))
+    }
+
+    pub fn read_all<T: Entity>(&self) -> DbResult<Vec<T>> {
+        match self.read_table::<T>()? {
+            Some((raw, _)) => raw.loaded(),
+            None => Err(DbError::type_not_found::<T>()),
+        }
+    }
+
+    pub fn read_all_ids<T: Entity>(&self) -> DbResult<Vec<T::Id>> {
+        Ok(self.read_all::<T>()?.iter().map(|e| e.get_id()).collect())
+    }
+
+    pub fn find_by_id<T: Entity>(&self, id: T::Id) -> DbResult<Option<T>> {
+        Ok(self.read_all::<T>()?.into_iter().find(|e| e.get_id() == id))
+    }
+
+    pub fn update_entity<T: Entity>(&mut self, entity: T) -> DbResult<()> {
+        self.change(false, |raw| raw.update(entity.clone()))
+    }
+
+    pub fn delete_entity_by_id<T: Entity>(&mut self, id: T::Id) -> DbResult<()> {
+        self.change(false, |raw: &mut EntityMeta<T>| raw.delete(id))
+    }
+
+    pub fn delete_entity_store<T: Entity>(&mut self) -> DbResult<()> {
+        loop {
+            let (raw, expected) = self
+                .read_table::<T>()?
+                .ok_or_else(DbError::type_not_found::<T>)?;
+            for entity in &raw.entities {
+                entity.before_delete().map_err(DbError::validation::<T>)?;
+            }
+
+            let request = Request::Delete {
+                table: T::type_hash(),
+                expected,
+            };
+            match self.request(&request)? {
+                Response::Done(true) => return Ok(()),
+                Response::Done(false) => continue,
+                response => return Err(Self::unexpected(response)),
+            }
+        }
+    }
+
+    /// Lists all tables stored on the server, sorted by name.
+    ///
+    /// The paths of the tables are paths on the server.
+    pub fn catalog(&self) -> DbResult<Vec<TableInfo>> {
+        match self.request(&Request::Catalog)? {
+            Response::Tables(tables) => Ok(tables),
+            response => Err(Self::unexpected(response)),
+        }
+    }
+
+    /// Finds a table by its struct name, full rust type name or encoded type hash.
+    pub fn table_info(&self, name: &str) -> DbResult<Option<TableInfo>> {
+        Ok(self.catalog()?.into_iter().find(|t| t.matches(name)))
+    }
+
+    /// Reads the entities of `T` that match a filter built at runtime.
+    ///
+    /// The filter runs on the server, only the matching entities are sent back.
+    pub fn query_dyn_as<T: Entity>(&self, filter: &Expr) -> DbResult<Vec<T>> {
+        filter.check(&T::table_schema())?;
+
+        let request = Request::Query {
+            table: T::type_hash(),
+            filter: filter.to_string(),
+        };
+        match self.request(&request)? {
+            Response::Table(None) => Err(DbError::type_not_found::<T>()),
+            Response::Table(Some((schema, data))) => {
+                check_compatible::<T>(schema.as_ref())?;
+                EntityMeta::<T>::load(&data)?.loaded()
+            }
+            response => Err(Self::unexpected(response)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn roundtrip_request(request: Request) {
+        let encoded = request.encoded();
+        let mut reader = ByteReader::new(&encoded);
+        assert_eq!(
+            Request::decoded(reader.reader_for_block().unwrap()).unwrap(),
+            request
+        );
+    }
+
+    fn roundtrip_response(response: Response) {
+        let encoded = response.encoded();
+        let mut reader = ByteReader::new(&encoded);
+        assert_eq!(
+            Response::decoded(reader.reader_for_block().unwrap()).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn message_roundtrip() {
+        let table = unsafe { TypeHash::from_str("table") };
+        roundtrip_request(Request::Catalog);
+        roundtrip_request(Request::Read { table });
+        roundtrip_request(Request::Delete {
+            table,
+            expected: fingerprint(b"data"),
+        });
+        roundtrip_request(Request::Query {
+            table,
+            filter: "quantity > 3".into(),
+        });
+
+        roundtrip_response(Response::Table(None));
+        roundtrip_response(Response::Table(Some((None, vec![1, 2, 3]))));
+        roundtrip_response(Response::Done(false));
+        roundtrip_response(Response::error(DbError::IdNotFound {
+            table: "Note".into(),
+            id: "1".into(),
+        }));
+    }
+
+    #[test]
+    fn written_tables_are_checked() {
+        use crate::{
+            db::Database,
+            entity_meta::DynEntityMeta,
+            schema::{Constraint, FieldConstraint, FieldSchema, FieldType},
+        };
+
+        let mut db = Database::new("remote_written_tables_sdb/", true).unwrap();
+        let table = unsafe { TypeHash::from_str("Order") };
+        let schema = TableSchema {
+            name: "Order".into(),
+            type_name: "Order".into(),
+            id_field: "id".into(),
+            generate_id: true,
+            fields: vec![
+                FieldSchema::new("id", FieldType::U32),
+                FieldSchema::new("quantity", FieldType::U32),
+            ],
+            constraints: vec![
+                FieldConstraint::new("quantity", Constraint::Max(Value::U32(100))),
+                FieldConstraint::new("quantity", Constraint::Check("is_even".into())),
+            ],
+        };
+        let table_with = |quantity| {
+            DynEntityMeta {
+                last_id: Value::U32(1),
+                entities: vec![Value::Struct(vec![
+                    ("id".into(), Value::U32(1)),
+                    ("quantity".into(), Value::U32(quantity)),
+                ])],
+            }
+            .encoded(&schema)
+        };
+
+        let err = db
+            .write_table_file(&table, &schema, &fingerprint(&[]), &table_with(500))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Validation);
+
+        // custom checks already ran on the client
+        assert!(
+            db.write_table_file(&table, &schema, &fingerprint(&[]), &table_with(7))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn oversized_messages_are_refused() {
+        let mut stream = &((MAX_MESSAGE_LEN + 1) as u32).to_be_bytes()[..];
+        assert_eq!(
+            receive(&mut stream).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        // a length without the data doesn't allocate the claimed size
+        let mut stream = &(MAX_MESSAGE_LEN as u32).to_be_bytes()[..];
+        assert_eq!(
+            receive(&mut stream).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn unknown_tags_are_decode_errors() {
+        let encoded = bytes(&9u8.encoded());
+        let reader = ByteReader::new(&encoded[4..]);
+        assert_eq!(
+            Request::decoded(reader).unwrap_err().kind(),
+            ErrorKind::Decode
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! Self describing schema information for stored entities.
+//!
+//! The schema of every table is persisted next to its data so
+//! that tools can make sense of a database without having the
+//! Rust types compiled in.
+
+use std::{cmp::Ordering, fmt::Display};
+
+use crate::{
+    byte_reader::ByteReader,
+    db::{DbError, DbResult},
+    dyn_query::compare,
+    entity::Entity,
+    entity_meta::with_len,
+    storable::Storable,
+    type_hash::TypeHash,
+    value::Value,
+};
+
+/// The type of a stored field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    Usize,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Isize,
+    String,
+    List(Box<FieldType>),
+    Struct {
+        name: String,
+        fields: Vec<FieldSchema>,
+    },
+    /// A value encoded with serde. The name is the rust type of the value,
+    /// the content can only be decoded as raw bytes without it.
+    Serde(String),
+    /// An optional value, empty for `None` and a block for `Some`.
+    Option(Box<FieldType>),
+}
+
+impl FieldType {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::U8 => 0,
+            Self::U16 => 1,
+            Self::U32 => 2,
+            Self::U64 => 3,
+            Self::U128 => 4,
+            Self::Usize => 5,
+            Self::I8 => 6,
+            Self::I16 => 7,
+            Self::I32 => 8,
+            Self::I64 => 9,
+            Self::I128 => 10,
+            Self::Isize => 11,
+            Self::String => 12,
+            Self::List(_) => 13,
+            Self::Struct { .. } => 14,
+            Self::Serde(_) => 15,
+            Self::Option(_) => 16,
+        }
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::U8 => write!(f, "u8"),
+            Self::U16 => write!(f, "u16"),
+            Self::U32 => write!(f, "u32"),
+            Self::U64 => write!(f, "u64"),
+            Self::U128 => write!(f, "u128"),
+            Self::Usize => write!(f, "usize"),
+            Self::I8 => write!(f, "i8"),
+            Self::I16 => write!(f, "i16"),
+            Self::I32 => write!(f, "i32"),
+            Self::I64 => write!(f, "i64"),
+            Self::I128 => write!(f, "i128"),
+            Self::Isize => write!(f, "isize"),
+            Self::String => write!(f, "String"),
+            Self::List(inner) => write!(f, "Vec<{inner}>"),
+            Self::Struct { name, .. } => write!(f, "{name}"),
+            Self::Serde(name) => write!(f, "{name}"),
+            Self::Option(inner) => write!(f, "Option<{inner}>"),
+        }
+    }
+}
+
+unsafe impl Storable for FieldType {
+    fn type_hash() -> TypeHash {
+        unsafe { TypeHash::from_str("somedb::FieldType") }
+    }
+
+    fn field_type() -> FieldType {
+        // schemas are never stored as fields of an entity.
+        FieldType::Struct {
+            name: "FieldType".to_string(),
+            fields: vec![],
+        }
+    }
+
+    fn inner_encoded(&self) -> Vec<u8> {
+        let mut res = self.tag().encoded();
+        match self {
+            Self::List(inner) | Self::Option(inner) => res.append(&mut inner.encoded()),
+            Self::Struct { name, fields } => {
+                res.append(&mut name.encoded());
+                res.append(&mut fields.encoded());
+            }
+            Self::Serde(name) => res.append(&mut name.encoded()),
+            _ => {}
+        }
+        res
+    }
+
+    fn decoded(mut reader: ByteReader) -> DbResult<Self> {
+        let offset = reader.position();
+        Ok(match reader.read::<u8>()? {
+            0 => Self::U8,
+            1 => Self::U16,
+            2 => Self::U32,
+            3 => Self::U64,
+            4 => Self::U128,
+            5 => Self::Usize,
+            6 => Self::I8,
+            7 => Self::I16,
+            8 => Self::I32,
+            9 => Self::I64,
+            10 => Self::I128,
+            11 => Self::Isize,
+            12 => Self::String,
+            13 => Self::List(Box::new(reader.read::<Self>()?)),
+            14 => Self::Struct {
+                name: reader.read::<String>()?,
+                fields: reader.read::<Vec<_>>()?,
+            },
+            15 => Self::Serde(reader.read::<String>()?),
+            16 => Self::Option(Box::new(reader.read::<Self>()?)),
+            tag => {
+                return Err(DbError::Decode {
+                    type_name: "FieldType",
+                    offset,
+                    reason: format!("unknown field type tag {tag}"),
+                });
+            }
+        })
+    }
+}
+
+/// A single named field of a struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSchema {
+    pub name: String,
+    pub ty: FieldType,
+}
+
+impl FieldSchema {
+    pub fn new(name: impl Into<String>, ty: FieldType) -> Self {
+        Self {
+            name: name.into(),
+            ty,
+        }
+    }
+}
+
+unsafe impl Storable for FieldSchema {
+    fn type_hash() -> TypeHash {
+        unsafe { TypeHash::from_str("somedb::FieldSchema") }
+    }
+
+    fn field_type() -> FieldType {
+        FieldType::Struct {
+            name: "FieldSchema".to_string(),
+            fields: vec![],
+        }
+    }
+
+    fn inner_encoded(&self) -> Vec<u8> {
+        let mut res = self.name.encoded();
+        res.append(&mut self.ty.encoded());
+        res
+    }
+
+    fn decoded(mut reader: ByteReader) -> DbResult<Self> {
+        Ok(Self {
+            name: reader.read::<String>()?,
+            ty: reader.read::<FieldType>()?,
+        })
+    }
+}
+
+/// A constraint declared with an attribute on a field of an entity.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    /// `#[not_empty]`
+    NotEmpty,
+    /// The `min` of `#[range(min = .., max = ..)]`.
+    Min(Value),
+    /// The `max` of `#[range(min = .., max = ..)]`.
+    Max(Value),
+    /// `#[max_len(..)]`
+    MaxLen(u64),
+    /// `#[unique]`
+    Unique,
+    /// `#[check(function)]` with the path of the function. It can only
+    /// be run with the rust type of the entity.
+    Check(String),
+}
+
+impl Constraint {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::NotEmpty => 0,
+            Self::Min(_) => 1,
+            Self::Max(_) => 2,
+            Self::MaxLen(_) => 3,
+            Self::Unique => 4,
+            Self::Check(_) => 5,
+        }
+    }
+}
+
+/// A [Constraint] on a named field, persisted with the [TableSchema] so that
+/// writes without the rust type can be checked as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldConstraint {
+    pub field: String,
+    pub constraint: Constraint,
+}
+
+impl FieldConstraint {
+    pub fn new(field: impl Into<String>, constraint: Constraint) -> Self {
+        Self {
+            field: field.into(),
+            constraint,
+        }
+    }
+
+    /// Checks the value of the field, returns the problem if it is invalid.
+    ///
+    /// [Unique](Constraint::Unique) needs all rows of the table and is not checked here.
+    /// `None` of an optional field is always valid.
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        if *value == Value::Null {
+            return Ok(());
+        }
+        let len = || match value {
+            Value::String(s) => Ok(s.len()),
+            Value::List(values) => Ok(values.len()),
+            _ => Err(format!("has no length that can be checked for {value}")),
+        };
+        match &self.constraint {
+            Constraint::NotEmpty if len()? == 0 => Err("must not be empty".to_string()),
+            Constraint::Min(min) if compare(value, min).is_none_or(Ordering::is_lt) => {
+                Err(format!("must be at least {min}"))
+            }
+            Constraint::Max(max) if compare(value, max).is_none_or(Ordering::is_gt) => {
+                Err(format!("must be at most {max}"))
+            }
+            Constraint::MaxLen(max) if len()? as u64 > *max => {
+                Err(format!("must not be longer than {max}"))
+            }
+            Constraint::Check(check) => Err(format!(
+                "has the check {check} which can only run with the rust type"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn encoded(&self) -> Vec<u8> {
+        let mut res = self.field.encoded();
+        res.append(&mut self.constraint.tag().encoded());
+        match &self.constraint {
+            Constraint::Min(bound) | Constraint::Max(bound) => res.append(&mut bound.encoded()),
+            Constraint::MaxLen(max) => res.append(&mut max.encoded()),
+            Constraint::Check(check) => res.append(&mut check.encoded()),
+            Constraint::NotEmpty | Constraint::Unique => {}
+        }
+        with_len(res)
+    }
+
+    /// Decodes a constraint, the bounds of ranges are decoded with the type of their field.
+    fn decoded(fields: &[FieldSchema], mut reader: ByteReader) -> DbResult<Self> {
+        let field = reader.read::<String>()?;
+        let offset = reader.position();
+        let bound = |reader: &mut ByteReader| {
+            let ty = &fields
+                .iter()
+                .find(|f| f.name == field)
+                .ok_or_else(|| reader.error("Constraint", format!("unknown field {field}")))?
+                .ty;
+            Value::decode(ty, reader.reader_for_block()?)
+        };
+        let constraint = match reader.read::<u8>()? {
+            0 => Constraint::NotEmpty,
+            1 => Constraint::Min(bound(&mut reader)?),
+            2 => Constraint::Max(bound(&mut reader)?),
+            3 => Constraint::MaxLen(reader.read::<u64>()?),
+            4 => Constraint::Unique,
+            5 => Constraint::Check(reader.read::<String>()?),
+            tag => {
+                return Err(DbError::Decode {
+                    type_name: "Constraint",
+                    offset,
+                    reason: format!("unknown constraint tag {tag}"),
+                });
+            }
+        };
+        Ok(Self { field, constraint })
+    }
+}
+
+/// The schema of an entity table as it is persisted in the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSchema {
+    /// The name of the entity struct.
+    pub name: String,
+    /// The fully qualified rust type name of the entity.
+    pub type_name: String,
+    /// The name of the field used as the id.
+    pub id_field: String,
+    pub generate_id: bool,
+    pub fields: Vec<FieldSchema>,
+    pub constraints: Vec<FieldConstraint>,
+}
+
+impl TableSchema {
+    pub fn of<T: Entity>() -> Self {
+        let FieldType::Struct { name, fields } = T::field_type() else {
+            panic!("entities must be structs");
+        };
+
+        Self {
+            name,
+            type_name: std::any::type_name::<T>().to_string(),
+            id_field: T::ID_FIELD.to_string(),
+            generate_id: T::GENERATE_ID,
+            fields,
+            constraints: T::field_constraints(),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldSchema> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    pub fn id_type(&self) -> Option<&FieldType> {
+        self.field(&self.id_field).map(|f| &f.ty)
+    }
+
+    /// Compares the schema with the schema the stored data was written with.
+    pub fn compatibility(&self, stored: &TableSchema) -> Compatibility {
+        if self.fields == stored.fields {
+            Compatibility::Identical
+        } else if self.fields.starts_with(&stored.fields) {
+            Compatibility::Extension
+        } else {
+            Compatibility::Breaking
+        }
+    }
+
+    /// The type of a whole row of this table.
+    pub fn row_type(&self) -> FieldType {
+        FieldType::Struct {
+            name: self.name.clone(),
+            fields: self.fields.clone(),
+        }
+    }
+
+    /// Makes the id field optional to read rows that get a generated id.
+    pub(crate) fn with_optional_id(mut self) -> Self {
+        for field in &mut self.fields {
+            if field.name == self.id_field {
+                field.ty = FieldType::Option(Box::new(field.ty.clone()));
+            }
+        }
+        self
+    }
+}
+
+/// How the schema of an entity relates to the schema of its stored data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    Identical,
+    /// Fields with default values were appended, the stored
+    /// data can still be read.
+    Extension,
+    /// The stored data can't be read with the current schema.
+    Breaking,
+}
+
+unsafe impl Storable for TableSchema {
+    fn type_hash() -> TypeHash {
+        unsafe { TypeHash::from_str("somedb::TableSchema") }
+    }
+
+    fn field_type() -> FieldType {
+        FieldType::Struct {
+            name: "TableSchema".to_string(),
+            fields: vec![],
+        }
+    }
+
+    fn inner_encoded(&self) -> Vec<u8> {
+        let mut res = self.name.encoded();
+        res.append(&mut self.type_name.encoded());
+        res.append(&mut self.id_field.encoded());
+        res.append(&mut (self.generate_id as u8).encoded());
+        res.append(&mut self.fields.encoded());
+        res.append(&mut with_len(
+            self.constraints.iter().flat_map(|c| c.encoded()).collect(),
+        ));
+        res
+    }
+
+    fn decoded(mut reader: ByteReader) -> DbResult<Self> {
+        let name = reader.read::<String>()?;
+        let type_name = reader.read::<String>()?;
+        let id_field = reader.read::<String>()?;
+        let generate_id = reader.read::<u8>()? != 0;
+        let fields = reader.read::<Vec<FieldSchema>>()?;
+
+        // schemas persisted before constraints were added end with the fields
+        let mut constraints = vec![];
+        if !reader.is_at_end() {
+            let mut block = reader.reader_for_block()?;
+            while !block.is_at_end() {
+                constraints.push(FieldConstraint::decoded(
+                    &fields,
+                    block.reader_for_block()?,
+                )?);
+            }
+        }
+
+        Ok(Self {
+            name,
+            type_name,
+            id_field,
+            generate_id,
+            fields,
+            constraints,
+        })
+    }
+}
@@ -0,0 +1,633 @@
+//! Storing types that implement serde's `Serialize` and `Deserialize`.
+//!
+//! Values are encoded with the same length prefixed blocks as [Storable]
+//! types: numbers are big endian, strings are utf-8 and every element of a
+//! sequence, tuple, map or struct is a block of its own. A struct with only
+//! storable fields therefore has the same encoding whether it derives
+//! [Storable] or serde's traits. Options are empty for `None` and a block
+//! for `Some`, enums start with a block holding the `u32` index of the variant.
+//!
+//! The encoding is not self describing, so values can only be decoded with
+//! the type they were encoded with. Fields that are skipped conditionally
+//! (`#[serde(skip_serializing_if = ..)]`) are not supported, but trailing
+//! fields with `#[serde(default)]` can be added to a type later on.
+//!
+//! Use [Serde] to store a serde type where a [Storable] is expected, or mark
+//! a field of an entity with `#[storable(with = "serde")]`:
+//!
+//! ```rust
+//! use somedb::entity;
+//! # #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+//! # struct Address { street: String, zip: Option<u32> }
+//!
+//! #[entity]
+//! struct Customer {
+//!     #[entity_id(auto_generate)]
+//!     id: u32,
+//!     #[storable(with = "serde")]
+//!     address: Address,
+//! }
+//! ```
+//!
+//! Serde types can only be stored as fields: [Serde] implements [Storable]
+//! but not [Entity](crate::entity::Entity), which needs an id and the query
+//! accessors generated by the `entity` macro. A type from another crate
+//! gets a table of its own by wrapping it in an entity with an id field,
+//! like `Customer` above.
+
+use std::{
+    any::type_name,
+    fmt::Display,
+    ops::{Deref, DerefMut},
+};
+
+use ::serde::{
+    Serialize,
+    de::{self, DeserializeOwned, IntoDeserializer, Visitor},
+    ser,
+};
+
+use crate::{
+    byte_reader::ByteReader,
+    db::{DbError, DbResult},
+    schema::FieldType,
+    storable::Storable,
+    type_hash::TypeHash,
+};
+
+/// Stores a value using its serde implementations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Serde<T>(pub T);
+
+impl<T> Deref for Serde<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Serde<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for Serde<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+unsafe impl<T: Serialize + DeserializeOwned + Clone> Storable for Serde<T> {
+    fn type_hash() -> TypeHash {
+        unsafe { TypeHash::from_str(type_name::<T>()) }
+    }
+
+    fn field_type() -> FieldType {
+        FieldType::Serde(type_name::<T>().to_string())
+    }
+
+    fn inner_encoded(&self) -> Vec<u8> {
+        inner_encoded(&self.0)
+    }
+
+    fn decoded(reader: ByteReader) -> DbResult<Self> {
+        decoded(reader).map(Self)
+    }
+}
+
+/// Encodes a value as a length prefixed block like [Storable::encoded].
+///
+/// # Panics
+/// If the `Serialize` implementation of the value fails.
+pub fn encoded<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+    let mut encoder = Encoder { out: vec![] };
+    encoder
+        .block(value)
+        .unwrap_or_else(|e| panic!("failed to serialize {}: {}", type_name::<T>(), e.reason));
+    encoder.out
+}
+
+fn inner_encoded<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+    let mut encoder = Encoder { out: vec![] };
+    value
+        .serialize(&mut encoder)
+        .unwrap_or_else(|e| panic!("failed to serialize {}: {}", type_name::<T>(), e.reason));
+    encoder.out
+}
+
+/// Decodes a value from the content of a block like [Storable::decoded].
+pub fn decoded<T: DeserializeOwned>(reader: ByteReader) -> DbResult<T> {
+    let mut decoder = Decoder { reader };
+    T::deserialize(&mut decoder).map_err(|e| DbError::Decode {
+        type_name: type_name::<T>(),
+        offset: e.offset.unwrap_or(decoder.reader.position()),
+        reason: e.reason,
+    })
+}
+
+/// An error of the serde encoder or decoder.
+#[derive(Debug)]
+struct Error {
+    /// The offset of decode errors that are caused by the data
+    /// instead of the `Deserialize` implementation.
+    offset: Option<usize>,
+    reason: String,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self {
+            offset: None,
+            reason: msg.to_string(),
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self {
+            offset: None,
+            reason: msg.to_string(),
+        }
+    }
+}
+
+impl From<DbError> for Error {
+    fn from(e: DbError) -> Self {
+        match e {
+            DbError::Decode { offset, reason, .. } => Self {
+                offset: Some(offset),
+                reason,
+            },
+            e => Self {
+                offset: None,
+                reason: e.to_string(),
+            },
+        }
+    }
+}
+
+struct Encoder {
+    out: Vec<u8>,
+}
+
+impl Encoder {
+    /// Writes a value as a length prefixed block.
+    fn block<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let start = self.out.len();
+        self.out.extend_from_slice(&[0; 4]);
+        value.serialize(&mut *self)?;
+        let len = (self.out.len() - start - 4) as u32;
+        self.out[start..start + 4].copy_from_slice(&len.to_be_bytes());
+        Ok(())
+    }
+}
+
+macro_rules! serialize_numbers {
+    ($($name:ident: $ty:ty),*) => {
+        $(fn $name(self, v: $ty) -> Result<(), Error> {
+            self.out.extend_from_slice(&v.to_be_bytes());
+            Ok(())
+        })*
+    };
+}
+
+impl ser::Serializer for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    serialize_numbers!(
+        serialize_u8: u8, serialize_u16: u16, serialize_u32: u32, serialize_u64: u64,
+        serialize_u128: u128, serialize_i8: i8, serialize_i16: i16, serialize_i32: i32,
+        serialize_i64: i64, serialize_i128: i128
+    );
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.serialize_u8(v as u8)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_u32(v.to_bits())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.serialize_u64(v.to_bits())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.out.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        self.block(value)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Error> {
+        self.block(&variant_index)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.block(&variant_index)?;
+        self.block(value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, Error> {
+        self.block(&variant_index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, Error> {
+        self.block(&variant_index)?;
+        Ok(self)
+    }
+}
+
+macro_rules! serialize_elements {
+    ($($tr:ident::$method:ident),*) => {
+        $(impl ser::$tr for &mut Encoder {
+            type Ok = ();
+            type Error = Error;
+
+            fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+                self.block(value)
+            }
+
+            fn end(self) -> Result<(), Error> {
+                Ok(())
+            }
+        })*
+    };
+}
+
+serialize_elements!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+impl ser::SerializeMap for &mut Encoder {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.block(key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.block(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+macro_rules! serialize_fields {
+    ($($tr:ident),*) => {
+        $(impl ser::$tr for &mut Encoder {
+            type Ok = ();
+            type Error = Error;
+
+            fn serialize_field<T: Serialize + ?Sized>(
+                &mut self,
+                _key: &'static str,
+                value: &T,
+            ) -> Result<(), Error> {
+                self.block(value)
+            }
+
+            fn end(self) -> Result<(), Error> {
+                Ok(())
+            }
+        })*
+    };
+}
+
+serialize_fields!(SerializeStruct, SerializeStructVariant);
+
+/// Decodes a single value from the content of its block.
+struct Decoder<'de> {
+    reader: ByteReader<'de>,
+}
+
+impl<'de> Decoder<'de> {
+    fn block(&mut self) -> Result<Decoder<'de>, Error> {
+        Ok(Decoder {
+            reader: self.reader.reader_for_block()?,
+        })
+    }
+
+    fn number<T: Storable>(&self) -> Result<T, Error> {
+        Ok(T::decoded(self.reader.clone())?)
+    }
+
+    fn error(&self, reason: impl Into<String>) -> Error {
+        Error {
+            offset: Some(self.reader.position()),
+            reason: reason.into(),
+        }
+    }
+}
+
+macro_rules! deserialize_numbers {
+    ($($name:ident: $ty:ty => $visit:ident),*) => {
+        $(fn $name<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.$visit(self.number::<$ty>()?)
+        })*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Decoder<'de> {
+    type Error = Error;
+
+    deserialize_numbers!(
+        deserialize_u8: u8 => visit_u8, deserialize_u16: u16 => visit_u16,
+        deserialize_u32: u32 => visit_u32, deserialize_u64: u64 => visit_u64,
+        deserialize_u128: u128 => visit_u128, deserialize_i8: i8 => visit_i8,
+        deserialize_i16: i16 => visit_i16, deserialize_i32: i32 => visit_i32,
+        deserialize_i64: i64 => visit_i64, deserialize_i128: i128 => visit_i128
+    );
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(self.error("the serde encoding is not self describing"))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.number::<u8>()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            v => Err(self.error(format!("invalid bool {v}"))),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f32(f32::from_bits(self.number()?))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f64(f64::from_bits(self.number()?))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let v = self.number::<u32>()?;
+        visitor
+            .visit_char(char::from_u32(v).ok_or_else(|| self.error(format!("invalid char {v}")))?)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let bytes = self.reader.read_byte_slice();
+        visitor.visit_borrowed_str(
+            std::str::from_utf8(bytes).map_err(|e| self.error(format!("invalid utf-8: {e}")))?,
+        )
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.reader.read_byte_slice())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.reader.is_at_end() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(&mut self.block()?)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // the value is confined to its block, so it doesn't need to be read
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for Decoder<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.reader.is_at_end() {
+            return Ok(None);
+        }
+        seed.deserialize(&mut self.block()?).map(Some)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Decoder<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        de::SeqAccess::next_element_seed(self, seed)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut self.block()?)
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Decoder<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let index = self.block()?.number::<u32>()?;
+        let variant = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Decoder<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(&mut self.block()?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(self)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_seq(self)
+    }
+}
@@ -0,0 +1,80 @@
+//! Serves a [Database] to [RemoteDatabase](crate::remote::RemoteDatabase) clients.
+//!
+//! Every connection is handled on its own thread, the requests of all
+//! connections are executed one at a time. This is what the `somedb-server`
+//! binary runs, but a server can also be embedded in an application.
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+
+use crate::{
+    byte_reader::ByteReader,
+    db::{Database, DbResult},
+    dyn_query::Expr,
+    remote::{Request, Response, receive, send},
+};
+
+/// Serves the database to the clients connecting to a tcp socket.
+///
+/// This only returns if accepting connections fails.
+pub fn serve_tcp(db: Database, listener: TcpListener) -> io::Result<()> {
+    serve(db, listener.incoming())
+}
+
+/// Serves the database to the clients connecting to a unix socket.
+///
+/// This only returns if accepting connections fails.
+#[cfg(unix)]
+pub fn serve_unix(db: Database, listener: UnixListener) -> io::Result<()> {
+    serve(db, listener.incoming())
+}
+
+fn serve<S: Read + Write + Send + 'static>(
+    db: Database,
+    incoming: impl Iterator<Item = io::Result<S>>,
+) -> io::Result<()> {
+    let db = Arc::new(Mutex::new(db));
+    for stream in incoming {
+        let stream = stream?;
+        let db = db.clone();
+        // the client notices when its connection fails
+        thread::spawn(move || handle_connection(&db, stream));
+    }
+    Ok(())
+}
+
+fn handle_connection(db: &Mutex<Database>, mut stream: impl Read + Write) -> io::Result<()> {
+    while let Some(message) = receive(&mut stream)? {
+        let response = Request::decoded(ByteReader::new(&message))
+            .and_then(|request| execute(&mut db.lock().unwrap_or_else(|e| e.into_inner()), request))
+            .unwrap_or_else(Response::error);
+        send(&mut stream, &response.encoded())?;
+    }
+    Ok(())
+}
+
+fn execute(db: &mut Database, request: Request) -> DbResult<Response> {
+    Ok(match request {
+        Request::Catalog => Response::Tables(db.catalog()?),
+        Request::Read { table } => Response::Table(db.read_table_file(&table)?),
+        Request::Write {
+            table,
+            schema,
+            expected,
+            data,
+        } => Response::Done(db.write_table_file(&table, &schema, &expected, &data)?),
+        Request::Delete { table, expected } => {
+            Response::Done(db.delete_table_file(&table, &expected)?)
+        }
+        Request::Query { table, filter } => {
+            Response::Table(db.query_table_file(&table, &Expr::parse(&filter)?)?)
+        }
+    })
+}
@@ -0,0 +1,461 @@
+//! A small sql dialect over the stored tables.
+//!
+//! ```sql
+//! SELECT * | <column>, ... FROM <table> [WHERE <expr>] [ORDER BY <column> [ASC | DESC], ...] [LIMIT <n>]
+//! INSERT INTO <table> [(<column>, ...)] VALUES (<value>, ...), ...
+//! UPDATE <table> SET <column> = <value>, ... [WHERE <expr>]
+//! DELETE FROM <table> [WHERE <expr>]
+//! ```
+//!
+//! Statements only use the persisted table schemas, so they work without the
+//! rust types of the tables. `WHERE` takes a [dynamic expression](Expr).
+//! Lists, structs and serde values are written as text in the same format
+//! as in csv files, `NULL` sets an optional field to `None` and `IS NULL`
+//! finds it. Hooks of the entities are not run since they need the rust
+//! types. The field constraints persisted with the schema are checked,
+//! tables with a `#[check(function)]` constraint can't be written.
+
+use std::cmp::Ordering;
+
+use crate::{
+    catalog::TableInfo,
+    db::{Database, DbError, DbResult},
+    dyn_query::{Expr, Parser, Row, compare},
+    format::from_text,
+    schema::{FieldSchema, FieldType, TableSchema},
+    value::Value,
+};
+
+/// A parsed sql statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Select {
+        /// The selected columns, all columns if it is empty.
+        columns: Vec<String>,
+        table: String,
+        filter: Option<Expr>,
+        /// The columns to sort by and whether they are sorted in descending order.
+        order_by: Vec<(String, bool)>,
+        limit: Option<usize>,
+    },
+    Insert {
+        table: String,
+        /// The columns the values are for, all columns if it is empty.
+        columns: Vec<String>,
+        rows: Vec<Vec<Value>>,
+    },
+    Update {
+        table: String,
+        set: Vec<(String, Value)>,
+        filter: Option<Expr>,
+    },
+    Delete {
+        table: String,
+        filter: Option<Expr>,
+    },
+}
+
+/// The result of [executing](Database::execute) a statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlOutput {
+    /// The rows returned by `SELECT`.
+    Rows(Vec<Row>),
+    /// The number of inserted, updated or deleted rows.
+    Changed(usize),
+}
+
+impl Statement {
+    pub fn parse(src: &str) -> DbResult<Self> {
+        let mut parser = Parser::new(src)?;
+        let statement = if parser.keyword("SELECT") {
+            parse_select(&mut parser)?
+        } else if parser.keyword("INSERT") {
+            parse_insert(&mut parser)?
+        } else if parser.keyword("UPDATE") {
+            parse_update(&mut parser)?
+        } else if parser.keyword("DELETE") {
+            parser.expect_keyword("FROM")?;
+            Self::Delete {
+                table: parser.ident()?,
+                filter: parse_where(&mut parser)?,
+            }
+        } else {
+            return parser.unexpected("SELECT, INSERT, UPDATE or DELETE");
+        };
+        parser.finish()?;
+        Ok(statement)
+    }
+
+    /// Runs the statement against the tables of `db`.
+    pub fn execute(&self, db: &mut Database) -> DbResult<SqlOutput> {
+        match self {
+            Self::Select {
+                columns,
+                table,
+                filter,
+                order_by,
+                limit,
+            } => {
+                let (info, schema) = find_table(db, table)?;
+                for column in columns.iter().chain(order_by.iter().map(|(c, _)| c)) {
+                    field(&schema, column)?;
+                }
+                check_filter(filter, &schema)?;
+
+                let mut rows: Vec<_> = db
+                    .raw_read_dyn(&info)?
+                    .entities
+                    .into_iter()
+                    .filter(|row| matches(filter, row))
+                    .collect();
+                rows.sort_by(|a, b| {
+                    order_by
+                        .iter()
+                        .map(|(column, descending)| {
+                            let ord = match (a.field(column), b.field(column)) {
+                                (Some(a), Some(b)) => compare(a, b).unwrap_or(Ordering::Equal),
+                                _ => Ordering::Equal,
+                            };
+                            if *descending { ord.reverse() } else { ord }
+                        })
+                        .find(|ord| ord.is_ne())
+                        .unwrap_or(Ordering::Equal)
+                });
+                rows.truncate(limit.unwrap_or(usize::MAX));
+
+                Ok(SqlOutput::Rows(
+                    rows.into_iter()
+                        .map(|row| {
+                            if columns.is_empty() {
+                                return Row::from(row);
+                            }
+                            Row {
+                                columns: columns
+                                    .iter()
+                                    .map(|c| (c.clone(), row.field(c).cloned().unwrap()))
+                                    .collect(),
+                            }
+                        })
+                        .collect(),
+                ))
+            }
+            Self::Insert {
+                table,
+                columns,
+                rows,
+            } => {
+                let (info, schema) = find_table(db, table)?;
+                let columns = if columns.is_empty() {
+                    schema.fields.iter().map(|f| f.name.clone()).collect()
+                } else {
+                    columns.clone()
+                };
+                for column in &columns {
+                    field(&schema, column)?;
+                }
+
+                let mut raw = db.raw_read_dyn(&info)?;
+                for values in rows {
+                    if values.len() != columns.len() {
+                        return Err(DbError::ParseError(format!(
+                            "expected {} values, found {}",
+                            columns.len(),
+                            values.len()
+                        )));
+                    }
+
+                    let mut row = Vec::new();
+                    for f in &schema.fields {
+                        let value = match columns.iter().position(|c| *c == f.name) {
+                            Some(i) => convert(f, &values[i])?,
+                            None if f.name == schema.id_field && schema.generate_id => {
+                                raw.last_id.next_id().ok_or_else(|| {
+                                    DbError::ParseError("can't generate a new id".into())
+                                })?
+                            }
+                            None => {
+                                return Err(DbError::ParseError(format!(
+                                    "missing column: {}",
+                                    f.name
+                                )));
+                            }
+                        };
+                        row.push((f.name.clone(), value));
+                    }
+                    let row = Value::Struct(row);
+
+                    let id = row.field(&schema.id_field).unwrap();
+                    if raw
+                        .entities
+                        .iter()
+                        .any(|e| e.field(&schema.id_field) == Some(id))
+                    {
+                        return Err(DbError::IdExists {
+                            table: schema.name.clone(),
+                            id: id.to_string(),
+                        });
+                    }
+                    // explicit ids must not be generated again later on
+                    if compare(id, &raw.last_id) == Some(Ordering::Greater) {
+                        raw.last_id = id.clone();
+                    }
+                    raw.entities.push(row);
+                }
+
+                db.raw_write_dyn(&info, raw)?;
+                Ok(SqlOutput::Changed(rows.len()))
+            }
+            Self::Update { table, set, filter } => {
+                let (info, schema) = find_table(db, table)?;
+                let set = set
+                    .iter()
+                    .map(|(column, value)| {
+                        if *column == schema.id_field {
+                            return Err(DbError::ParseError(format!(
+                                "the id column {column} can't be changed"
+                            )));
+                        }
+                        Ok((column, convert(field(&schema, column)?, value)?))
+                    })
+                    .collect::<DbResult<Vec<_>>>()?;
+                check_filter(filter, &schema)?;
+
+                let mut raw = db.raw_read_dyn(&info)?;
+                let mut changed = 0;
+                for row in raw.entities.iter_mut().filter(|row| matches(filter, row)) {
+                    for (column, value) in &set {
+                        row.set_field(column, value.clone());
+                    }
+                    changed += 1;
+                }
+
+                if changed > 0 {
+                    db.raw_write_dyn(&info, raw)?;
+                }
+                Ok(SqlOutput::Changed(changed))
+            }
+            Self::Delete { table, filter } => {
+                let (info, schema) = find_table(db, table)?;
+                check_filter(filter, &schema)?;
+
+                let mut raw = db.raw_read_dyn(&info)?;
+                let count = raw.entities.len();
+                raw.entities.retain(|row| !matches(filter, row));
+                let changed = count - raw.entities.len();
+
+                if changed > 0 {
+                    db.raw_write_dyn(&info, raw)?;
+                }
+                Ok(SqlOutput::Changed(changed))
+            }
+        }
+    }
+}
+
+fn parse_select(parser: &mut Parser) -> DbResult<Statement> {
+    let columns = if parser.symbol("*") {
+        vec![]
+    } else {
+        parse_list(parser, Parser::ident)?
+    };
+    parser.expect_keyword("FROM")?;
+    let table = parser.ident()?;
+    let filter = parse_where(parser)?;
+
+    let mut order_by = vec![];
+    if parser.keyword("ORDER") {
+        parser.expect_keyword("BY")?;
+        order_by = parse_list(parser, |parser| {
+            let column = parser.ident()?;
+            let descending = parser.keyword("DESC");
+            if !descending {
+                parser.keyword("ASC");
+            }
+            Ok((column, descending))
+        })?;
+    }
+
+    let mut limit = None;
+    if parser.keyword("LIMIT") {
+        limit = Some(match parser.value()? {
+            Value::I64(n) if n >= 0 => n as usize,
+            n => return Err(DbError::ParseError(format!("invalid limit: {n}"))),
+        });
+    }
+
+    Ok(Statement::Select {
+        columns,
+        table,
+        filter,
+        order_by,
+        limit,
+    })
+}
+
+fn parse_insert(parser: &mut Parser) -> DbResult<Statement> {
+    parser.expect_keyword("INTO")?;
+    let table = parser.ident()?;
+
+    let mut columns = vec![];
+    if parser.symbol("(") {
+        columns = parse_list(parser, Parser::ident)?;
+        parser.expect_symbol(")")?;
+    }
+
+    parser.expect_keyword("VALUES")?;
+    let rows = parse_list(parser, |parser| {
+        parser.expect_symbol("(")?;
+        let values = parse_list(parser, Parser::value)?;
+        parser.expect_symbol(")")?;
+        Ok(values)
+    })?;
+
+    Ok(Statement::Insert {
+        table,
+        columns,
+        rows,
+    })
+}
+
+fn parse_update(parser: &mut Parser) -> DbResult<Statement> {
+    let table = parser.ident()?;
+    parser.expect_keyword("SET")?;
+    let set = parse_list(parser, |parser| {
+        let column = parser.ident()?;
+        parser.expect_symbol("=")?;
+        Ok((column, parser.value()?))
+    })?;
+
+    Ok(Statement::Update {
+        table,
+        set,
+        filter: parse_where(parser)?,
+    })
+}
+
+fn parse_where(parser: &mut Parser) -> DbResult<Option<Expr>> {
+    if parser.keyword("WHERE") {
+        return Ok(Some(parser.expr()?));
+    }
+    Ok(None)
+}
+
+/// Parses items separated by commas.
+fn parse_list<T>(
+    parser: &mut Parser,
+    mut item: impl FnMut(&mut Parser) -> DbResult<T>,
+) -> DbResult<Vec<T>> {
+    let mut items = vec![item(parser)?];
+    while parser.symbol(",") {
+        items.push(item(parser)?);
+    }
+    Ok(items)
+}
+
+fn find_table(db: &Database, name: &str) -> DbResult<(TableInfo, TableSchema)> {
+    let info = db.table_info(name)?.ok_or_else(|| DbError::TypeNotFound {
+        table: name.to_string(),
+    })?;
+    let schema = info
+        .schema
+        .clone()
+        .ok_or_else(|| DbError::SchemaNotFound { table: info.name() })?;
+    Ok((info, schema))
+}
+
+fn field<'a>(schema: &'a TableSchema, name: &str) -> DbResult<&'a FieldSchema> {
+    schema
+        .field(name)
+        .ok_or_else(|| DbError::ParseError(format!("unknown field: {name}")))
+}
+
+fn check_filter(filter: &Option<Expr>, schema: &TableSchema) -> DbResult<()> {
+    filter.as_ref().map_or(Ok(()), |f| f.check(schema))
+}
+
+fn matches(filter: &Option<Expr>, row: &Value) -> bool {
+    filter.as_ref().is_none_or(|f| f.matches(row))
+}
+
+/// Converts a parsed constant to the type of a field.
+fn convert(field: &FieldSchema, value: &Value) -> DbResult<Value> {
+    match (&field.ty, value) {
+        (FieldType::Option(_), Value::Null) => Ok(Value::Null),
+        (FieldType::Option(inner), _) => convert(
+            &FieldSchema::new(field.name.clone(), (**inner).clone()),
+            value,
+        ),
+        (FieldType::String, Value::String(_)) => Ok(value.clone()),
+        (FieldType::List(_) | FieldType::Struct { .. } | FieldType::Serde(_), Value::String(s)) => {
+            from_text(&field.ty, s)
+        }
+        (FieldType::String, _) | (_, Value::String(_) | Value::Null) => Err(DbError::ParseError(
+            format!("expected {} for {}, found {value}", field.ty, field.name),
+        )),
+        (ty, number) => from_text(ty, &number.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Statement;
+    use crate::{dyn_query::Expr, value::Value};
+
+    #[test]
+    fn parse_statements() {
+        assert_eq!(
+            Statement::parse(
+                "select name, age from Person where age > 3 order by age desc, name limit 2;"
+            )
+            .unwrap(),
+            Statement::Select {
+                columns: vec!["name".into(), "age".into()],
+                table: "Person".into(),
+                filter: Some(Expr::field("age").gt(Value::I64(3))),
+                order_by: vec![("age".into(), true), ("name".into(), false)],
+                limit: Some(2),
+            }
+        );
+        assert_eq!(
+            Statement::parse("INSERT INTO \"app::Person\" VALUES (1, 'a'), (2, 'b')").unwrap(),
+            Statement::Insert {
+                table: "app::Person".into(),
+                columns: vec![],
+                rows: vec![
+                    vec![Value::I64(1), Value::String("a".into())],
+                    vec![Value::I64(2), Value::String("b".into())],
+                ],
+            }
+        );
+        assert_eq!(
+            Statement::parse("UPDATE Person SET age = -1, name = 'x'").unwrap(),
+            Statement::Update {
+                table: "Person".into(),
+                set: vec![
+                    ("age".into(), Value::I64(-1)),
+                    ("name".into(), Value::String("x".into()))
+                ],
+                filter: None,
+            }
+        );
+        assert_eq!(
+            Statement::parse("DELETE FROM Person WHERE id IN (1, 2)").unwrap(),
+            Statement::Delete {
+                table: "Person".into(),
+                filter: Some(Expr::field("id").in_list([Value::I64(1), Value::I64(2)])),
+            }
+        );
+
+        for src in [
+            "SELECT FROM Person",
+            "SELECT * Person",
+            "SELECT * FROM Person LIMIT -1",
+            "INSERT INTO Person VALUES ()",
+            "UPDATE Person SET age = age + 1",
+            "DROP TABLE Person",
+            "DELETE FROM Person WHERE",
+        ] {
+            assert!(Statement::parse(src).is_err(), "{src}");
+        }
+    }
+}
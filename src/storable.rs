@@ -1,4 +1,4 @@
-use crate::{byte_reader::ByteReader, db::DbResult, type_hash::TypeHash};
+use crate::{byte_reader::ByteReader, db::DbResult, schema::FieldType, type_hash::TypeHash};
 
 /// Represents a storable data type
 ///
@@ -6,6 +6,8 @@ use crate::{byte_reader::ByteReader, db::DbResult, type_hash::TypeHash};
 /// This should only be implemented via the "entity" macro.
 pub unsafe trait Storable: Sized + Clone {
     fn type_hash() -> TypeHash;
+    /// Describes how this type is stored so it can be decoded without the rust type.
+    fn field_type() -> FieldType;
     fn encoded(&self) -> Vec<u8> {
         let mut enc = self.inner_encoded();
         let mut vec = Vec::from((enc.len() as u32).to_be_bytes());
@@ -19,25 +21,29 @@ pub unsafe trait Storable: Sized + Clone {
 }
 
 macro_rules! impl_all_storable_number {
-    ($($ty:ident),*) => {
-        $(impl_storable_number!($ty);)*
+    ($($ty:ident => $field_type:ident),*) => {
+        $(impl_storable_number!($ty, $field_type);)*
     }
 }
 
 macro_rules! impl_storable_number {
-    ($ty:ident) => {
+    ($ty:ident, $field_type:ident) => {
         unsafe impl Storable for $ty {
             fn type_hash() -> TypeHash {
                 unsafe { TypeHash::from_str(stringify!($ty)) }
             }
 
+            fn field_type() -> FieldType {
+                FieldType::$field_type
+            }
+
             fn inner_encoded(&self) -> Vec<u8> {
                 Vec::from(self.to_be_bytes())
             }
 
             fn decoded(reader: ByteReader) -> DbResult<Self> {
                 Ok(Self::from_be_bytes(
-                    reader.read_byte_slice().try_into().unwrap(),
+                    reader.read_exact(std::any::type_name::<Self>())?,
                 ))
             }
         }
@@ -45,18 +51,28 @@ macro_rules! impl_storable_number {
 }
 
 impl_all_storable_number!(
-    u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, usize, isize
+    u8 => U8, u16 => U16, u32 => U32, u64 => U64, u128 => U128,
+    i8 => I8, i16 => I16, i32 => I32, i64 => I64, i128 => I128,
+    usize => Usize, isize => Isize
 );
 
 unsafe impl Storable for String {
     fn type_hash() -> TypeHash {
         unsafe { TypeHash::from_str("String") }
     }
+    fn field_type() -> FieldType {
+        FieldType::String
+    }
     fn inner_encoded(&self) -> Vec<u8> {
         Vec::from(self.as_bytes())
     }
     fn decoded(reader: ByteReader) -> DbResult<Self> {
-        Ok(String::from_utf8(reader.read_byte_slice().to_vec()).unwrap())
+        String::from_utf8(reader.read_byte_slice().to_vec()).map_err(|e| {
+            reader.error(
+                std::any::type_name::<Self>(),
+                format!("invalid utf-8: {}", e.utf8_error()),
+            )
+        })
     }
 }
 
@@ -65,6 +81,10 @@ unsafe impl<T: Storable> Storable for Vec<T> {
         unsafe { TypeHash::new("Vec", &["inner"], &[T::type_hash()]) }
     }
 
+    fn field_type() -> FieldType {
+        FieldType::List(Box::new(T::field_type()))
+    }
+
     fn inner_encoded(&self) -> Vec<u8> {
         self.iter().flat_map(|e| e.encoded()).collect()
     }
@@ -72,8 +92,35 @@ unsafe impl<T: Storable> Storable for Vec<T> {
     fn decoded(mut reader: ByteReader) -> DbResult<Self> {
         let mut res = Vec::new();
         while !reader.is_at_end() {
-            res.push(T::decoded(reader.reader_for_block())?);
+            res.push(reader.read::<T>()?);
         }
         Ok(res)
     }
 }
+
+/// Options are empty for `None` and a block for `Some`, which is
+/// also how [Serde](crate::serde::Serde) encodes them.
+unsafe impl<T: Storable> Storable for Option<T> {
+    fn type_hash() -> TypeHash {
+        unsafe { TypeHash::new("Option", &["inner"], &[T::type_hash()]) }
+    }
+
+    fn field_type() -> FieldType {
+        FieldType::Option(Box::new(T::field_type()))
+    }
+
+    fn inner_encoded(&self) -> Vec<u8> {
+        self.as_ref().map(T::encoded).unwrap_or_default()
+    }
+
+    fn decoded(mut reader: ByteReader) -> DbResult<Self> {
+        if reader.is_at_end() {
+            return Ok(None);
+        }
+        let value = reader.read::<T>()?;
+        if !reader.is_at_end() {
+            return Err(reader.error("Option", "more than one value"));
+        }
+        Ok(Some(value))
+    }
+}
@@ -49,6 +49,31 @@ impl TypeHash {
         }
     }
 
+    /// Like [new](Self::new) but only the first `required` fields are hashed.
+    ///
+    /// The remaining fields have default values so they can be missing in
+    /// stored data. Appending fields with a default value is a compatible
+    /// extension that keeps the hash and therefore the stored table, while
+    /// changing, removing or reordering the required fields is a breaking
+    /// change that results in a new hash.
+    ///
+    /// ## Safety:
+    /// Since types must be completely unique it is unsafe to manually create them.
+    pub const unsafe fn new_extensible(
+        type_name: &'static str,
+        field_names: &[&'static str],
+        field_types: &[TypeHash],
+        required: usize,
+    ) -> Self {
+        unsafe {
+            Self::new(
+                type_name,
+                field_names.split_at(required).0,
+                field_types.split_at(required).0,
+            )
+        }
+    }
+
     /// ## Safety:
     /// Since types must be completely unique it is unsafe to manually create them.
     pub const unsafe fn from_str(src: &'static str) -> Self {
@@ -114,7 +139,7 @@ mod test {
     #[test]
     fn code_roundtrip() {
         let start = unsafe { TypeHash::from_str("abc") };
-        let end = TypeHash::decode(&start.clone().encode());
+        let end = TypeHash::decode(&start.encode());
         assert_eq!(start, end);
     }
 }
@@ -0,0 +1,241 @@
+//! Dynamically typed values decoded using a persisted [FieldType].
+
+use std::fmt::Display;
+
+use crate::{
+    byte_reader::ByteReader, db::DbResult, entity_meta::with_len, schema::FieldType,
+    storable::Storable,
+};
+
+/// A stored value whose type is only known at runtime.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Value {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    Usize(usize),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    Isize(isize),
+    String(String),
+    List(Vec<Value>),
+    Struct(Vec<(String, Value)>),
+    /// The encoded content of a [FieldType::Serde] value.
+    Bytes(Vec<u8>),
+    /// A [FieldType::Option] that is `None`, a `Some` is the value itself.
+    Null,
+}
+
+impl Value {
+    /// Decodes a value of type `ty` from a block.
+    pub fn decode(ty: &FieldType, mut reader: ByteReader) -> DbResult<Self> {
+        Ok(match ty {
+            FieldType::U8 => Value::U8(u8::decoded(reader)?),
+            FieldType::U16 => Value::U16(u16::decoded(reader)?),
+            FieldType::U32 => Value::U32(u32::decoded(reader)?),
+            FieldType::U64 => Value::U64(u64::decoded(reader)?),
+            FieldType::U128 => Value::U128(u128::decoded(reader)?),
+            FieldType::Usize => Value::Usize(usize::decoded(reader)?),
+            FieldType::I8 => Value::I8(i8::decoded(reader)?),
+            FieldType::I16 => Value::I16(i16::decoded(reader)?),
+            FieldType::I32 => Value::I32(i32::decoded(reader)?),
+            FieldType::I64 => Value::I64(i64::decoded(reader)?),
+            FieldType::I128 => Value::I128(i128::decoded(reader)?),
+            FieldType::Isize => Value::Isize(isize::decoded(reader)?),
+            FieldType::String => Value::String(String::decoded(reader)?),
+            FieldType::List(inner) => {
+                let mut res = Vec::new();
+                while !reader.is_at_end() {
+                    res.push(Self::decode(inner, reader.reader_for_block()?)?);
+                }
+                Value::List(res)
+            }
+            FieldType::Struct { fields, .. } => Value::Struct(
+                fields
+                    .iter()
+                    .map(|f| {
+                        Ok((
+                            f.name.clone(),
+                            Self::decode(&f.ty, reader.reader_for_block()?)?,
+                        ))
+                    })
+                    .collect::<DbResult<_>>()?,
+            ),
+            FieldType::Serde(_) => Value::Bytes(reader.read_byte_slice().to_vec()),
+            FieldType::Option(_) if reader.is_at_end() => Value::Null,
+            FieldType::Option(inner) => {
+                let value = Self::decode(inner, reader.reader_for_block()?)?;
+                if !reader.is_at_end() {
+                    return Err(reader.error("Option", "more than one value"));
+                }
+                value
+            }
+        })
+    }
+
+    /// Converts a storable using the field metadata of its type.
+    pub fn of<T: Storable>(value: &T) -> DbResult<Self> {
+        Self::decode(&T::field_type(), ByteReader::new(&value.inner_encoded()))
+    }
+
+    /// Converts the value back to the storable type it has the field metadata of.
+    pub fn to<T: Storable>(&self) -> DbResult<T> {
+        T::decoded(ByteReader::new(&self.inner_encoded_as(&T::field_type())))
+    }
+
+    /// Encodes the value the same way the equivalent rust type would be encoded.
+    ///
+    /// A `Some` of a [FieldType::Option] is encoded like the value it holds,
+    /// use [encoded_as](Self::encoded_as) for values that can contain options.
+    pub fn encoded(&self) -> Vec<u8> {
+        with_len(self.inner_encoded())
+    }
+
+    /// Encodes the value the same way a rust value of type `ty` would be encoded.
+    pub fn encoded_as(&self, ty: &FieldType) -> Vec<u8> {
+        with_len(self.inner_encoded_as(ty))
+    }
+
+    fn inner_encoded_as(&self, ty: &FieldType) -> Vec<u8> {
+        match (ty, self) {
+            (FieldType::Option(_), Value::Null) => vec![],
+            (FieldType::Option(inner), value) => value.encoded_as(inner),
+            (FieldType::List(inner), Value::List(values)) => {
+                values.iter().flat_map(|v| v.encoded_as(inner)).collect()
+            }
+            (FieldType::Struct { fields, .. }, Value::Struct(values)) => values
+                .iter()
+                .enumerate()
+                .flat_map(|(i, (_, v))| match fields.get(i) {
+                    Some(field) => v.encoded_as(&field.ty),
+                    None => v.encoded(),
+                })
+                .collect(),
+            _ => self.inner_encoded(),
+        }
+    }
+
+    fn inner_encoded(&self) -> Vec<u8> {
+        match self {
+            Value::U8(v) => v.inner_encoded(),
+            Value::U16(v) => v.inner_encoded(),
+            Value::U32(v) => v.inner_encoded(),
+            Value::U64(v) => v.inner_encoded(),
+            Value::U128(v) => v.inner_encoded(),
+            Value::Usize(v) => v.inner_encoded(),
+            Value::I8(v) => v.inner_encoded(),
+            Value::I16(v) => v.inner_encoded(),
+            Value::I32(v) => v.inner_encoded(),
+            Value::I64(v) => v.inner_encoded(),
+            Value::I128(v) => v.inner_encoded(),
+            Value::Isize(v) => v.inner_encoded(),
+            Value::String(v) => v.inner_encoded(),
+            Value::List(values) => values.iter().flat_map(|v| v.encoded()).collect(),
+            Value::Struct(fields) => fields.iter().flat_map(|(_, v)| v.encoded()).collect(),
+            Value::Bytes(bytes) => bytes.clone(),
+            Value::Null => vec![],
+        }
+    }
+
+    /// The id following this one, mirroring [IdType::generate](crate::id::IdType::generate)
+    /// for the number types.
+    pub fn next_id(&self) -> Option<Value> {
+        Some(match self {
+            Value::U8(v) => Value::U8(v.checked_add(1)?),
+            Value::U16(v) => Value::U16(v.checked_add(1)?),
+            Value::U32(v) => Value::U32(v.checked_add(1)?),
+            Value::U64(v) => Value::U64(v.checked_add(1)?),
+            Value::U128(v) => Value::U128(v.checked_add(1)?),
+            Value::Usize(v) => Value::Usize(v.checked_add(1)?),
+            Value::I8(v) => Value::I8(v.checked_add(1)?),
+            Value::I16(v) => Value::I16(v.checked_add(1)?),
+            Value::I32(v) => Value::I32(v.checked_add(1)?),
+            Value::I64(v) => Value::I64(v.checked_add(1)?),
+            Value::I128(v) => Value::I128(v.checked_add(1)?),
+            Value::Isize(v) => Value::Isize(v.checked_add(1)?),
+            _ => return None,
+        })
+    }
+
+    /// Gets a field of a struct value.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Replaces a field of a struct value.
+    pub fn set_field(&mut self, name: &str, value: Value) -> Option<()> {
+        match self {
+            Value::Struct(fields) => {
+                fields.iter_mut().find(|(n, _)| n == name)?.1 = value;
+                Some(())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::U8(v) => write!(f, "{v}"),
+            Value::U16(v) => write!(f, "{v}"),
+            Value::U32(v) => write!(f, "{v}"),
+            Value::U64(v) => write!(f, "{v}"),
+            Value::U128(v) => write!(f, "{v}"),
+            Value::Usize(v) => write!(f, "{v}"),
+            Value::I8(v) => write!(f, "{v}"),
+            Value::I16(v) => write!(f, "{v}"),
+            Value::I32(v) => write!(f, "{v}"),
+            Value::I64(v) => write!(f, "{v}"),
+            Value::I128(v) => write!(f, "{v}"),
+            Value::Isize(v) => write!(f, "{v}"),
+            Value::String(v) => write!(f, "{v:?}"),
+            Value::List(values) => {
+                write!(f, "[")?;
+                for (i, v) in values.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{v}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Struct(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, v)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}: {v}")?;
+                }
+                write!(f, "}}")
+            }
+            Value::Bytes(bytes) => write!(f, "0x{}", hex(bytes)),
+            Value::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// Formats bytes as lowercase hex digits.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Parses bytes formatted with [hex].
+pub(crate) fn from_hex(src: &str) -> Option<Vec<u8>> {
+    if !src.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..src.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(src.get(i..i + 2)?, 16).ok())
+        .collect()
+}
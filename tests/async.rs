@@ -0,0 +1,144 @@
+use std::{
+    error::Error,
+    fs,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use somedb::{async_db::AsyncDatabase, db::ErrorKind, entity};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Job {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+}
+
+fn job(id: u32, name: &str) -> Job {
+    Job {
+        id,
+        name: name.into(),
+    }
+}
+
+#[tokio::test]
+async fn store_and_load() -> Result<(), Box<dyn Error>> {
+    let db = AsyncDatabase::new("async_store_sdb/", true).await?;
+
+    let first = db.store(job(0, "build")).await?;
+    let second = db.store(job(0, "test")).await?;
+    assert_eq!(db.find_by_id::<Job>(first.id).await?, Some(first));
+
+    db.update_entity(job(second.id, "deploy")).await?;
+    db.delete_entity_by_id::<Job>(1).await?;
+    assert_eq!(db.read_all::<Job>().await?, vec![job(2, "deploy")]);
+
+    // everything else is available through the synchronous database
+    let tables = db.with(|db| db.catalog()).await?;
+    assert_eq!(tables.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stream_query_results() -> Result<(), Box<dyn Error>> {
+    let db = AsyncDatabase::new("async_stream_sdb/", true).await?;
+    db.write_all((1..=150).map(|i| job(i, "job")).collect())
+        .await?;
+
+    let mut jobs = db.query::<Job>().await?;
+    let mut ids = vec![];
+    while let Some(job) = jobs.next().await {
+        ids.push(job?.id);
+    }
+    assert_eq!(ids, (1..=150).collect::<Vec<_>>());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn streams_return_the_rows_before_an_error() -> Result<(), Box<dyn Error>> {
+    let db = AsyncDatabase::new("async_stream_error_sdb/", true).await?;
+    db.write_all((1..=10).map(|i| job(i, &format!("job-{i:02}"))).collect())
+        .await?;
+
+    // damage the sixth job
+    let path = db.with(|db| db.table_info("Job")).await?.unwrap().path;
+    let mut data = fs::read(&path)?;
+    let at = data.windows(6).position(|w| w == b"job-06").unwrap();
+    data[at] = b'J';
+    fs::write(&path, data)?;
+
+    let mut jobs = db.query::<Job>().await?;
+    let mut ids = vec![];
+    let err = loop {
+        match jobs.next().await {
+            Some(Ok(job)) => ids.push(job.id),
+            Some(Err(e)) => break e,
+            None => panic!("the damaged job was returned"),
+        }
+    };
+    assert_eq!(ids, [1, 2, 3, 4, 5]);
+    assert_eq!(err.kind(), ErrorKind::ChecksumMismatch);
+    assert!(jobs.next().await.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn writers_wait_for_open_streams() -> Result<(), Box<dyn Error>> {
+    let db = AsyncDatabase::new("async_stream_lock_sdb/", true).await?;
+    db.write_all((1..=100).map(|i| job(i, "job")).collect())
+        .await?;
+
+    // the stream stops reading after the first batch
+    let mut jobs = db.query::<Job>().await?;
+    assert!(jobs.next().await.is_some());
+
+    let writer = tokio::spawn({
+        let db = db.clone();
+        async move { db.store(job(0, "second")).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!writer.is_finished());
+
+    drop(jobs);
+    assert_eq!(writer.await??.id, 101);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn waiting_for_locks_does_not_block_the_executor() -> Result<(), Box<dyn Error>> {
+    let db = AsyncDatabase::new("async_wait_sdb/", true).await?;
+    db.store(job(0, "first")).await?;
+    let path = db.with(|db| db.table_info("Job")).await?.unwrap().path;
+
+    // another process is writing the table
+    let lock = path.with_extension("wlock");
+    fs::write(&lock, "other")?;
+
+    let ticks = Arc::new(AtomicU32::new(0));
+    let ticker = tokio::spawn({
+        let ticks = ticks.clone();
+        async move {
+            for _ in 0..5 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                ticks.fetch_add(1, Ordering::Relaxed);
+            }
+            fs::remove_file(lock).unwrap();
+        }
+    });
+
+    // the ticker runs on the same thread while the store waits
+    db.store(job(0, "second")).await?;
+    assert_eq!(ticks.load(Ordering::Relaxed), 5);
+    ticker.await?;
+    assert_eq!(db.read_all::<Job>().await?.len(), 2);
+
+    Ok(())
+}
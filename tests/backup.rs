@@ -0,0 +1,148 @@
+use std::{error::Error, fs, thread, time::SystemTime};
+
+use somedb::{
+    backup::list_snapshots,
+    db::{Database, ErrorKind},
+    entity,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Account {
+    #[entity_id(auto_generate)]
+    id: u32,
+    owner: String,
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Log {
+    #[entity_id(auto_generate)]
+    id: u32,
+    message: String,
+}
+
+fn account(owner: &str) -> Account {
+    Account {
+        id: 0,
+        owner: owner.into(),
+    }
+}
+
+fn owners(db: &Database) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(db
+        .read_all::<Account>()?
+        .into_iter()
+        .map(|a| a.owner)
+        .collect())
+}
+
+#[test]
+fn incremental_backup_and_restore() -> Result<(), Box<dyn Error>> {
+    let backups = "backup_incremental_sdb/";
+    let _ = fs::remove_dir_all(backups);
+
+    let mut db = Database::new("backup_db_sdb/", true)?;
+    db.store(account("ada"))?;
+    db.store(Log {
+        id: 0,
+        message: "created ada".into(),
+    })?;
+
+    let first = db.backup_to(backups)?;
+    assert_eq!(first.tables, 2);
+    assert_eq!(first.copied_tables, 2);
+
+    db.store(account("alan"))?;
+    let second = db.backup_incremental_to(backups)?;
+    assert!(second.time > first.time);
+    assert_eq!(second.tables, 2);
+    assert_eq!(second.copied_tables, 1);
+    assert_eq!(
+        list_snapshots(backups)?,
+        vec![first.clone(), second.clone()]
+    );
+
+    db.delete_entity_store::<Log>()?;
+    db.store(account("grace"))?;
+
+    db.restore_from(backups, first.time)?;
+    assert_eq!(owners(&db)?, vec!["ada"]);
+    assert_eq!(db.read_all::<Log>()?.len(), 1);
+
+    db.restore_from(backups, SystemTime::now())?;
+    assert_eq!(owners(&db)?, vec!["ada", "alan"]);
+    assert!(db.verify()?.iter().all(|r| r.is_ok()));
+
+    // restored tables are still usable by a new handle
+    let db = Database::new("backup_db_sdb/", false)?;
+    assert_eq!(db.catalog()?.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn restore_removes_newer_tables() -> Result<(), Box<dyn Error>> {
+    let backups = "backup_newer_tables_sdb/";
+    let _ = fs::remove_dir_all(backups);
+
+    let mut db = Database::new("backup_newer_db_sdb/", true)?;
+    db.store(account("ada"))?;
+    let snapshot = db.backup_to(backups)?;
+
+    db.store(Log {
+        id: 0,
+        message: "new table".into(),
+    })?;
+    db.restore_from(backups, snapshot.time)?;
+
+    assert_eq!(
+        db.read_all::<Log>().unwrap_err().kind(),
+        ErrorKind::TypeNotFound
+    );
+    assert_eq!(db.catalog()?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn restore_without_backup() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("backup_missing_sdb/", true)?;
+    assert_eq!(
+        db.restore_from("backup_missing_sdb/none", SystemTime::now())
+            .unwrap_err()
+            .kind(),
+        ErrorKind::BackupNotFound
+    );
+    Ok(())
+}
+
+#[test]
+fn backup_while_writing() -> Result<(), Box<dyn Error>> {
+    let backups = "backup_online_sdb/";
+    let _ = fs::remove_dir_all(backups);
+
+    let mut db = Database::new("backup_online_db_sdb/", true)?;
+    db.store(account("initial"))?;
+
+    let writer = thread::spawn(|| {
+        let mut db = Database::new("backup_online_db_sdb/", false).unwrap();
+        for i in 0..20 {
+            db.store(account(&format!("writer {i}"))).unwrap();
+        }
+    });
+
+    let snapshots: Vec<_> = (0..5)
+        .map(|_| db.backup_incremental_to(backups))
+        .collect::<Result<_, _>>()?;
+    writer.join().unwrap();
+
+    for snapshot in snapshots {
+        let mut restored = Database::new("backup_online_restored_sdb/", true)?;
+        restored.restore_from(backups, snapshot.time)?;
+        assert!(restored.verify()?.iter().all(|r| r.is_ok()));
+        assert!(owners(&restored)?.contains(&"initial".to_string()));
+    }
+
+    Ok(())
+}
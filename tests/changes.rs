@@ -0,0 +1,143 @@
+use std::error::Error;
+
+use somedb::{
+    changes::{Change, ChangeKind},
+    db::Database,
+    entity,
+    gen_query::GenExpr,
+    query::DbIterator,
+    value::Value,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Item {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Other {
+    #[entity_id(auto_generate)]
+    id: u32,
+}
+
+fn item(id: u32, name: &str) -> Item {
+    Item {
+        id,
+        name: name.into(),
+    }
+}
+
+#[test]
+fn subscribe_to_changes() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("changes_subscribe_sdb/", true)?;
+    let changes = db.subscribe::<Item>();
+    let other = db.subscribe::<Other>();
+
+    db.store(item(0, "a"))?;
+    db.store(item(0, "b"))?;
+    db.update_entity(item(1, "c"))?;
+    // an update without a difference is no change
+    db.update_entity(item(1, "c"))?;
+    db.delte_entity_by_id::<Item>(2)?;
+    db.query_mut::<Item>()?
+        .filter(|i| i.id().eq(100))
+        .save_to_db()?;
+
+    assert_eq!(
+        changes.try_iter().collect::<Vec<_>>(),
+        vec![
+            Change::Inserted(item(1, "a")),
+            Change::Inserted(item(2, "b")),
+            Change::Updated {
+                old: item(1, "a"),
+                new: item(1, "c"),
+            },
+            Change::Deleted(2),
+            Change::Deleted(1),
+        ]
+    );
+    assert_eq!(other.try_iter().count(), 0);
+
+    // dropped receivers are removed on the next change
+    drop(changes);
+    db.store(item(0, "d"))?;
+
+    Ok(())
+}
+
+#[test]
+fn tail_change_log() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("changes_log_sdb/", true)?;
+    db.set_change_log(true)?;
+
+    db.store(item(0, "a"))?;
+    db.store(Other { id: 0 })?;
+    db.update_entity(item(1, "b"))?;
+
+    let changes = db.changes_since(0)?;
+    assert_eq!(
+        changes.iter().map(|c| c.sequence).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(
+        changes[0].decode::<Item>()?,
+        Some(Change::Inserted(item(1, "a")))
+    );
+    assert_eq!(changes[1].decode::<Item>()?, None);
+    assert_eq!(changes[1].kind, ChangeKind::Inserted);
+
+    // the log can be read without the rust types
+    let schema = db.table_info("Item")?.unwrap().schema.unwrap();
+    let (old, new) = changes[2].decode_dyn(&schema)?;
+    assert_eq!(old.unwrap().field("name"), Some(&Value::String("a".into())));
+    assert_eq!(new.unwrap().field("name"), Some(&Value::String("b".into())));
+
+    // a consumer resumes from the last sequence it has seen
+    let mut db = Database::new("changes_log_sdb/", false)?;
+    db.set_change_log(true)?;
+    db.delte_entity_by_id::<Item>(1)?;
+
+    let changes = db.changes_since(3)?;
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].sequence, 4);
+    assert_eq!(changes[0].decode::<Item>()?, Some(Change::Deleted(1)));
+
+    Ok(())
+}
+
+#[test]
+fn handles_share_the_sequence() -> Result<(), Box<dyn Error>> {
+    let mut first = Database::new("changes_handles_sdb/", true)?;
+    let mut second = Database::new("changes_handles_sdb/", false)?;
+    first.set_change_log(true)?;
+    second.set_change_log(true)?;
+
+    first.store(item(0, "a"))?;
+    second.store(Other { id: 0 })?;
+    first.store(item(0, "b"))?;
+    first.store(item(0, "c"))?;
+    second.store(Other { id: 0 })?;
+
+    assert_eq!(
+        first
+            .changes_since(0)?
+            .iter()
+            .map(|c| c.sequence)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3, 4, 5]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn change_log_is_disabled_by_default() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("changes_disabled_sdb/", true)?;
+    db.store(item(0, "a"))?;
+    assert!(db.changes_since(0)?.is_empty());
+    Ok(())
+}
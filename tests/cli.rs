@@ -0,0 +1,118 @@
+use std::{error::Error, fs, process::Command};
+
+use somedb::{db::Database, entity};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Person {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    tags: Vec<String>,
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Code {
+    #[entity_id]
+    id: u32,
+    label: String,
+}
+
+fn somedb(args: &[&str]) -> (bool, String) {
+    somedb_in("cli_sdb/", args)
+}
+
+fn somedb_in(dir: &str, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_somedb"))
+        .args(["--dir", dir])
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn inspect_and_edit() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("cli_sdb/", true)?;
+    db.store(Person {
+        id: 0,
+        name: "Alan, \"Al\"".into(),
+        tags: vec!["a".into()],
+    })?;
+
+    let (ok, tables) = somedb(&["tables"]);
+    assert!(ok);
+    assert!(tables.starts_with("Person\t1 rows"));
+
+    let (ok, json) = somedb(&["dump", "Person"]);
+    assert!(ok);
+    assert_eq!(
+        json,
+        "[\n{\"id\":1,\"name\":\"Alan, \\\"Al\\\"\",\"tags\":[\"a\"]}\n]\n"
+    );
+
+    let (ok, csv) = somedb(&["dump", "Person", "--format", "csv"]);
+    assert!(ok);
+    assert_eq!(
+        csv,
+        "id,name,tags\n1,\"Alan, \"\"Al\"\"\",\"[\"\"a\"\"]\"\n"
+    );
+
+    fs::write("cli_sdb_import.csv", csv)?;
+    let (ok, _) = somedb(&["import", "Person", "cli_sdb_import.csv", "--format", "csv"]);
+    fs::remove_file("cli_sdb_import.csv")?;
+    assert!(ok);
+
+    let (ok, count) = somedb(&["count", "Person"]);
+    assert!(ok);
+    assert_eq!(count, "2\n");
+
+    let people = db.read_all::<Person>()?;
+    assert_eq!(people[1].id, 2);
+    assert_eq!(people[1].name, people[0].name);
+
+    let (ok, check) = somedb(&["check"]);
+    assert!(ok);
+    assert_eq!(check, "Person: ok (2 rows)\n");
+
+    assert!(!somedb(&["dump", "Nobody"]).0);
+
+    let (ok, out) = somedb(&["sql", "UPDATE Person SET name = 'Grace' WHERE id = 2"]);
+    assert!(ok);
+    assert_eq!(out, "changed 1 rows\n");
+    let (ok, out) = somedb(&["sql", "SELECT id, name FROM Person ORDER BY id DESC"]);
+    assert!(ok);
+    assert_eq!(
+        out,
+        "{id: 2, name: \"Grace\"}\n{id: 1, name: \"Alan, \\\"Al\\\"\"}\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn import_keeps_the_last_id() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("cli_import_sdb/", true)?;
+    db.store(Code {
+        id: 10,
+        label: "ten".into(),
+    })?;
+
+    fs::write("cli_import_sdb.csv", "id,label\n3,three\n")?;
+    let (ok, _) = somedb_in(
+        "cli_import_sdb/",
+        &["import", "Code", "cli_import_sdb.csv", "--format", "csv"],
+    );
+    fs::remove_file("cli_import_sdb.csv")?;
+    assert!(ok);
+
+    let table = db.table_info("Code")?.unwrap();
+    assert_eq!(table.row_count, 2);
+    assert_eq!(table.last_id.unwrap().to_string(), "10");
+
+    Ok(())
+}
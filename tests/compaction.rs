@@ -0,0 +1,90 @@
+use std::{error::Error, fs};
+
+use somedb::{db::Database, entity};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Note {
+    #[entity_id(auto_generate)]
+    id: u32,
+    text: String,
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Tag {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+}
+
+fn file_size(db: &Database, name: &str) -> Result<u64, Box<dyn Error>> {
+    let table = db.table_info(name)?.unwrap();
+    Ok(fs::metadata(table.path)?.len())
+}
+
+fn fill(db: &mut Database) -> Result<(), Box<dyn Error>> {
+    for i in 0..10 {
+        db.store(Note {
+            id: 0,
+            text: format!("note number {i}"),
+        })?;
+        db.store(Tag {
+            id: 0,
+            name: format!("tag {i}"),
+        })?;
+    }
+    for id in 1..=8 {
+        db.delte_entity_by_id::<Note>(id)?;
+        db.delte_entity_by_id::<Tag>(id)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn compact_reclaims_deleted_rows() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("compaction_sdb/", true)?;
+    fill(&mut db)?;
+
+    let before = file_size(&db, "Note")?;
+    let reclaimed = db.compact::<Note>()?;
+    assert!(reclaimed > 0);
+    assert_eq!(file_size(&db, "Note")?, before - reclaimed);
+
+    assert_eq!(db.compact::<Note>()?, 0);
+    assert_eq!(db.read_all::<Note>()?.len(), 2);
+    assert!(db.verify()?.iter().all(|r| r.is_ok()));
+
+    Ok(())
+}
+
+#[test]
+fn vacuum_compacts_all_tables() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("vacuum_sdb/", true)?;
+    fill(&mut db)?;
+
+    let before = file_size(&db, "Note")? + file_size(&db, "Tag")?;
+    let reclaimed = db.vacuum()?;
+    assert_eq!(
+        file_size(&db, "Note")? + file_size(&db, "Tag")?,
+        before - reclaimed
+    );
+    assert_eq!(db.vacuum()?, 0);
+
+    assert_eq!(db.read_all::<Note>()?.len(), 2);
+    assert_eq!(db.read_all::<Tag>()?.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn auto_compact() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("auto_compact_sdb/", true)?;
+    db.set_auto_compact(Some(0));
+    fill(&mut db)?;
+
+    assert_eq!(db.vacuum()?, 0);
+    assert_eq!(db.read_all::<Note>()?.len(), 2);
+
+    Ok(())
+}
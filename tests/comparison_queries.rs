@@ -0,0 +1,180 @@
+use std::error::Error;
+
+use somedb::{db::Database, entity, gen_query::GenExpr, query::DbIterator};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Member {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    age: u8,
+}
+
+fn setup(dir: &str) -> Result<Database, Box<dyn Error>> {
+    let mut db = Database::new(dir, true)?;
+    for (name, age) in [("Ann", 25), ("Bob", 30), ("Cid", 35), ("Dee", 40)] {
+        db.store(Member {
+            id: 0,
+            name: name.into(),
+            age,
+        })?;
+    }
+    Ok(db)
+}
+
+fn names(query: impl DbIterator<Item = Member>) -> Vec<String> {
+    query.collect_vec().into_iter().map(|m| m.name).collect()
+}
+
+#[test]
+fn ordering() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("comparison_ordering_sdb/")?;
+
+    assert_eq!(
+        names(db.query_mut::<Member>()?.filter(|m| m.age().gt(30))),
+        ["Cid", "Dee"]
+    );
+    assert_eq!(
+        names(db.query_mut::<Member>()?.filter(|m| m.age().gte(30))),
+        ["Bob", "Cid", "Dee"]
+    );
+    assert_eq!(
+        names(db.query_mut::<Member>()?.filter(|m| m.age().lt(30))),
+        ["Ann"]
+    );
+    assert_eq!(
+        names(
+            db.query_mut::<Member>()?
+                .filter(|m| m.age().lte(35).land(m.id().gt(1)))
+        ),
+        ["Bob", "Cid"]
+    );
+    // both sides can be expressions
+    assert_eq!(
+        names(
+            db.query_mut::<Member>()?
+                .filter(|m| m.id().mul(10).lt(m.id().add(20)))
+        ),
+        ["Ann", "Bob"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn ranges_and_lists() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("comparison_ranges_sdb/")?;
+
+    assert_eq!(
+        names(
+            db.query_mut::<Member>()?
+                .filter(|m| m.age().between(30, 35))
+        ),
+        ["Bob", "Cid"]
+    );
+    assert_eq!(
+        names(
+            db.query_mut::<Member>()?
+                .filter(|m| m.id().in_list([1, 4, 9]))
+        ),
+        ["Ann", "Dee"]
+    );
+    assert_eq!(
+        names(
+            db.query_mut::<Member>()?
+                .filter(|m| m.name().in_list(["Bob".to_string(), "Eve".to_string()]))
+        ),
+        ["Bob"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn negation() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("comparison_negation_sdb/")?;
+
+    assert_eq!(
+        names(
+            db.query_mut::<Member>()?
+                .filter(|m| m.age().between(30, 35).not())
+        ),
+        ["Ann", "Dee"]
+    );
+    assert_eq!(
+        names(
+            db.query_mut::<Member>()?.filter(|m| m
+                .name()
+                .starts_with("B")
+                .lor(m.id().in_list([1, 2]))
+                .not())
+        ),
+        ["Cid", "Dee"]
+    );
+    // on integers `not` inverts the bits
+    assert_eq!(
+        names(db.query_mut::<Member>()?.filter(|m| m.age().not().eq(!40))),
+        ["Dee"]
+    );
+
+    Ok(())
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Guest {
+    #[entity_id(auto_generate)]
+    id: u32,
+    nickname: Option<String>,
+    age: Option<u8>,
+}
+
+#[test]
+fn optional_fields() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("comparison_optional_sdb/", true)?;
+    for (nickname, age) in [
+        (Some("ann"), None),
+        (None, Some(30)),
+        (Some("cid"), Some(35)),
+    ] {
+        db.store(Guest {
+            id: 0,
+            nickname: nickname.map(Into::into),
+            age,
+        })?;
+    }
+
+    let ids = |guests: Vec<Guest>| guests.into_iter().map(|g| g.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(db
+            .query_mut::<Guest>()?
+            .filter(|g| g.nickname().is_null())
+            .collect_vec()),
+        [2]
+    );
+    assert_eq!(
+        ids(db
+            .query_mut::<Guest>()?
+            .filter(|g| g.age().is_some())
+            .collect_vec()),
+        [2, 3]
+    );
+    assert_eq!(
+        ids(db
+            .query_mut::<Guest>()?
+            .filter(|g| g.nickname().is_some().land(g.age().is_null()))
+            .collect_vec()),
+        [1]
+    );
+    assert_eq!(
+        db.find_by_id::<Guest>(3)?,
+        Some(Guest {
+            id: 3,
+            nickname: Some("cid".into()),
+            age: Some(35),
+        })
+    );
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use std::error::Error;
+
+use somedb::{
+    db::{Database, DbError, ErrorKind},
+    entity,
+};
+
+fn is_lowercase(name: &str) -> bool {
+    name.chars().all(|c| !c.is_uppercase())
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Member {
+    #[entity_id(auto_generate)]
+    id: u32,
+    #[not_empty]
+    #[max_len(8)]
+    #[check(is_lowercase)]
+    name: String,
+    #[range(min = 18, max = 150)]
+    age: u8,
+    #[unique]
+    email: String,
+    #[not_empty]
+    roles: Vec<String>,
+}
+
+fn member(name: &str, age: u8, email: &str) -> Member {
+    Member {
+        id: 0,
+        name: name.into(),
+        age,
+        email: email.into(),
+        roles: vec!["user".into()],
+    }
+}
+
+fn invalid_field(err: DbError) -> Option<(String, String)> {
+    match err {
+        DbError::Validation {
+            field: Some(field),
+            message,
+            ..
+        } => Some((field, message)),
+        _ => None,
+    }
+}
+
+#[test]
+fn constraints_are_checked_on_store() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("constraints_store_sdb/", true)?;
+    db.store(member("ada", 36, "ada@example.com"))?;
+
+    let cases = [
+        (member("", 36, "a@b"), "name", "must not be empty"),
+        (
+            member("adalovelace", 36, "a@b"),
+            "name",
+            "must not be longer than 8",
+        ),
+        (
+            member("Ada", 36, "a@b"),
+            "name",
+            "failed check is_lowercase",
+        ),
+        (member("ada", 17, "a@b"), "age", "must be at least 18"),
+        (member("ada", 151, "a@b"), "age", "must be at most 150"),
+    ];
+    for (member, field, message) in cases {
+        let err = db.store(member).unwrap_err();
+        assert_eq!(
+            invalid_field(err),
+            Some((field.to_string(), message.to_string()))
+        );
+    }
+
+    let mut no_roles = member("alan", 41, "alan@example.com");
+    no_roles.roles.clear();
+    assert_eq!(
+        invalid_field(db.store(no_roles).unwrap_err()).unwrap().0,
+        "roles"
+    );
+
+    let (field, message) =
+        invalid_field(db.store(member("alan", 41, "ada@example.com")).unwrap_err()).unwrap();
+    assert_eq!(field, "email");
+    assert!(message.contains("id 1"));
+
+    assert_eq!(db.read_all::<Member>()?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn constraints_are_checked_on_every_write() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("constraints_writes_sdb/", true)?;
+    let ada = db.store(member("ada", 36, "ada@example.com"))?;
+    db.store(member("alan", 41, "alan@example.com"))?;
+
+    let mut update = ada.clone();
+    update.email = "alan@example.com".into();
+    assert_eq!(
+        db.update_entity(update).unwrap_err().kind(),
+        ErrorKind::Validation
+    );
+
+    let mut too_young = ada.clone();
+    too_young.age = 3;
+    assert_eq!(
+        db.write_all(vec![too_young]).unwrap_err().kind(),
+        ErrorKind::Validation
+    );
+
+    let mut renamed = ada.clone();
+    renamed.name = "lovelace".into();
+    db.update_entity(renamed)?;
+
+    assert_eq!(
+        db.read_all::<Member>()?
+            .into_iter()
+            .map(|m| m.name)
+            .collect::<Vec<_>>(),
+        vec!["lovelace", "alan"]
+    );
+
+    Ok(())
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Account {
+    #[entity_id(auto_generate)]
+    id: u32,
+    #[not_empty]
+    #[max_len(8)]
+    name: String,
+    #[range(min = 18, max = 150)]
+    age: u8,
+    #[unique]
+    email: String,
+}
+
+#[test]
+fn constraints_are_checked_without_the_rust_type() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("constraints_untyped_sdb/", true)?;
+    db.store(Account {
+        id: 0,
+        name: "ada".into(),
+        age: 36,
+        email: "ada@example.com".into(),
+    })?;
+
+    let schema = db.table_info("Account")?.unwrap().schema.unwrap();
+    assert_eq!(schema.constraints.len(), 5);
+
+    let cases = [
+        (
+            "INSERT INTO Account (name, age, email) VALUES ('', 40, 'a@b')",
+            "name",
+            "must not be empty",
+        ),
+        (
+            "INSERT INTO Account (name, age, email) VALUES ('adalovelace', 40, 'a@b')",
+            "name",
+            "must not be longer than 8",
+        ),
+        (
+            "INSERT INTO Account (name, age, email) VALUES ('alan', 17, 'a@b')",
+            "age",
+            "must be at least 18",
+        ),
+        ("UPDATE Account SET age = 200", "age", "must be at most 150"),
+    ];
+    for (statement, field, message) in cases {
+        let err = db.execute(statement).unwrap_err();
+        assert_eq!(
+            invalid_field(err),
+            Some((field.to_string(), message.to_string()))
+        );
+    }
+
+    db.execute("INSERT INTO Account (name, age, email) VALUES ('alan', 41, 'alan@example.com')")?;
+    let (field, message) = invalid_field(
+        db.execute("UPDATE Account SET email = 'ada@example.com'")
+            .unwrap_err(),
+    )
+    .unwrap();
+    assert_eq!(field, "email");
+    assert!(message.contains("id 1"));
+    assert_eq!(db.read_all::<Account>()?.len(), 2);
+
+    // custom checks can't run without the rust type
+    db.store(member("ada", 36, "ada@example.com"))?;
+    let (field, message) =
+        invalid_field(db.execute("UPDATE Member SET age = 40").unwrap_err()).unwrap();
+    assert_eq!(field, "name");
+    assert!(message.contains("is_lowercase"));
+    assert_eq!(db.read_all::<Member>()?[0].age, 36);
+
+    Ok(())
+}
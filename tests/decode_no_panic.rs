@@ -0,0 +1,183 @@
+//! Feeds corrupted tables to the decoders, see the `fuzz` directory
+//! for the corresponding fuzz targets.
+
+use std::fs;
+
+use somedb::{
+    byte_reader::ByteReader,
+    db::{Database, DbError},
+    entity,
+    entity::Entity,
+    entity_meta::{Checksums, DynEntityMeta, EntityMeta, FORMAT_VERSION},
+    storable::Storable,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Fuzzed {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    values: Vec<i64>,
+}
+
+#[entity]
+struct Wider {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    values: Vec<i64>,
+    extra: u8,
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn decode(data: &[u8]) {
+    let _ = ByteReader::new(data)
+        .reader_for_block()
+        .and_then(EntityMeta::<Fuzzed>::decoded);
+    let _ = ByteReader::new(data)
+        .reader_for_block()
+        .and_then(|r| DynEntityMeta::decoded(&Fuzzed::table_schema(), r));
+}
+
+fn valid_table() -> Vec<u8> {
+    EntityMeta {
+        last_id: 2,
+        entities: vec![
+            Fuzzed {
+                id: 1,
+                name: "first".into(),
+                values: vec![1, -2, 3],
+            },
+            Fuzzed {
+                id: 2,
+                name: "zweitä".into(),
+                values: vec![],
+            },
+        ],
+    }
+    .encoded()
+}
+
+#[test]
+fn truncated_input() {
+    let data = valid_table();
+    for len in 0..data.len() {
+        decode(&data[..len]);
+    }
+}
+
+#[test]
+fn corrupted_bytes() {
+    let data = valid_table();
+    let mut rng = XorShift(0x2545F4914F6CDD1D);
+
+    for _ in 0..10_000 {
+        let mut data = data.clone();
+        for _ in 0..1 + rng.next() % 4 {
+            let i = (rng.next() % data.len() as u64) as usize;
+            data[i] = rng.next() as u8;
+        }
+        decode(&data);
+    }
+}
+
+#[test]
+fn random_input() {
+    let mut rng = XorShift(0x9E3779B97F4A7C15);
+
+    for _ in 0..10_000 {
+        let len = rng.next() % 64;
+        let data: Vec<_> = (0..len).map(|_| rng.next() as u8).collect();
+        decode(&data);
+    }
+}
+
+/// Streams every corrupted table from disk, the stream validates the lengths itself.
+fn stream_all(dir: &str, tables: impl Iterator<Item = Vec<u8>>) {
+    let mut db = Database::new(dir, true).unwrap();
+    db.store(Fuzzed {
+        id: 0,
+        name: String::new(),
+        values: vec![],
+    })
+    .unwrap();
+    let path = db.table_info("Fuzzed").unwrap().unwrap().path;
+
+    for data in tables {
+        fs::write(&path, data).unwrap();
+        if let Ok(mut query) = db.query::<Fuzzed>() {
+            while let Ok(Some(_)) = query.try_next() {}
+        }
+    }
+}
+
+#[test]
+fn streamed_truncated_input() {
+    let data = valid_table();
+    stream_all(
+        "decode_stream_truncated_sdb/",
+        (0..data.len()).map(|len| data[..len].to_vec()),
+    );
+}
+
+#[test]
+fn streamed_corrupted_bytes() {
+    let data = valid_table();
+    let mut rng = XorShift(0xD1B54A32D192ED03);
+
+    stream_all(
+        "decode_stream_corrupted_sdb/",
+        (0..1_000).map(|_| {
+            let mut data = data.clone();
+            for _ in 0..1 + rng.next() % 4 {
+                let i = (rng.next() % data.len() as u64) as usize;
+                data[i] = rng.next() as u8;
+            }
+            data
+        }),
+    );
+}
+
+#[test]
+fn streamed_short_entities_block() {
+    // the entities block ends with 2 bytes, less than the length of an entity
+    let mut inner = FORMAT_VERSION.to_string().encoded();
+    inner.append(&mut 0u32.encoded());
+    inner.extend_from_slice(&2u32.to_be_bytes());
+    inner.extend_from_slice(&[0, 0]);
+    inner.append(
+        &mut Checksums {
+            file: 0,
+            records: vec![],
+        }
+        .encoded(),
+    );
+    let mut data = (inner.len() as u32).to_be_bytes().to_vec();
+    data.append(&mut inner);
+
+    stream_all("decode_stream_short_sdb/", std::iter::once(data));
+}
+
+#[test]
+fn missing_field_is_a_decode_error() {
+    let data = valid_table();
+    let res = ByteReader::new(&data)
+        .reader_for_block()
+        .and_then(EntityMeta::<Wider>::decoded);
+
+    assert!(matches!(
+        res,
+        Err(DbError::Decode { type_name: "u8", reason, .. }) if reason == "unexpected end of data"
+    ));
+}
@@ -0,0 +1,142 @@
+use std::error::Error;
+
+use somedb::{
+    db::{Database, DbError, ErrorKind},
+    dyn_query::{Expr, Row},
+    entity,
+    value::Value,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Employee {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    age: u8,
+    tags: Vec<String>,
+}
+
+fn setup(dir: &str) -> Result<Database, Box<dyn Error>> {
+    let mut db = Database::new(dir, true)?;
+    for (name, age) in [("Ada", 36), ("Alan", 41), ("Grace", 29), ("Linus", 54)] {
+        db.store(Employee {
+            id: 0,
+            name: name.into(),
+            age,
+            tags: vec![],
+        })?;
+    }
+    Ok(db)
+}
+
+fn names(rows: &[Row]) -> Vec<String> {
+    rows.iter()
+        .map(|r| match r.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            other => panic!("unexpected name: {other:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn built_in_code() -> Result<(), Box<dyn Error>> {
+    let db = setup("dyn_query_built_sdb/")?;
+
+    // the stored ages are `u8`, numbers are compared by their value
+    let rows = db.query_dyn("Employee", &Expr::field("age").gt(Value::I64(30)))?;
+    assert_eq!(names(&rows), ["Ada", "Alan", "Linus"]);
+    assert_eq!(rows[0].get("age"), Some(&Value::U8(36)));
+
+    let filter = Expr::field("name")
+        .starts_with(Value::String("A".into()))
+        .and(!Expr::field("id").in_list([Value::U32(2)]));
+    assert_eq!(names(&db.query_dyn("Employee", &filter)?), ["Ada"]);
+
+    Ok(())
+}
+
+#[test]
+fn parsed_from_text() -> Result<(), Box<dyn Error>> {
+    let db = setup("dyn_query_parsed_sdb/")?;
+
+    let query = |src: &str| -> Result<Vec<String>, DbError> {
+        Ok(names(&db.query_dyn("Employee", &Expr::parse(src)?)?))
+    };
+    assert_eq!(query("age BETWEEN 30 AND 45")?, ["Ada", "Alan"]);
+    assert_eq!(
+        query("name LIKE '%a%' OR id = 4")?,
+        ["Ada", "Alan", "Grace", "Linus"]
+    );
+    assert_eq!(query("NOT (age < 40 OR name = 'Linus')")?, ["Alan"]);
+    assert_eq!(query("ends_with(name, 'e') AND age != 0")?, ["Grace"]);
+    assert_eq!(query("age > 1000")?, Vec::<String>::new());
+
+    Ok(())
+}
+
+#[test]
+fn checked_against_schema() -> Result<(), Box<dyn Error>> {
+    let db = setup("dyn_query_checked_sdb/")?;
+
+    let err = |src: &str| {
+        db.query_dyn("Employee", &Expr::parse(src).unwrap())
+            .unwrap_err()
+    };
+    assert_eq!(
+        err("salary > 3"),
+        DbError::ParseError("unknown field: salary".into())
+    );
+    assert_eq!(
+        err("name > 3"),
+        DbError::ParseError("cannot compare name and 3".into())
+    );
+    assert_eq!(
+        err("contains(age, 'x')"),
+        DbError::ParseError("contains needs text operands: contains(age, 'x')".into())
+    );
+    assert_eq!(
+        err("tags < 'x'"),
+        DbError::ParseError("cannot compare tags and 'x'".into())
+    );
+    assert_eq!(
+        err("age"),
+        DbError::ParseError("age is not a condition".into())
+    );
+    assert_eq!(err("age = 1 OR 2").kind(), ErrorKind::Parse);
+
+    assert_eq!(
+        db.query_dyn("Unknown", &Expr::parse("a = 1")?)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::TypeNotFound
+    );
+
+    Ok(())
+}
+
+#[test]
+fn typed_results() -> Result<(), Box<dyn Error>> {
+    let db = setup("dyn_query_typed_sdb/")?;
+
+    let filter = Expr::parse("age < 30")?;
+    let grace = db.query_dyn_as::<Employee>(&filter)?;
+    assert_eq!(grace.len(), 1);
+    assert_eq!(grace[0].name, "Grace");
+
+    let rows = db.query_dyn("Employee", &filter)?;
+    assert_eq!(rows[0].clone().into_entity::<Employee>()?, grace[0]);
+    assert_eq!(
+        rows[0].to_string(),
+        r#"{id: 3, name: "Grace", age: 29, tags: []}"#
+    );
+
+    assert_eq!(
+        db.query_dyn_as::<Employee>(&Expr::parse("salary = 1")?)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Parse
+    );
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::error::Error;
+
+use somedb::{
+    db::Database,
+    entity,
+    gen_query::{ExprInfo, GenExpr, lit},
+    query::{DbIterator, Plan},
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Visit {
+    #[entity_id(auto_generate)]
+    id: u32,
+    page: String,
+    seconds: u32,
+}
+
+fn setup(dir: &str) -> Result<Database, Box<dyn Error>> {
+    let mut db = Database::new(dir, true)?;
+    for i in 0..30 {
+        db.store(Visit {
+            id: 0,
+            page: format!("/page/{}", i % 3),
+            seconds: i,
+        })?;
+    }
+    Ok(db)
+}
+
+#[test]
+fn plan_tree() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("explain_tree_sdb/")?;
+
+    let query = db
+        .query_mut::<Visit>()?
+        .filter(|v| v.seconds().gt(10).land(v.page().starts_with("/page/1")))
+        .map(|v| v);
+    assert_eq!(
+        query.explain().to_string(),
+        "map, ~3 rows\n  \
+           filter (seconds > <u32>) && page.starts_with(<&str>), ~3 rows\n    \
+             full scan of Visit, 30 rows\n"
+    );
+
+    let Plan::Map { input } = query.explain() else {
+        panic!("expected a map");
+    };
+    let Plan::Filter { predicate, input } = *input else {
+        panic!("expected a filter");
+    };
+    assert!(matches!(predicate, ExprInfo::Binary("&&", ..)));
+    assert_eq!(input.estimated_rows(), Some(30));
+
+    // explaining doesn't consume the query
+    assert_eq!(query.collect_vec().len(), 6);
+
+    Ok(())
+}
+
+#[test]
+fn estimates() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("explain_estimates_sdb/")?;
+
+    let estimate = |plan: Plan| plan.estimated_rows().unwrap();
+
+    // the id is unique
+    let query = db.query_mut::<Visit>()?.filter(|v| v.id().eq(7));
+    assert_eq!(estimate(query.explain()), 1);
+    let query = db.query_mut::<Visit>()?.filter(|v| v.id().eq(7).not());
+    assert_eq!(estimate(query.explain()), 29);
+
+    let query = db.query_mut::<Visit>()?.filter(|v| {
+        v.seconds()
+            .between(3, 8)
+            .lor(v.page().eq(lit("/".to_string())))
+    });
+    assert_eq!(estimate(query.explain()), 10);
+
+    let query = db
+        .query_mut::<Visit>()?
+        .filter(|v| v.seconds().rem(2).eq(0))
+        .filter(|v| v.page().len().lt(10));
+    assert_eq!(
+        query.explain().to_string(),
+        "filter page.len() < <usize>, ~1 rows\n  \
+           filter (seconds % <u32>) == <u32>, ~3 rows\n    \
+             full scan of Visit, 30 rows\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn id_lookup() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("explain_lookup_sdb/")?;
+
+    let query = db.query_mut::<Visit>()?.filter(|v| v.id().eq(7));
+    assert_eq!(
+        query.explain().to_string(),
+        "lookup of Visit by id == <u32>, 1 rows\n"
+    );
+    let visits = query.collect_vec();
+    assert_eq!(visits.len(), 1);
+    assert_eq!(visits[0].seconds, 6);
+
+    // the rest of the conjunction is filtered after the lookup
+    let query = db
+        .query_mut::<Visit>()?
+        .filter(|v| v.seconds().gt(3).land(lit(8).eq(v.id())));
+    assert_eq!(
+        query.explain().to_string(),
+        "filter seconds > <u32>, ~0 rows\n  \
+           lookup of Visit by id == <u32>, 1 rows\n"
+    );
+    assert_eq!(query.collect_vec().len(), 1);
+    let query = db
+        .query_mut::<Visit>()?
+        .filter(|v| v.seconds().gt(10).land(v.id().eq(8)));
+    assert!(query.collect_vec().is_empty());
+
+    // ids compared with other fields or after a map are scanned
+    let query = db.query_mut::<Visit>()?.filter(|v| v.id().eq(v.seconds()));
+    assert!(matches!(
+        query.explain(),
+        Plan::Filter { input, .. } if matches!(*input, Plan::FullScan { .. })
+    ));
+    let query = db.query_mut::<Visit>()?.map(|v| v).filter(|v| v.id().eq(2));
+    assert!(matches!(query.explain(), Plan::Filter { .. }));
+    assert_eq!(query.collect_vec().len(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::{error::Error, fs, path::Path};
+
+use somedb::{
+    db::{Database, DbError},
+    entity,
+    entity_meta::FORMAT_VERSION,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Person {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    age: u8,
+}
+
+/// Copies a fixture so the tests never change the checked in files.
+fn open_fixture(fixture: &str, dir: &str) -> Result<Database, Box<dyn Error>> {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir)?;
+    for entry in fs::read_dir(Path::new("tests/fixtures").join(fixture))? {
+        let entry = entry?;
+        fs::copy(entry.path(), Path::new(dir).join(entry.file_name()))?;
+    }
+    Ok(Database::new(dir, false)?)
+}
+
+fn expected() -> Vec<Person> {
+    vec![
+        Person {
+            id: 1,
+            name: "Ada".into(),
+            age: 36,
+        },
+        Person {
+            id: 2,
+            name: "Alan".into(),
+            age: 41,
+        },
+    ]
+}
+
+#[test]
+fn read_format_1_without_checksums() -> Result<(), Box<dyn Error>> {
+    let db = open_fixture("format_1", "format_1_sdb/")?;
+    assert_eq!(db.read_all::<Person>()?, expected());
+
+    let table = &db.catalog()?[0];
+    assert_eq!(table.format_version, Some(1));
+    assert!(table.needs_upgrade());
+    assert!(db.verify()?[0].is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn read_format_1_with_checksums() -> Result<(), Box<dyn Error>> {
+    let db = open_fixture("format_1_checksums", "format_1_checksums_sdb/")?;
+    assert_eq!(db.read_all::<Person>()?, expected());
+
+    let table = &db.catalog()?[0];
+    assert_eq!(table.name(), "Person");
+    assert_eq!(table.format_version, Some(1));
+    assert!(db.verify()?[0].is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn explicit_upgrade() -> Result<(), Box<dyn Error>> {
+    let mut db = open_fixture("format_1", "format_upgrade_sdb/")?;
+
+    assert_eq!(db.upgrade()?.len(), 1);
+    assert!(db.upgrade()?.is_empty());
+
+    let table = &db.catalog()?[0];
+    assert_eq!(table.format_version, Some(FORMAT_VERSION));
+    assert!(!table.needs_upgrade());
+    assert!(db.verify()?[0].is_ok());
+    assert_eq!(db.read_all::<Person>()?, expected());
+
+    Ok(())
+}
+
+#[test]
+fn writing_upgrades_automatically() -> Result<(), Box<dyn Error>> {
+    let mut db = open_fixture("format_1", "format_auto_upgrade_sdb/")?;
+
+    let stored = db.store(Person {
+        id: 0,
+        name: "Grace".into(),
+        age: 85,
+    })?;
+    assert_eq!(stored.id, 3);
+
+    assert_eq!(db.catalog()?[0].format_version, Some(FORMAT_VERSION));
+    assert_eq!(db.read_all::<Person>()?.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn reject_newer_formats() -> Result<(), Box<dyn Error>> {
+    let db = open_fixture("format_1", "format_newer_sdb/")?;
+    let path = &db.catalog()?[0].path;
+
+    // the version block of the fixture contains "0.1.2"
+    let mut data = fs::read(path)?;
+    let pos = data.windows(5).position(|w| w == b"0.1.2").unwrap();
+    data[pos..pos + 5].copy_from_slice(b"0.2.0");
+    fs::write(path, data)?;
+
+    assert_eq!(
+        db.read_all::<Person>().unwrap_err(),
+        DbError::InvalidFileVersion {
+            found: "0.2.0".to_string()
+        }
+    );
+    assert_eq!(db.catalog()?[0].format_version, None);
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use std::{error::Error, fs};
+
+use somedb::{
+    db::{Database, DbError, ErrorKind},
+    entity,
+    gen_query::GenExpr,
+    query::DbIterator,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Article {
+    #[entity_id(auto_generate)]
+    id: u32,
+    #[fulltext]
+    title: String,
+    #[fulltext]
+    body: String,
+    author: String,
+}
+
+fn article(title: &str, body: &str) -> Article {
+    Article {
+        id: 0,
+        title: title.to_string(),
+        body: body.to_string(),
+        author: "someone".to_string(),
+    }
+}
+
+fn setup(dir: &str) -> Result<Database, Box<dyn Error>> {
+    let mut db = Database::new(dir, true)?;
+    db.store(article(
+        "Writing a database in Rust",
+        "Storing entities on disk with a small Rust database.",
+    ))?;
+    db.store(article(
+        "Rust iterators",
+        "Iterators are lazy. Rust iterators are fast, rust rust rust.",
+    ))?;
+    db.store(article(
+        "Gardening",
+        "Tomatoes need a lot of sun and some patience.",
+    ))?;
+    Ok(db)
+}
+
+fn ids(db: &Database, field: &str, query: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+    Ok(db
+        .search::<Article>(field, query)?
+        .into_iter()
+        .map(|hit| hit.entity.id)
+        .collect())
+}
+
+#[test]
+fn queries() -> Result<(), Box<dyn Error>> {
+    let db = setup("fulltext_queries_sdb/")?;
+
+    // the article repeating the word ranks first
+    assert_eq!(ids(&db, "body", "rust")?, [2, 1]);
+    assert_eq!(ids(&db, "title", "RUST")?, [2, 1]);
+    assert_eq!(ids(&db, "body", "rust database")?, [1]);
+    assert_eq!(ids(&db, "body", "iter*")?, [2]);
+    assert_eq!(ids(&db, "body", "tomato*")?, [3]);
+    assert_eq!(ids(&db, "body", r#""rust database""#)?, [1]);
+    assert_eq!(ids(&db, "body", r#""database rust""#)?, [] as [u32; 0]);
+    assert_eq!(ids(&db, "title", "writing-a-database")?, [1]);
+    assert_eq!(ids(&db, "body", "tomatoes rust")?, [] as [u32; 0]);
+    assert_eq!(ids(&db, "body", "")?, [] as [u32; 0]);
+
+    let hits = db.search::<Article>("body", "rust")?;
+    assert!(hits[0].score > hits[1].score);
+    assert_eq!(hits[1].entity.title, "Writing a database in Rust");
+
+    Ok(())
+}
+
+#[test]
+fn errors() -> Result<(), Box<dyn Error>> {
+    let db = setup("fulltext_errors_sdb/")?;
+
+    match db.search::<Article>("author", "someone").unwrap_err() {
+        DbError::Validation {
+            field: Some(field), ..
+        } => assert_eq!(field, "author"),
+        err => panic!("unexpected error {err:?}"),
+    }
+    assert_eq!(
+        db.search::<Article>("body", "\"rust").unwrap_err().kind(),
+        ErrorKind::Parse
+    );
+
+    let empty = Database::new("fulltext_errors_empty_sdb/", true)?;
+    assert_eq!(
+        empty.search::<Article>("body", "rust").unwrap_err().kind(),
+        ErrorKind::TypeNotFound
+    );
+
+    Ok(())
+}
+
+#[test]
+fn typed_writes() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("fulltext_typed_sdb/")?;
+
+    let mut updated = db.find_by_id::<Article>(3)?.unwrap();
+    updated.body = "Growing rust resistant tomatoes".to_string();
+    db.update_entity(updated)?;
+    assert_eq!(ids(&db, "body", "tomato*")?, [3]);
+    assert_eq!(ids(&db, "body", "rust")?, [2, 3, 1]);
+
+    db.delte_entity_by_id::<Article>(2)?;
+    assert_eq!(ids(&db, "body", "rust")?, [3, 1]);
+
+    db.query_mut::<Article>()?
+        .filter(|a| a.id().neq(1))
+        .save_to_db()?;
+    assert_eq!(ids(&db, "body", "rust")?, [3]);
+
+    db.write_all(vec![Article {
+        id: 7,
+        ..article("Only one", "A rust article")
+    }])?;
+    assert_eq!(ids(&db, "body", "rust")?, [7]);
+
+    Ok(())
+}
+
+#[test]
+fn untyped_writes() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("fulltext_sql_sdb/")?;
+
+    db.execute("UPDATE Article SET body = 'nothing left' WHERE id = 2")?;
+    assert_eq!(ids(&db, "body", "rust")?, [1]);
+
+    db.execute(
+        "INSERT INTO Article (id, title, body, author) \
+         VALUES (4, 'Sql', 'rust from sql', 'me')",
+    )?;
+    assert_eq!(ids(&db, "body", "\"from sql\"")?, [4]);
+
+    db.execute("DELETE FROM Article WHERE id = 1")?;
+    assert_eq!(ids(&db, "body", "rust")?, [4]);
+
+    Ok(())
+}
+
+#[test]
+fn other_handles_and_restores() -> Result<(), Box<dyn Error>> {
+    let db = setup("fulltext_handles_sdb/")?;
+    let backups = "fulltext_handles_backups_sdb/";
+    let _ = fs::remove_dir_all(backups);
+    let snapshot = db.backup_to(backups)?;
+    assert_eq!(ids(&db, "body", "rust")?, [2, 1]);
+
+    let mut other = Database::new("fulltext_handles_sdb/", false)?;
+    other.store(article("Rust", "rust"))?;
+    assert_eq!(ids(&db, "body", "rust")?, [2, 4, 1]);
+
+    other.restore_from(backups, snapshot.time)?;
+    assert_eq!(ids(&db, "body", "rust")?, [2, 1]);
+    assert_eq!(ids(&other, "body", "rust")?, [2, 1]);
+
+    Ok(())
+}
+
+#[test]
+fn damaged_index() -> Result<(), Box<dyn Error>> {
+    let dir = "fulltext_damaged_sdb/";
+    let mut db = setup(dir)?;
+
+    let index = fs::read_dir(dir)?
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "fts"))
+        .expect("the table has an index");
+    fs::write(&index, b"garbage")?;
+    assert_eq!(ids(&db, "body", "rust")?, [2, 1]);
+
+    fs::remove_file(&index)?;
+    assert_eq!(ids(&db, "body", "rust")?, [2, 1]);
+    assert!(index.exists());
+
+    // an index that doesn't match the table anymore is rebuilt
+    let stale = fs::read(&index)?;
+    db.store(article("New", "rust"))?;
+    fs::write(&index, stale)?;
+    assert_eq!(ids(&db, "body", "rust")?, [2, 4, 1]);
+
+    Ok(())
+}
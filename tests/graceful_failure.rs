@@ -1,7 +1,7 @@
 use std::error::Error;
 
 use somedb::{
-    db::{Database, DbError},
+    db::{Database, DbError, ErrorKind},
     entity,
 };
 
@@ -12,14 +12,44 @@ struct Foo {
     id: u32,
 }
 
+#[derive(Debug)]
+#[entity]
+struct Bar {
+    #[entity_id]
+    id: u32,
+}
+
 #[test]
 fn load_without_create() -> Result<(), Box<dyn Error>> {
     let db = Database::default()?;
+    let err = db
+        .find_by_id::<Foo>(0)
+        .expect_err("find should not succed in this case");
+
+    assert_eq!(err.kind(), ErrorKind::TypeNotFound);
     assert_eq!(
-        db.find_by_id::<Foo>(0)
-            .expect_err("find should not succed in this case"),
-        DbError::TypeNotFound
+        err,
+        DbError::TypeNotFound {
+            table: std::any::type_name::<Foo>().to_string()
+        }
     );
 
     Ok(())
 }
+
+#[test]
+fn errors_include_context() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("graceful_failure_sdb/", true)?;
+    db.store(Bar { id: 7 })?;
+
+    let err = db.store(Bar { id: 7 }).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::IdExists);
+    assert!(err.to_string().contains("Bar"));
+    assert!(err.to_string().contains('7'));
+
+    let err = db.update_entity(Bar { id: 8 }).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::IdNotFound);
+    assert!(err.source().is_none());
+
+    Ok(())
+}
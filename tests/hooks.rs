@@ -0,0 +1,160 @@
+use std::error::Error;
+
+use somedb::{
+    db::{Database, DbError, ErrorKind},
+    entity,
+    entity::EntityHooks,
+    gen_query::GenExpr,
+    query::DbIterator,
+};
+
+#[entity(hooks)]
+#[derive(Debug, PartialEq)]
+struct User {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    /// Filled in by the hooks.
+    revision: u32,
+    name_len: usize,
+}
+
+impl EntityHooks for User {
+    fn before_insert(&mut self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("the name must not be empty".to_string());
+        }
+        self.revision = 1;
+        Ok(())
+    }
+
+    fn before_update(&mut self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("the name must not be empty".to_string());
+        }
+        self.revision += 1;
+        Ok(())
+    }
+
+    fn before_delete(&self) -> Result<(), String> {
+        if self.name == "admin" {
+            return Err("the admin can't be deleted".to_string());
+        }
+        Ok(())
+    }
+
+    fn after_load(&mut self) -> Result<(), String> {
+        self.name_len = self.name.len();
+        Ok(())
+    }
+}
+
+fn user(id: u32, name: &str, revision: u32) -> User {
+    User {
+        id,
+        name: name.into(),
+        revision,
+        name_len: 0,
+    }
+}
+
+#[test]
+fn hooks_fill_derived_fields() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("hooks_derived_sdb/", true)?;
+
+    let stored = db.store(user(0, "ada", 0))?;
+    assert_eq!(stored.revision, 1);
+
+    db.update_entity(user(stored.id, "ada lovelace", 1))?;
+    let loaded = db.find_by_id::<User>(stored.id)?.unwrap();
+    assert_eq!(loaded.revision, 2);
+    assert_eq!(loaded.name_len, 12);
+
+    // write_all updates existing entities and inserts new ones
+    db.write_all(vec![user(1, "ada", 2), user(2, "alan", 7)])?;
+    let revisions: Vec<_> = db.read_all::<User>()?.iter().map(|u| u.revision).collect();
+    assert_eq!(revisions, vec![3, 1]);
+
+    Ok(())
+}
+
+#[test]
+fn hooks_abort_operations() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("hooks_abort_sdb/", true)?;
+
+    let err = db.store(user(0, "", 0)).unwrap_err();
+    assert_eq!(
+        err,
+        DbError::Validation {
+            table: std::any::type_name::<User>().to_string(),
+            field: None,
+            message: "the name must not be empty".to_string(),
+        }
+    );
+
+    let admin = db.store(user(0, "admin", 0))?;
+    assert_eq!(
+        db.update_entity(user(admin.id, "", 1)).unwrap_err().kind(),
+        ErrorKind::Validation
+    );
+    assert_eq!(
+        db.delte_entity_by_id::<User>(admin.id).unwrap_err().kind(),
+        ErrorKind::Validation
+    );
+    assert_eq!(
+        db.write_all::<User>(vec![]).map_err(|e| e.kind()),
+        Err(ErrorKind::Validation)
+    );
+    assert_eq!(
+        db.delete_entity_store::<User>().map_err(|e| e.kind()),
+        Err(ErrorKind::Validation)
+    );
+
+    // nothing was changed by the failed operations
+    assert_eq!(db.read_all::<User>()?.len(), 1);
+    assert_eq!(db.read_all::<User>()?[0].revision, 1);
+
+    Ok(())
+}
+
+#[test]
+fn after_load_only_runs_for_returned_entities() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("hooks_after_load_sdb/", true)?;
+    db.store(user(0, "ada", 0))?;
+    db.store(user(0, "alan", 0))?;
+    db.update_entity(user(2, "alan turing", 1))?;
+
+    // the derived field is never written back
+    let raw = db.raw_read_all::<User>()?;
+    assert!(raw.entities.iter().all(|u| u.name_len == 0));
+    assert_eq!(db.read_all::<User>()?[1].name_len, 11);
+
+    Ok(())
+}
+
+#[test]
+fn saved_queries_run_hooks() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("hooks_saved_query_sdb/", true)?;
+    db.store(user(0, "ada", 0))?;
+    db.store(user(0, "admin", 0))?;
+    db.store(user(0, "alan", 0))?;
+
+    db.query_mut::<User>()?
+        .filter(|u| u.id().neq(3))
+        .save_to_db()?;
+    let revisions: Vec<_> = db.read_all::<User>()?.iter().map(|u| u.revision).collect();
+    assert_eq!(revisions, vec![2, 2]);
+
+    // removing the admin deletes it
+    assert_eq!(
+        db.query_mut::<User>()?
+            .filter(|u| u.id().eq(1))
+            .save_to_db()
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Validation
+    );
+    assert_eq!(db.read_all::<User>()?.len(), 2);
+
+    Ok(())
+}
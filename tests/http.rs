@@ -0,0 +1,227 @@
+use std::{
+    error::Error,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use somedb::{
+    db::Database,
+    entity,
+    http::{MAX_BODY_LEN, MAX_CONNECTIONS, MAX_HEADERS, MAX_LINE_LEN, RestApi},
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Sensor {
+    #[entity_id(auto_generate)]
+    id: u32,
+    room: String,
+    #[range(min = -50, max = 100)]
+    celsius: i16,
+}
+
+fn start(dir: &str) -> Result<SocketAddr, Box<dyn Error>> {
+    let mut api = RestApi::new(Database::new(dir, true)?);
+    api.register::<Sensor>();
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    thread::spawn(move || api.serve(listener));
+    Ok(addr)
+}
+
+/// Sends a request and returns the status code and the body of the response.
+fn request(addr: SocketAddr, method: &str, target: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{method} {target} HTTP/1.1\r\nhost: localhost\r\ncontent-length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+#[test]
+fn crud() -> Result<(), Box<dyn Error>> {
+    let addr = start("http_crud_sdb/")?;
+
+    assert_eq!(
+        request(addr, "GET", "/tables/Sensor", ""),
+        (200, "[]".into())
+    );
+
+    let (status, body) = request(
+        addr,
+        "POST",
+        "/tables/Sensor",
+        r#"{"room": "kitchen", "celsius": 21}"#,
+    );
+    assert_eq!(status, 201);
+    assert_eq!(body, r#"{"id":1,"room":"kitchen","celsius":21}"#);
+    request(
+        addr,
+        "POST",
+        "/tables/Sensor",
+        r#"{"room": "living room", "celsius": 19}"#,
+    );
+
+    assert_eq!(
+        request(addr, "GET", "/tables/Sensor/2", ""),
+        (200, r#"{"id":2,"room":"living room","celsius":19}"#.into())
+    );
+
+    let (status, body) = request(
+        addr,
+        "PUT",
+        "/tables/Sensor/1",
+        r#"{"room": "kitchen", "celsius": -3}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(body, r#"{"id":1,"room":"kitchen","celsius":-3}"#);
+
+    assert_eq!(request(addr, "DELETE", "/tables/Sensor/2", "").0, 204);
+    assert_eq!(request(addr, "GET", "/tables/Sensor/2", "").0, 404);
+    assert_eq!(request(addr, "DELETE", "/tables/Sensor/2", "").0, 404);
+    assert_eq!(
+        request(addr, "GET", "/tables/Sensor", ""),
+        (200, r#"[{"id":1,"room":"kitchen","celsius":-3}]"#.into())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn filters() -> Result<(), Box<dyn Error>> {
+    let addr = start("http_filters_sdb/")?;
+    for (room, celsius) in [("kitchen", 21), ("living room", 19), ("cellar", 12)] {
+        let body = format!(r#"{{"room": "{room}", "celsius": {celsius}}}"#);
+        assert_eq!(request(addr, "POST", "/tables/Sensor", &body).0, 201);
+    }
+
+    let ids = |target: &str| {
+        let (status, body) = request(addr, "GET", target, "");
+        assert_eq!(status, 200, "{body}");
+        body.match_indices(r#""id":"#)
+            .map(|(i, _)| body[i + 5..].split(',').next().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(ids("/tables/Sensor?room=living+room"), ["2"]);
+    assert_eq!(ids("/tables/Sensor?room.ne=kitchen"), ["2", "3"]);
+    assert_eq!(ids("/tables/Sensor?celsius.gte=19"), ["1", "2"]);
+    assert_eq!(ids("/tables/Sensor?celsius.lt=20&id.gt=2"), ["3"]);
+    assert_eq!(ids("/tables/Sensor?room=%63ellar"), ["3"]);
+
+    assert_eq!(
+        request(addr, "GET", "/tables/Sensor?humidity=3", ""),
+        (400, r#"{"error":"unknown field: humidity"}"#.into())
+    );
+    assert_eq!(
+        request(addr, "GET", "/tables/Sensor?celsius=warm", "").0,
+        400
+    );
+
+    Ok(())
+}
+
+#[test]
+fn errors() -> Result<(), Box<dyn Error>> {
+    let addr = start("http_errors_sdb/")?;
+
+    assert_eq!(request(addr, "GET", "/tables/Unknown", "").0, 404);
+    assert_eq!(request(addr, "GET", "/other", "").0, 404);
+    assert_eq!(request(addr, "PATCH", "/tables/Sensor/1", "").0, 405);
+    assert_eq!(request(addr, "POST", "/tables/Sensor", "{").0, 400);
+    assert_eq!(
+        request(addr, "POST", "/tables/Sensor", r#"{"room": "x"}"#),
+        (400, r#"{"error":"missing field: celsius"}"#.into())
+    );
+
+    // constraints of the entity are checked
+    let (status, body) = request(
+        addr,
+        "POST",
+        "/tables/Sensor",
+        r#"{"room": "oven", "celsius": 250}"#,
+    );
+    assert_eq!(status, 400);
+    assert!(body.contains("celsius"), "{body}");
+
+    assert_eq!(
+        request(
+            addr,
+            "PUT",
+            "/tables/Sensor/7",
+            r#"{"room": "x", "celsius": 1}"#
+        )
+        .0,
+        404
+    );
+
+    // deeply nested bodies are refused instead of overflowing the stack
+    let nested = "[".repeat(100_000);
+    assert_eq!(request(addr, "POST", "/tables/Sensor", &nested).0, 400);
+    assert_eq!(request(addr, "GET", "/tables/Sensor", "").0, 200);
+
+    // the body isn't read if it is too large
+    let mut stream = TcpStream::connect(addr)?;
+    write!(
+        stream,
+        "POST /tables/Sensor HTTP/1.1\r\ncontent-length: {}\r\n\r\n",
+        MAX_BODY_LEN + 1
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    assert!(response.starts_with("HTTP/1.1 413 "), "{response}");
+
+    // neither are overlong lines or too many headers
+    let long_target = format!("/tables/{}", "x".repeat(MAX_LINE_LEN));
+    assert_eq!(request(addr, "GET", &long_target, "").0, 400);
+    let long_header = format!("x-long: {}\r\n", "x".repeat(MAX_LINE_LEN));
+    assert_eq!(raw_status(addr, &long_header), 431);
+    assert_eq!(
+        raw_status(addr, &"x-many: 1\r\n".repeat(MAX_HEADERS + 1)),
+        431
+    );
+    assert_eq!(
+        raw_status(addr, &"x-many: 1\r\n".repeat(MAX_HEADERS - 1)),
+        200
+    );
+
+    Ok(())
+}
+
+/// Sends a request for the sensors with extra headers and returns the status code.
+fn raw_status(addr: SocketAddr, headers: &str) -> u16 {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET /tables/Sensor HTTP/1.1\r\n{headers}\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response.split(' ').nth(1).unwrap().parse().unwrap()
+}
+
+#[test]
+fn connection_limit() -> Result<(), Box<dyn Error>> {
+    let addr = start("http_connections_sdb/")?;
+
+    let idle = (0..MAX_CONNECTIONS)
+        .map(|_| TcpStream::connect(addr))
+        .collect::<Result<Vec<_>, _>>()?;
+    let waiting = thread::spawn(move || request(addr, "GET", "/tables/Sensor", "").0);
+    thread::sleep(Duration::from_millis(200));
+    assert!(!waiting.is_finished());
+
+    // closed connections free their slot
+    drop(idle);
+    assert_eq!(waiting.join().unwrap(), 200);
+
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use std::error::Error;
+
+use somedb::{
+    db::{Database, ErrorKind},
+    entity,
+    format::{Format, IdMode},
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Book {
+    #[entity_id(auto_generate)]
+    id: u32,
+    title: String,
+    authors: Vec<String>,
+    pages: u16,
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Isbn {
+    #[entity_id]
+    id: u64,
+    book: u32,
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Member {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    email: Option<String>,
+    age: Option<u8>,
+}
+
+fn book(id: u32, title: &str, authors: &[&str]) -> Book {
+    Book {
+        id,
+        title: title.into(),
+        authors: authors.iter().map(|a| a.to_string()).collect(),
+        pages: 100,
+    }
+}
+
+fn books() -> Vec<Book> {
+    vec![
+        book(1, "Notes, on \"quotes\"", &["Ada"]),
+        book(2, "Line\nbreaks", &["Alan", "Grace"]),
+        book(3, "", &[]),
+    ]
+}
+
+#[test]
+fn roundtrip_all_formats() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("import_export_roundtrip_sdb/", true)?;
+    db.write_all(books())?;
+
+    for format in [Format::Json, Format::NdJson, Format::Csv] {
+        let mut out = Vec::new();
+        assert_eq!(db.export::<Book>(&mut out, format)?, 3);
+
+        let mut copy = Database::new("import_export_copy_sdb/", true)?;
+        assert_eq!(copy.import::<Book>(&out[..], format, IdMode::Preserve)?, 3);
+        assert_eq!(copy.read_all::<Book>()?, books(), "{format:?}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn export_formats() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("import_export_formats_sdb/", true)?;
+    db.write_all(books()[..2].to_vec())?;
+
+    let mut csv = Vec::new();
+    db.export::<Book>(&mut csv, Format::Csv)?;
+    assert_eq!(
+        String::from_utf8(csv)?,
+        "id,title,authors,pages\n\
+         1,\"Notes, on \"\"quotes\"\"\",\"[\"\"Ada\"\"]\",100\n\
+         2,\"Line\nbreaks\",\"[\"\"Alan\"\",\"\"Grace\"\"]\",100\n"
+    );
+
+    let mut ndjson = Vec::new();
+    db.export::<Book>(&mut ndjson, Format::NdJson)?;
+    assert_eq!(
+        String::from_utf8(ndjson)?.lines().next(),
+        Some(r#"{"id":1,"title":"Notes, on \"quotes\"","authors":["Ada"],"pages":100}"#)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn optional_fields() -> Result<(), Box<dyn Error>> {
+    let members = || {
+        vec![
+            Member {
+                id: 1,
+                name: "Ada".into(),
+                email: Some("ada@example.com".into()),
+                age: None,
+            },
+            Member {
+                id: 2,
+                name: "Alan".into(),
+                email: None,
+                age: Some(41),
+            },
+        ]
+    };
+    let mut db = Database::new("import_export_optional_sdb/", true)?;
+    db.write_all(members())?;
+
+    let mut ndjson = Vec::new();
+    db.export::<Member>(&mut ndjson, Format::NdJson)?;
+    assert_eq!(
+        String::from_utf8(ndjson)?,
+        "{\"id\":1,\"name\":\"Ada\",\"email\":\"ada@example.com\",\"age\":null}\n\
+         {\"id\":2,\"name\":\"Alan\",\"email\":null,\"age\":41}\n"
+    );
+
+    let mut csv = Vec::new();
+    db.export::<Member>(&mut csv, Format::Csv)?;
+    assert_eq!(
+        String::from_utf8(csv)?,
+        "id,name,email,age\n1,Ada,ada@example.com,\n2,Alan,,41\n"
+    );
+
+    for format in [Format::Json, Format::NdJson, Format::Csv] {
+        let mut out = Vec::new();
+        db.export::<Member>(&mut out, format)?;
+        let mut copy = Database::new("import_export_optional_copy_sdb/", true)?;
+        copy.import::<Member>(&out[..], format, IdMode::Preserve)?;
+        assert_eq!(copy.read_all::<Member>()?, members(), "{format:?}");
+    }
+
+    // missing optional fields are imported as `None`
+    let mut copy = Database::new("import_export_optional_missing_sdb/", true)?;
+    copy.import::<Member>(
+        &br#"{"id": 3, "name": "Grace"}"#[..],
+        Format::NdJson,
+        IdMode::Preserve,
+    )?;
+    assert_eq!(copy.find_by_id::<Member>(3)?.unwrap().email, None);
+
+    Ok(())
+}
+
+#[test]
+fn import_modes() -> Result<(), Box<dyn Error>> {
+    let fixture = r#"
+        {"id": 10, "title": "a", "authors": [], "pages": 1}
+        {"id": 20, "title": "b", "authors": [], "pages": 2}
+    "#;
+
+    // generated ids are regenerated by default
+    let mut db = Database::new("import_export_modes_sdb/", true)?;
+    db.store(book(0, "existing", &[]))?;
+    db.import::<Book>(fixture.as_bytes(), Format::NdJson, IdMode::Auto)?;
+    assert_eq!(db.read_all_ids::<Book>()?, vec![1, 2, 3]);
+
+    // preserved ids continue the id sequence
+    db.import::<Book>(fixture.as_bytes(), Format::NdJson, IdMode::Preserve)?;
+    assert_eq!(db.store(book(0, "next", &[]))?.id, 21);
+    assert_eq!(
+        db.import::<Book>(fixture.as_bytes(), Format::NdJson, IdMode::Preserve)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::IdExists
+    );
+
+    // manual ids are kept by default
+    let isbns = "id,book\n9780262510875,1\n9780131103627,2\n";
+    db.import::<Isbn>(isbns.as_bytes(), Format::Csv, IdMode::Auto)?;
+    assert_eq!(
+        db.read_all_ids::<Isbn>()?,
+        vec![9780262510875, 9780131103627]
+    );
+    assert_eq!(
+        db.import::<Isbn>(isbns.as_bytes(), Format::Csv, IdMode::Auto)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::IdExists
+    );
+
+    Ok(())
+}
+
+#[test]
+fn generated_ids_can_be_left_out() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("import_export_no_ids_sdb/", true)?;
+    let rows = r#"{"title": "a", "authors": [], "pages": 1}"#;
+    db.import::<Book>(rows.as_bytes(), Format::NdJson, IdMode::Auto)?;
+    let rows = "title,authors,pages\nb,[],2\n";
+    db.import::<Book>(rows.as_bytes(), Format::Csv, IdMode::Regenerate)?;
+    assert_eq!(db.read_all_ids::<Book>()?, vec![1, 2]);
+
+    // ids that are kept can't be left out
+    assert_eq!(
+        db.import::<Book>(rows.as_bytes(), Format::Csv, IdMode::Preserve)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Parse
+    );
+    assert_eq!(
+        db.import::<Isbn>("book\n1\n".as_bytes(), Format::Csv, IdMode::Auto)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Parse
+    );
+
+    // duplicates within the imported rows are found as well
+    let isbns = "id,book\n7,1\n7,2\n";
+    assert_eq!(
+        db.import::<Isbn>(isbns.as_bytes(), Format::Csv, IdMode::Auto)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::IdExists
+    );
+    assert!(db.read_all::<Isbn>()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn import_without_rust_types() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("import_export_dyn_sdb/", true)?;
+    db.store(book(0, "existing", &[]))?;
+    let table = db.table_info("Book")?.unwrap();
+
+    let rows = "title,authors,pages\na,[],1\nb,[],2\n";
+    assert_eq!(
+        db.import_dyn(&table, rows.as_bytes(), Format::Csv, IdMode::Auto)?,
+        2
+    );
+    let rows = r#"{"id": 9, "title": "c", "authors": [], "pages": 3}"#;
+    db.import_dyn(&table, rows.as_bytes(), Format::NdJson, IdMode::Preserve)?;
+    assert_eq!(
+        db.import_dyn(&table, rows.as_bytes(), Format::NdJson, IdMode::Preserve)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::IdExists
+    );
+
+    // the typed import continues after the imported ids
+    db.import::<Book>(rows.as_bytes(), Format::NdJson, IdMode::Auto)?;
+    assert_eq!(db.read_all_ids::<Book>()?, vec![1, 2, 3, 9, 10]);
+
+    Ok(())
+}
+
+#[test]
+fn invalid_input_is_a_parse_error() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("import_export_invalid_sdb/", true)?;
+    for (input, format) in [
+        ("{\"id\": 1}", Format::Json),
+        ("[{\"id\": 1, \"title\": 2}]", Format::Json),
+        ("id,title\n1,a\n", Format::Csv),
+    ] {
+        assert_eq!(
+            db.import::<Book>(input.as_bytes(), format, IdMode::Auto)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::Parse
+        );
+    }
+    assert!(db.read_all::<Book>()?.is_empty());
+    Ok(())
+}
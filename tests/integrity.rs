@@ -0,0 +1,96 @@
+use std::{error::Error, fs};
+
+use somedb::{
+    db::{Database, DbError, ErrorKind},
+    entity,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Person {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+}
+
+fn corrupt(db: &Database, f: impl FnOnce(&mut Vec<u8>)) -> Result<(), Box<dyn Error>> {
+    let path = &db.catalog()?[0].path;
+    let mut data = fs::read(path)?;
+    f(&mut data);
+    fs::write(path, data)?;
+    Ok(())
+}
+
+#[test]
+fn repair_corrupted_row() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("integrity_row_sdb/", true)?;
+    for name in ["aaaa", "bbbb", "cccc"] {
+        db.store(Person {
+            id: 0,
+            name: name.into(),
+        })?;
+    }
+
+    corrupt(&db, |data| {
+        let pos = data.windows(4).position(|w| w == b"bbbb").unwrap();
+        data[pos] = 0xFF;
+    })?;
+
+    assert_eq!(
+        db.read_all::<Person>().unwrap_err().kind(),
+        ErrorKind::ChecksumMismatch
+    );
+
+    let report = &db.verify()?[0];
+    assert!(!report.is_ok());
+    assert_eq!(report.intact_rows, 2);
+    assert!(
+        report
+            .corruptions
+            .iter()
+            .any(|c| c.row == Some(1) && c.field.as_deref() == Some("name"))
+    );
+
+    db.repair()?;
+
+    let names: Vec<_> = db
+        .read_all::<Person>()?
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    assert_eq!(names, vec!["aaaa", "cccc"]);
+    assert!(db.verify()?[0].is_ok());
+
+    // the last id is kept so no ids are reused
+    assert_eq!(
+        db.store(Person {
+            id: 0,
+            name: "dddd".into()
+        })?
+        .id,
+        4
+    );
+
+    Ok(())
+}
+
+#[test]
+fn truncated_file_does_not_panic() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("integrity_truncated_sdb/", true)?;
+    for name in ["aaaa", "bbbb"] {
+        db.store(Person {
+            id: 0,
+            name: name.into(),
+        })?;
+    }
+
+    corrupt(&db, |data| data.truncate(data.len() - 30))?;
+
+    assert!(matches!(
+        db.read_all::<Person>().unwrap_err(),
+        DbError::Decode { .. }
+    ));
+    assert!(!db.verify()?[0].is_ok());
+
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use std::{
+    error::Error,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    thread,
+};
+
+use somedb::{
+    db::{DbError, ErrorKind},
+    dyn_query::Expr,
+    entity,
+    remote::RemoteDatabase,
+    value::Value,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Order {
+    #[entity_id(auto_generate)]
+    id: u32,
+    item: String,
+    #[range(min = 1, max = 100)]
+    quantity: u32,
+}
+
+fn order(item: &str, quantity: u32) -> Order {
+    Order {
+        id: 0,
+        item: item.into(),
+        quantity,
+    }
+}
+
+/// A `somedb-server` process that is killed when dropped.
+struct Server {
+    process: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start(dir: &str, listen: &[&str]) -> Self {
+        let _ = std::fs::remove_dir_all(dir);
+        let mut process = Command::new(env!("CARGO_BIN_EXE_somedb-server"))
+            .args(["--dir", dir])
+            .args(listen)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let mut line = String::new();
+        BufReader::new(process.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let addr = line
+            .trim()
+            .strip_prefix("listening on ")
+            .unwrap()
+            .to_string();
+        Self { process, addr }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+#[test]
+fn same_operations_as_database() -> Result<(), Box<dyn Error>> {
+    let server = Server::start("remote_ops_sdb/", &["--listen", "127.0.0.1:0"]);
+    let mut db = RemoteDatabase::connect(&server.addr)?;
+
+    assert_eq!(
+        db.read_all::<Order>().unwrap_err().kind(),
+        ErrorKind::TypeNotFound
+    );
+
+    let first = db.store(order("apple", 3))?;
+    let second = db.store(order("pear", 1))?;
+    assert_eq!((first.id, second.id), (1, 2));
+    assert_eq!(db.find_by_id::<Order>(1)?, Some(first));
+
+    db.update_entity(Order {
+        quantity: 5,
+        ..second.clone()
+    })?;
+    assert_eq!(db.find_by_id::<Order>(2)?.unwrap().quantity, 5);
+    assert_eq!(
+        db.update_entity(Order { id: 9, ..second })
+            .unwrap_err()
+            .kind(),
+        ErrorKind::IdNotFound
+    );
+
+    // constraints are checked before anything is sent
+    assert!(matches!(
+        db.store(order("plum", 0)),
+        Err(DbError::Validation { .. })
+    ));
+
+    db.delete_entity_by_id::<Order>(1)?;
+    assert_eq!(db.read_all_ids::<Order>()?, vec![2]);
+
+    let table = db.table_info("Order")?.unwrap();
+    assert_eq!(table.row_count, 1);
+    assert_eq!(table.last_id.unwrap().to_string(), "2");
+
+    db.write_all(vec![order("fig", 7)])?;
+    assert_eq!(db.read_all::<Order>()?, vec![order("fig", 7)]);
+
+    db.delete_entity_store::<Order>()?;
+    assert!(db.catalog()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn queries_run_on_the_server() -> Result<(), Box<dyn Error>> {
+    let server = Server::start("remote_query_sdb/", &["--listen", "127.0.0.1:0"]);
+    let mut db = RemoteDatabase::connect(&server.addr)?;
+
+    let filter = Expr::field("quantity").gt(Expr::value(Value::U32(2)));
+    assert_eq!(
+        db.query_dyn_as::<Order>(&filter).unwrap_err().kind(),
+        ErrorKind::TypeNotFound
+    );
+
+    db.write_all(vec![order("apple", 3), order("pear", 1), order("fig", 7)])?;
+    let orders = db.query_dyn_as::<Order>(&filter)?;
+    assert_eq!(
+        orders.iter().map(|o| o.item.as_str()).collect::<Vec<_>>(),
+        ["apple", "fig"]
+    );
+
+    let filter = Expr::parse("item LIKE 'p%' OR quantity = 7")?;
+    assert_eq!(db.query_dyn_as::<Order>(&filter)?.len(), 2);
+
+    assert_eq!(
+        db.query_dyn_as::<Order>(&Expr::parse("price > 3")?)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Parse
+    );
+
+    Ok(())
+}
+
+#[test]
+fn concurrent_clients_do_not_lose_changes() -> Result<(), Box<dyn Error>> {
+    let server = Server::start("remote_concurrent_sdb/", &["--listen", "127.0.0.1:0"]);
+
+    let clients: Vec<_> = (0..4)
+        .map(|client| {
+            let addr = server.addr.clone();
+            thread::spawn(move || -> Result<(), DbError> {
+                let mut db = RemoteDatabase::connect(addr)?;
+                for i in 1..=10 {
+                    db.store(order(&format!("{client}-{i}"), i))?;
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    for client in clients {
+        client.join().unwrap()?;
+    }
+
+    let db = RemoteDatabase::connect(&server.addr)?;
+    let mut ids = db.read_all_ids::<Order>()?;
+    ids.sort();
+    assert_eq!(ids, (1..=40).collect::<Vec<_>>());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket() -> Result<(), Box<dyn Error>> {
+    let server = Server::start("remote_unix_sdb/", &["--unix", "remote_unix_sdb.sock"]);
+    let mut db = RemoteDatabase::connect_unix(&server.addr)?;
+
+    let stored = db.store(order("kiwi", 2))?;
+    assert_eq!(db.read_all::<Order>()?, vec![stored]);
+
+    drop(server);
+    std::fs::remove_file("remote_unix_sdb.sock")?;
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use std::{error::Error, fs, path::Path};
+
+use somedb::{
+    db::{Database, DbError},
+    entity,
+    entity::Entity,
+    schema::{Compatibility, FieldType, TableSchema},
+    storable::Storable,
+};
+
+/// The fixture was written by this entity without the fields that have a default value.
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Person {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    #[default]
+    age: u8,
+    #[default = "unknown"]
+    city: String,
+    #[default(vec!["new".to_string()])]
+    tags: Vec<String>,
+}
+
+fn open_fixture(dir: &str) -> Result<Database, Box<dyn Error>> {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir)?;
+    for entry in fs::read_dir("tests/fixtures/schema_v1")? {
+        let entry = entry?;
+        fs::copy(entry.path(), Path::new(dir).join(entry.file_name()))?;
+    }
+    Ok(Database::new(dir, false)?)
+}
+
+fn person(id: u32, name: &str) -> Person {
+    Person {
+        id,
+        name: name.into(),
+        age: 0,
+        city: "unknown".into(),
+        tags: vec!["new".into()],
+    }
+}
+
+#[test]
+fn read_rows_without_new_fields() -> Result<(), Box<dyn Error>> {
+    let mut db = open_fixture("schema_extension_sdb/")?;
+    assert_eq!(
+        db.read_all::<Person>()?,
+        vec![person(1, "Ada"), person(2, "Alan")]
+    );
+
+    // the stored schema only has the old fields until the table is written
+    let stored = db.table_info("Person")?.unwrap().schema.unwrap();
+    assert_eq!(stored.fields.len(), 2);
+    assert_eq!(
+        Person::table_schema().compatibility(&stored),
+        Compatibility::Extension
+    );
+
+    let grace = db.store(Person {
+        age: 85,
+        city: "Arlington".into(),
+        ..person(0, "Grace")
+    })?;
+    assert_eq!(grace.id, 3);
+
+    let stored = db.table_info("Person")?.unwrap().schema.unwrap();
+    assert_eq!(stored, Person::table_schema());
+    assert_eq!(db.read_all::<Person>()?.len(), 3);
+    assert_eq!(db.find_by_id::<Person>(3)?, Some(grace));
+    assert!(db.verify()?.iter().all(|r| r.is_ok()));
+
+    Ok(())
+}
+
+#[test]
+fn default_fields_keep_the_type_hash() -> Result<(), Box<dyn Error>> {
+    let db = open_fixture("schema_type_hash_sdb/")?;
+    let table = &db.catalog()?[0];
+    assert_eq!(table.type_hash, Person::type_hash());
+    Ok(())
+}
+
+#[test]
+fn breaking_changes_are_rejected() -> Result<(), Box<dyn Error>> {
+    let db = open_fixture("schema_breaking_sdb/")?;
+    let table = &db.catalog()?[0];
+
+    // pretend the data was written with a different type for a default field
+    let mut schema: TableSchema = table.schema.clone().unwrap();
+    schema
+        .fields
+        .extend(Person::table_schema().fields[2..].iter().cloned());
+    schema.fields[2].ty = FieldType::U64;
+    assert_eq!(
+        Person::table_schema().compatibility(&schema),
+        Compatibility::Breaking
+    );
+    fs::write(table.path.with_extension("schema"), schema.encoded())?;
+
+    assert_eq!(
+        db.read_all::<Person>().unwrap_err(),
+        DbError::SchemaMismatch {
+            table: std::any::type_name::<Person>().to_string()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn deleted_tables_leave_no_schema() -> Result<(), Box<dyn Error>> {
+    let mut db = open_fixture("schema_delete_sdb/")?;
+    let schema_path = db.catalog()?[0].path.with_extension("schema");
+    db.store(person(0, "ada"))?;
+    assert!(schema_path.exists());
+
+    db.delete_entity_store::<Person>()?;
+    assert!(db.catalog()?.is_empty());
+    assert!(!schema_path.exists());
+
+    // the table doesn't come back when the database is opened again
+    let db = Database::new("schema_delete_sdb/", false)?;
+    assert!(db.catalog()?.is_empty());
+
+    Ok(())
+}
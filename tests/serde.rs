@@ -0,0 +1,160 @@
+use std::{collections::BTreeMap, error::Error};
+
+use serde::{Deserialize, Serialize};
+use somedb::{
+    Storable,
+    byte_reader::ByteReader,
+    db::{Database, ErrorKind},
+    entity,
+    gen_query::GenExpr,
+    query::DbIterator,
+    schema::FieldType,
+    serde::{Serde, decoded, encoded},
+    storable::Storable,
+    value::Value,
+};
+
+/// A type from another crate that only implements serde's traits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Profile {
+    nickname: Option<String>,
+    score: f64,
+    active: bool,
+    initial: char,
+    role: Role,
+    links: BTreeMap<String, (u16, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Role {
+    Guest,
+    Member(u32),
+    Admin { level: u8, teams: Vec<String> },
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct User {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    #[storable(with = "serde")]
+    profile: Profile,
+    history: Vec<Serde<Role>>,
+}
+
+fn profile(role: Role) -> Profile {
+    Profile {
+        nickname: Some("ada".into()),
+        score: 0.5,
+        active: true,
+        initial: 'λ',
+        role,
+        links: BTreeMap::from([("home".into(), (443, "example.com".into()))]),
+    }
+}
+
+#[test]
+fn store_serde_fields() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("serde_fields_sdb/", true)?;
+
+    let ada = db.store(User {
+        id: 0,
+        name: "Ada".into(),
+        profile: profile(Role::Admin {
+            level: 3,
+            teams: vec!["core".into()],
+        }),
+        history: vec![Serde(Role::Guest), Serde(Role::Member(7))],
+    })?;
+    let grace = db.store(User {
+        id: 0,
+        name: "Grace".into(),
+        profile: Profile {
+            nickname: None,
+            links: BTreeMap::new(),
+            ..profile(Role::Guest)
+        },
+        history: vec![],
+    })?;
+
+    assert_eq!(db.read_all::<User>()?, vec![ada.clone(), grace]);
+    assert_eq!(db.find_by_id::<User>(ada.id)?, Some(ada));
+
+    // the serde fields are opaque without the rust type
+    let schema = db.table_info("User")?.unwrap().schema.unwrap();
+    assert_eq!(
+        schema.field("profile").unwrap().ty,
+        FieldType::Serde(std::any::type_name::<Profile>().to_string())
+    );
+    let rows = db.raw_read_dyn(&db.catalog()?[0])?.entities;
+    assert!(matches!(rows[0].field("profile"), Some(Value::Bytes(_))));
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Storable, Serialize, Deserialize)]
+struct Point {
+    x: i32,
+    label: String,
+    tags: Vec<u64>,
+}
+
+#[test]
+fn same_encoding_as_storable() -> Result<(), Box<dyn Error>> {
+    let point = Point {
+        x: -4,
+        label: "origin".into(),
+        tags: vec![1, 2],
+    };
+    let bytes = encoded(&point);
+    assert_eq!(bytes, point.encoded());
+    assert_eq!(ByteReader::new(&bytes).read::<Point>()?, point);
+    assert_eq!(ByteReader::new(&bytes).read::<Serde<Point>>()?.0, point);
+    Ok(())
+}
+
+#[test]
+fn invalid_data_is_a_decode_error() {
+    let bytes = encoded(&profile(Role::Guest));
+    let err = decoded::<Profile>(ByteReader::new(&bytes[4..bytes.len() - 3])).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Decode);
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Account {
+    #[entity_id(auto_generate)]
+    id: u32,
+    #[storable(with = "serde")]
+    nickname: Option<String>,
+}
+
+#[test]
+fn optional_fields_in_queries() -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new("serde_queries_sdb/", true)?;
+    for nickname in [Some("ada"), None, Some("bob")] {
+        db.store(Account {
+            id: 0,
+            nickname: nickname.map(Into::into),
+        })?;
+    }
+
+    let ids = |accounts: Vec<Account>| accounts.into_iter().map(|a| a.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(db
+            .query_mut::<Account>()?
+            .filter(|a| a.nickname().is_null())
+            .collect_vec()),
+        [2]
+    );
+    assert_eq!(
+        ids(db
+            .query_mut::<Account>()?
+            .filter(|a| a.nickname().is_some())
+            .collect_vec()),
+        [1, 3]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use std::error::Error;
+
+use somedb::{
+    db::{Database, DbError, ErrorKind},
+    entity,
+    sql::SqlOutput,
+    value::Value,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Book {
+    #[entity_id(auto_generate)]
+    id: u32,
+    title: String,
+    year: i16,
+    authors: Vec<String>,
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Loan {
+    #[entity_id(auto_generate)]
+    id: u32,
+    book: u32,
+    returned: Option<i16>,
+}
+
+fn book(title: &str, year: i16, authors: &[&str]) -> Book {
+    Book {
+        id: 0,
+        title: title.into(),
+        year,
+        authors: authors.iter().map(|a| a.to_string()).collect(),
+    }
+}
+
+fn setup(dir: &str) -> Result<Database, Box<dyn Error>> {
+    let mut db = Database::new(dir, true)?;
+    db.store(book("Dune", 1965, &["Herbert"]))?;
+    db.store(book("Neuromancer", 1984, &["Gibson"]))?;
+    db.store(book("Good Omens", 1990, &["Pratchett", "Gaiman"]))?;
+    Ok(db)
+}
+
+/// Runs a `SELECT` and formats the returned rows.
+fn select(db: &mut Database, sql: &str) -> Result<Vec<String>, DbError> {
+    match db.execute(sql)? {
+        SqlOutput::Rows(rows) => Ok(rows.iter().map(|r| r.to_string()).collect()),
+        other => panic!("unexpected output: {other:?}"),
+    }
+}
+
+#[test]
+fn select_rows() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("sql_select_sdb/")?;
+
+    assert_eq!(
+        select(
+            &mut db,
+            "SELECT title FROM Book WHERE year > 1980 ORDER BY year DESC"
+        )?,
+        [r#"{title: "Good Omens"}"#, r#"{title: "Neuromancer"}"#]
+    );
+    assert_eq!(
+        select(&mut db, "select * from Book order by title limit 1")?,
+        [r#"{id: 1, title: "Dune", year: 1965, authors: ["Herbert"]}"#]
+    );
+    assert_eq!(
+        select(
+            &mut db,
+            "SELECT id FROM Book WHERE title LIKE '%o%' AND NOT id = 2"
+        )?,
+        ["{id: 3}"]
+    );
+
+    // the rows can be converted back to entities
+    let SqlOutput::Rows(rows) = db.execute("SELECT * FROM Book WHERE id = 2")? else {
+        panic!("expected rows");
+    };
+    assert_eq!(
+        rows[0].clone().into_entity::<Book>()?,
+        db.find_by_id::<Book>(2)?.unwrap()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn change_rows() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("sql_change_sdb/")?;
+
+    assert_eq!(
+        db.execute(
+            "INSERT INTO Book (title, year, authors) VALUES \
+             ('Emma', 1815, '[\"Austen\"]'), ('Ulysses', 1922, '[]')"
+        )?,
+        SqlOutput::Changed(2)
+    );
+    assert_eq!(db.read_all_ids::<Book>()?, [1, 2, 3, 4, 5]);
+    assert_eq!(
+        db.find_by_id::<Book>(4)?,
+        Some(Book {
+            id: 4,
+            ..book("Emma", 1815, &["Austen"])
+        })
+    );
+
+    // explicit ids are kept and not generated again
+    db.execute("INSERT INTO Book VALUES (10, 'Ubik', 1969, '[\"Dick\"]')")?;
+    assert_eq!(db.store(book("Solaris", 1961, &[]))?.id, 11);
+
+    assert_eq!(
+        db.execute("UPDATE Book SET year = 1970 WHERE year BETWEEN 1960 AND 1970")?,
+        SqlOutput::Changed(3)
+    );
+    assert_eq!(db.find_by_id::<Book>(1)?.unwrap().year, 1970);
+
+    assert_eq!(
+        db.execute("DELETE FROM Book WHERE year < 1950")?,
+        SqlOutput::Changed(2)
+    );
+    assert_eq!(db.read_all_ids::<Book>()?, [1, 2, 3, 10, 11]);
+    assert_eq!(
+        db.execute("DELETE FROM Book WHERE id = 99")?,
+        SqlOutput::Changed(0)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn invalid_statements() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("sql_invalid_sdb/")?;
+
+    let err = |db: &mut Database, sql: &str| db.execute(sql).unwrap_err();
+    assert_eq!(
+        err(&mut db, "SELECT * FROM Shelf").kind(),
+        ErrorKind::TypeNotFound
+    );
+    assert_eq!(
+        err(&mut db, "SELECT isbn FROM Book"),
+        DbError::ParseError("unknown field: isbn".into())
+    );
+    assert_eq!(
+        err(&mut db, "INSERT INTO Book (title) VALUES ('x')"),
+        DbError::ParseError("missing column: year".into())
+    );
+    assert_eq!(
+        err(
+            &mut db,
+            "INSERT INTO Book (title, year, authors) VALUES ('x', 99999, '[]')"
+        ),
+        DbError::ParseError("invalid i16: 99999".into())
+    );
+    assert_eq!(
+        err(&mut db, "INSERT INTO Book VALUES (1, 'x', 1, '[]')").kind(),
+        ErrorKind::IdExists
+    );
+    assert_eq!(
+        err(&mut db, "UPDATE Book SET id = 5"),
+        DbError::ParseError("the id column id can't be changed".into())
+    );
+    assert_eq!(
+        err(&mut db, "UPDATE Book SET title = 5"),
+        DbError::ParseError("expected String for title, found 5".into())
+    );
+    assert_eq!(
+        err(&mut db, "SELEKT * FROM Book"),
+        DbError::ParseError("expected SELECT, INSERT, UPDATE or DELETE, found SELEKT".into())
+    );
+
+    // nothing was changed by the failed statements
+    assert_eq!(db.read_all_ids::<Book>()?, [1, 2, 3]);
+    assert_eq!(
+        db.find_by_id::<Book>(1)?.map(|b| Value::String(b.title)),
+        Some(Value::String("Dune".into()))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn null_values() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("sql_null_sdb/")?;
+    db.store(Loan {
+        id: 0,
+        book: 1,
+        returned: None,
+    })?;
+    db.execute("INSERT INTO Loan (book, returned) VALUES (2, 1999), (3, NULL)")?;
+    assert_eq!(db.find_by_id::<Loan>(2)?.unwrap().returned, Some(1999));
+
+    assert_eq!(
+        select(&mut db, "SELECT * FROM Loan WHERE returned IS NULL")?,
+        [
+            "{id: 1, book: 1, returned: null}",
+            "{id: 3, book: 3, returned: null}"
+        ]
+    );
+    assert_eq!(
+        select(&mut db, "SELECT book FROM Loan WHERE returned IS NOT NULL")?,
+        ["{book: 2}"]
+    );
+    // `None` is never ordered
+    assert_eq!(
+        select(&mut db, "SELECT book FROM Loan WHERE returned < 2000")?,
+        ["{book: 2}"]
+    );
+
+    db.execute("UPDATE Loan SET returned = 2001 WHERE book = 1")?;
+    db.execute("UPDATE Loan SET returned = NULL WHERE book = 2")?;
+    assert_eq!(
+        db.read_all::<Loan>()?
+            .into_iter()
+            .map(|l| l.returned)
+            .collect::<Vec<_>>(),
+        [Some(2001), None, None]
+    );
+
+    assert_eq!(
+        db.execute("UPDATE Loan SET book = NULL")
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Parse
+    );
+    assert_eq!(
+        db.execute("SELECT * FROM Loan WHERE returned = NULL")
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Parse
+    );
+
+    Ok(())
+}
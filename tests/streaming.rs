@@ -0,0 +1,119 @@
+use std::{error::Error, fs};
+
+use somedb::{
+    db::{Database, ErrorKind},
+    entity,
+    gen_query::GenExpr,
+    query::DbIterator,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Reading {
+    #[entity_id(auto_generate)]
+    id: u32,
+    sensor: String,
+    value: u32,
+}
+
+fn reading(sensor: &str, value: u32) -> Reading {
+    Reading {
+        id: 0,
+        sensor: sensor.into(),
+        value,
+    }
+}
+
+fn setup(dir: &str) -> Result<Database, Box<dyn Error>> {
+    let mut db = Database::new(dir, true)?;
+    db.write_all(
+        (1..=100)
+            .map(|i| Reading {
+                id: i,
+                ..reading(if i % 2 == 0 { "even" } else { "odd" }, i * 10)
+            })
+            .collect(),
+    )?;
+    Ok(db)
+}
+
+#[test]
+fn take_from_query() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("streaming_take_sdb/")?;
+
+    let first: Vec<_> = db
+        .query::<Reading>()?
+        .filter(|r| r.sensor == "even")
+        .take(2)
+        .map(|r| r.id)
+        .collect();
+    assert_eq!(first, vec![2, 4]);
+
+    // the dropped query doesn't block writers
+    db.store(reading("odd", 0))?;
+    assert_eq!(db.query::<Reading>()?.count(), 101);
+
+    Ok(())
+}
+
+#[test]
+fn filter_and_save() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("streaming_save_sdb/")?;
+
+    db.query_mut::<Reading>()?
+        .filter(|r| r.value().rem(20).neq(0).land(r.id().neq(1)))
+        .save_to_db()?;
+
+    let ids: Vec<_> = db.query::<Reading>()?.map(|r| r.id).collect();
+    assert_eq!(ids, (3..=99).step_by(2).collect::<Vec<_>>());
+    // ids are not reused after the save
+    assert_eq!(db.store(reading("new", 1))?.id, 101);
+
+    Ok(())
+}
+
+#[test]
+fn corruption_ends_the_stream() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("streaming_corrupt_sdb/")?;
+    let path = db.table_info("Reading")?.unwrap().path;
+
+    // change a sensor name without changing any lengths
+    let mut data = fs::read(&path)?;
+    let at = data.windows(4).position(|w| w == b"even").unwrap();
+    data[at] = b'o';
+    fs::write(&path, data)?;
+
+    // all entities can be decoded, but the checksum of the second one doesn't match
+    let mut query = db.query::<Reading>()?;
+    assert_eq!(query.by_ref().count(), 1);
+    assert_eq!(query.error().unwrap().kind(), ErrorKind::ChecksumMismatch);
+    drop(query);
+
+    // rows are checked before they are returned, not only at the end
+    let even: Vec<_> = db
+        .query::<Reading>()?
+        .filter(|r| r.sensor == "even")
+        .take(1)
+        .collect();
+    assert!(even.is_empty());
+
+    // saving the query would write the corrupted data
+    let err = db.query_mut::<Reading>()?.save_to_db().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ChecksumMismatch);
+
+    Ok(())
+}
+
+#[test]
+fn truncated_table() -> Result<(), Box<dyn Error>> {
+    let db = setup("streaming_truncated_sdb/")?;
+    let path = db.table_info("Reading")?.unwrap().path;
+    let data = fs::read(&path)?;
+    fs::write(&path, &data[..data.len() / 2])?;
+
+    // the rows can't be checked without the checksums at the end
+    let err = db.query::<Reading>().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Decode);
+
+    Ok(())
+}
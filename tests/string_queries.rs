@@ -0,0 +1,122 @@
+use std::error::Error;
+
+use somedb::{
+    db::Database,
+    entity,
+    gen_query::{GenExpr, lit},
+    query::DbIterator,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Contact {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    email: String,
+}
+
+fn setup(dir: &str) -> Result<Database, Box<dyn Error>> {
+    let mut db = Database::new(dir, true)?;
+    for (name, email) in [
+        ("Ada Lovelace", "ada@example.com"),
+        ("Alan Turing", "alan@example.org"),
+        ("Grace Hopper", "grace@navy.mil"),
+        ("ada", "ada_2@example.com"),
+    ] {
+        db.store(Contact {
+            id: 0,
+            name: name.into(),
+            email: email.into(),
+        })?;
+    }
+    Ok(db)
+}
+
+fn names(query: impl DbIterator<Item = Contact>) -> Vec<String> {
+    query.collect_vec().into_iter().map(|c| c.name).collect()
+}
+
+#[test]
+fn string_operations() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("string_queries_sdb/")?;
+
+    let found = names(
+        db.query_mut::<Contact>()?
+            .filter(|c| c.name().contains("a")),
+    );
+    assert_eq!(
+        found,
+        ["Ada Lovelace", "Alan Turing", "Grace Hopper", "ada"]
+    );
+
+    let found = names(
+        db.query_mut::<Contact>()?
+            .filter(|c| c.name().starts_with("A").land(c.email().ends_with(".org"))),
+    );
+    assert_eq!(found, ["Alan Turing"]);
+
+    let found = names(db.query_mut::<Contact>()?.filter(|c| c.name().len().eq(3)));
+    assert_eq!(found, ["ada"]);
+
+    let found = names(
+        db.query_mut::<Contact>()?
+            .filter(|c| c.name().eq_ignore_case("ADA LOVELACE")),
+    );
+    assert_eq!(found, ["Ada Lovelace"]);
+
+    // `_` matches any character unless it is escaped
+    let found = names(
+        db.query_mut::<Contact>()?
+            .filter(|c| c.email().like("ada_%")),
+    );
+    assert_eq!(found, ["Ada Lovelace", "ada"]);
+    let found = names(
+        db.query_mut::<Contact>()?
+            .filter(|c| c.email().like("ada\\_%")),
+    );
+    assert_eq!(found, ["ada"]);
+
+    Ok(())
+}
+
+#[test]
+fn string_constants() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("string_constants_sdb/")?;
+
+    let wanted = String::from("Grace Hopper");
+    let found = names(
+        db.query_mut::<Contact>()?
+            .filter(|c| c.name().eq(lit(wanted.clone()))),
+    );
+    assert_eq!(found, ["Grace Hopper"]);
+
+    let found = names(db.query_mut::<Contact>()?.filter(|c| c.name().neq("ada")));
+    assert_eq!(found.len(), 3);
+
+    // both sides can be fields
+    let found = names(db.query_mut::<Contact>()?.filter(|c| {
+        c.email()
+            .contains(lit("@example.".to_string()))
+            .land(c.email().starts_with(c.name()))
+    }));
+    assert_eq!(found, ["ada"]);
+
+    Ok(())
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_matching() -> Result<(), Box<dyn Error>> {
+    use regex::Regex;
+
+    let mut db = setup("string_regex_sdb/")?;
+
+    let found = names(db.query_mut::<Contact>()?.filter(|c| {
+        c.email()
+            .matches(Regex::new(r"^\w+@example\.(com|org)$").unwrap())
+    }));
+    assert_eq!(found, ["Ada Lovelace", "Alan Turing", "ada"]);
+
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use std::error::Error;
+
+use somedb::{
+    db::Database,
+    entity,
+    gen_query::{GenExpr, exists, subquery},
+    query::DbIterator,
+};
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Customer {
+    #[entity_id(auto_generate)]
+    id: u32,
+    name: String,
+    tier: u8,
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Order {
+    #[entity_id(auto_generate)]
+    id: u32,
+    customer_id: u32,
+    total: u32,
+}
+
+#[entity]
+#[derive(Debug, PartialEq)]
+struct Refund {
+    #[entity_id(auto_generate)]
+    id: u32,
+    order_id: u32,
+}
+
+fn setup(dir: &str) -> Result<Database, Box<dyn Error>> {
+    let mut db = Database::new(dir, true)?;
+    for (name, tier) in [("ada", 3), ("bob", 1), ("cy", 2)] {
+        db.store(Customer {
+            id: 0,
+            name: name.to_string(),
+            tier,
+        })?;
+    }
+    for (customer_id, total) in [(1, 120), (2, 15), (1, 30), (3, 80), (2, 300)] {
+        db.store(Order {
+            id: 0,
+            customer_id,
+            total,
+        })?;
+    }
+    Ok(db)
+}
+
+fn ids<T: somedb::entity::Entity>(rows: Vec<T>) -> Vec<T::Id> {
+    rows.iter().map(|r| r.get_id()).collect()
+}
+
+#[test]
+fn in_query() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("subquery_in_sdb/")?;
+
+    let orders = db
+        .query_mut::<Order>()?
+        .filter(|o| {
+            o.customer_id()
+                .in_query(subquery::<Customer>().filter(|c| c.tier().gte(2)).ids())
+        })
+        .collect_vec();
+    assert_eq!(ids(orders), [1, 3, 4]);
+
+    // customers with an order over 100
+    let customers = db
+        .query_mut::<Customer>()?
+        .filter(|c| {
+            c.id().in_query(
+                subquery::<Order>()
+                    .filter(|o| o.total().gt(100))
+                    .select(|o| o.customer_id()),
+            )
+        })
+        .collect_vec();
+    assert_eq!(ids(customers), [1, 2]);
+
+    // customers without orders over 100
+    let customers = db
+        .query_mut::<Customer>()?
+        .filter(|c| {
+            c.id()
+                .in_query(
+                    subquery::<Order>()
+                        .filter(|o| o.total().gt(100))
+                        .select(|o| o.customer_id()),
+                )
+                .not()
+        })
+        .collect_vec();
+    assert_eq!(ids(customers), [3]);
+
+    // subqueries can be nested
+    let orders = db
+        .query_mut::<Order>()?
+        .filter(|o| {
+            o.customer_id().in_query(
+                subquery::<Customer>()
+                    .filter(|c| {
+                        c.id().in_query(
+                            subquery::<Order>()
+                                .filter(|o| o.total().lt(20))
+                                .select(|o| o.customer_id()),
+                        )
+                    })
+                    .ids(),
+            )
+        })
+        .collect_vec();
+    assert_eq!(ids(orders), [2, 5]);
+
+    Ok(())
+}
+
+#[test]
+fn exists_queries() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("subquery_exists_sdb/")?;
+
+    let orders = db
+        .query_mut::<Order>()?
+        .filter(|_| exists(subquery::<Customer>().filter(|c| c.tier().gt(2))))
+        .collect_vec();
+    assert_eq!(orders.len(), 5);
+
+    let orders = db
+        .query_mut::<Order>()?
+        .filter(|o| {
+            o.total()
+                .gt(50)
+                .land(exists(subquery::<Customer>().filter(|c| c.tier().gt(3))))
+        })
+        .collect_vec();
+    assert!(orders.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn missing_tables_are_empty() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("subquery_missing_sdb/")?;
+
+    let orders = db
+        .query_mut::<Order>()?
+        .filter(|_| exists(subquery::<Refund>()).not())
+        .collect_vec();
+    assert_eq!(orders.len(), 5);
+
+    let orders = db
+        .query_mut::<Order>()?
+        .filter(|o| {
+            o.id()
+                .in_query(subquery::<Refund>().select(|r| r.order_id()))
+        })
+        .collect_vec();
+    assert!(orders.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn save_with_subquery() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("subquery_save_sdb/")?;
+
+    // keep only the orders of customers with a tier of at least 2
+    db.query_mut::<Order>()?
+        .filter(|o| {
+            o.customer_id()
+                .in_query(subquery::<Customer>().filter(|c| c.tier().gte(2)).ids())
+        })
+        .save_to_db()?;
+    assert_eq!(ids(db.query::<Order>()?.collect()), [1, 3, 4]);
+
+    Ok(())
+}
+
+#[test]
+fn explain_subqueries() -> Result<(), Box<dyn Error>> {
+    let mut db = setup("subquery_explain_sdb/")?;
+
+    let query = db.query_mut::<Order>()?.filter(|o| {
+        o.customer_id()
+            .in_query(subquery::<Customer>().filter(|c| c.tier().gte(2)).ids())
+    });
+    assert_eq!(
+        query.explain().to_string(),
+        "filter customer_id.in_query(select id from Customer where tier >= <u8>), ~1 rows\n  \
+           full scan of Order, 5 rows\n"
+    );
+
+    let query = db
+        .query_mut::<Order>()?
+        .filter(|_| exists(subquery::<Refund>()));
+    assert_eq!(
+        query.explain().to_string(),
+        "filter (select * from Refund where true).exists(), ~3 rows\n  \
+           full scan of Order, 5 rows\n"
+    );
+
+    Ok(())
+}